@@ -0,0 +1,307 @@
+use super::virtual_path;
+use crate::error::MekuriError;
+use std::any::Any;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+/// Number of archives kept open when no capacity is given.
+const DEFAULT_CAPACITY: usize = 8;
+/// Total size of page bytes kept in memory when no budget is given.
+const DEFAULT_PAGE_BUDGET: usize = 256 * 1024 * 1024;
+
+/// A cached handle, type-erased so each backend can store its own handle type.
+type Handle = Arc<dyn Any + Send + Sync>;
+
+struct Slot {
+    path: String,
+    modified: SystemTime,
+    handle: Handle,
+}
+
+/// A page read from an archive, kept until the byte budget pushes it out.
+struct Page {
+    path: String,
+    entry_name: String,
+    modified: SystemTime,
+    data: Arc<Vec<u8>>,
+}
+
+#[derive(Default)]
+struct Pages {
+    /// Most recently used first
+    entries: VecDeque<Page>,
+    total_bytes: usize,
+}
+
+/// Open archive handles and recently read pages shared across page requests,
+/// both evicted least-recently-used first.
+///
+/// Entries are keyed by path and modification time, so an archive that changes on disk
+/// is reopened instead of being read through a stale handle. Cloning shares the cache.
+#[derive(Clone)]
+pub struct ArchiveCache {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    capacity: usize,
+    page_budget: usize,
+    /// Most recently used first
+    slots: Mutex<VecDeque<Slot>>,
+    pages: Mutex<Pages>,
+}
+
+impl Default for ArchiveCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_CAPACITY, DEFAULT_PAGE_BUDGET)
+    }
+}
+
+/// Read the modification time used to key cache entries. Nested archives kept in memory
+/// take the time of the file they were extracted from.
+pub(super) fn modified_time(path: &str) -> Result<SystemTime, MekuriError> {
+    if let Some((_, modified)) = virtual_path::in_memory(path) {
+        return Ok(modified);
+    }
+    std::fs::metadata(path)
+        .and_then(|m| m.modified())
+        .map_err(|e| MekuriError::io("Failed to open archive", e))
+}
+
+impl ArchiveCache {
+    /// Create a cache holding up to `capacity` open archives and `page_budget` bytes of pages.
+    pub fn new(capacity: usize, page_budget: usize) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                capacity: capacity.max(1),
+                page_budget,
+                slots: Mutex::new(VecDeque::new()),
+                pages: Mutex::new(Pages::default()),
+            }),
+        }
+    }
+
+    /// Return a previously read page, if it is cached for this version of the archive.
+    pub(super) fn cached_page(
+        &self,
+        path: &str,
+        modified: SystemTime,
+        entry_name: &str,
+    ) -> Option<Arc<Vec<u8>>> {
+        let mut pages = self.inner.pages.lock().ok()?;
+        let index = pages
+            .entries
+            .iter()
+            .position(|page| page.path == path && page.entry_name == entry_name)?;
+        let page = pages.entries.remove(index)?;
+        if page.modified != modified {
+            pages.total_bytes -= page.data.len();
+            return None;
+        }
+        let data = page.data.clone();
+        pages.entries.push_front(page);
+        Some(data)
+    }
+
+    /// Keep a page in memory, evicting the oldest pages to stay within the byte budget.
+    /// Pages larger than the whole budget are not kept.
+    pub(super) fn store_page(
+        &self,
+        path: &str,
+        modified: SystemTime,
+        entry_name: &str,
+        data: Arc<Vec<u8>>,
+    ) {
+        if data.len() > self.inner.page_budget {
+            return;
+        }
+        let Ok(mut pages) = self.inner.pages.lock() else {
+            return;
+        };
+
+        if let Some(index) = pages
+            .entries
+            .iter()
+            .position(|page| page.path == path && page.entry_name == entry_name)
+        {
+            if let Some(old) = pages.entries.remove(index) {
+                pages.total_bytes -= old.data.len();
+            }
+        }
+
+        pages.total_bytes += data.len();
+        pages.entries.push_front(Page {
+            path: path.to_string(),
+            entry_name: entry_name.to_string(),
+            modified,
+            data,
+        });
+        while pages.total_bytes > self.inner.page_budget {
+            match pages.entries.pop_back() {
+                Some(old) => pages.total_bytes -= old.data.len(),
+                None => break,
+            }
+        }
+    }
+
+    /// Return the cached handle for an archive, opening it with `open` on a miss.
+    ///
+    /// The handle is wrapped in its own mutex so reads from different archives do not
+    /// block each other; the cache lock is not held while opening.
+    pub(super) fn get_or_open<T, F>(
+        &self,
+        path: &str,
+        open: F,
+    ) -> Result<Arc<Mutex<T>>, MekuriError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&str) -> Result<T, MekuriError>,
+    {
+        let modified = modified_time(path)?;
+
+        if let Some(handle) = self.lookup::<T>(path, modified) {
+            return Ok(handle);
+        }
+
+        let handle = Arc::new(Mutex::new(open(path)?));
+        self.insert(path, modified, handle.clone());
+        Ok(handle)
+    }
+
+    fn lookup<T: Send + 'static>(&self, path: &str, modified: SystemTime) -> Option<Arc<Mutex<T>>> {
+        let mut slots = self.inner.slots.lock().ok()?;
+        let index = slots.iter().position(|slot| slot.path == path)?;
+        let slot = slots.remove(index)?;
+        if slot.modified != modified {
+            // The file changed on disk; drop the stale handle
+            return None;
+        }
+        let handle = slot.handle.clone().downcast::<Mutex<T>>().ok();
+        slots.push_front(slot);
+        handle
+    }
+
+    fn insert<T: Send + 'static>(&self, path: &str, modified: SystemTime, handle: Arc<Mutex<T>>) {
+        let Ok(mut slots) = self.inner.slots.lock() else {
+            return;
+        };
+        // Another thread may have opened the same archive in the meantime
+        slots.retain(|slot| slot.path != path);
+        slots.push_front(Slot {
+            path: path.to_string(),
+            modified,
+            handle,
+        });
+        slots.truncate(self.inner.capacity);
+    }
+
+    /// Drop the open handle of an archive so the next read reopens it, e.g. with a
+    /// newly entered password.
+    pub(super) fn forget_handle(&self, path: &str) {
+        if let Ok(mut slots) = self.inner.slots.lock() {
+            slots.retain(|slot| slot.path != path);
+        }
+    }
+
+    #[cfg(test)]
+    fn cached_paths(&self) -> Vec<String> {
+        let slots = self.inner.slots.lock().unwrap();
+        slots.iter().map(|slot| slot.path.clone()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn touch(dir: &tempfile::TempDir, name: &str) -> String {
+        let path = dir.path().join(name);
+        std::fs::write(&path, b"data").unwrap();
+        path.to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn test_get_or_open_reuses_handle() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = touch(&dir, "a.zip");
+        let cache = ArchiveCache::default();
+
+        let first = cache.get_or_open(&path, |_| Ok(1u32)).unwrap();
+        let second = cache
+            .get_or_open::<u32, _>(&path, |_| panic!("should not reopen"))
+            .unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+    }
+
+    #[test]
+    fn test_evicts_least_recently_used() {
+        let dir = tempfile::tempdir().unwrap();
+        let [a, b, c] = ["a.zip", "b.zip", "c.zip"].map(|name| touch(&dir, name));
+        let cache = ArchiveCache::new(2, DEFAULT_PAGE_BUDGET);
+
+        cache.get_or_open(&a, |_| Ok(())).unwrap();
+        cache.get_or_open(&b, |_| Ok(())).unwrap();
+        // Touch `a` so `b` becomes the oldest entry
+        cache.get_or_open(&a, |_| Ok(())).unwrap();
+        cache.get_or_open(&c, |_| Ok(())).unwrap();
+
+        assert_eq!(cache.cached_paths(), vec![c, a]);
+    }
+
+    #[test]
+    fn test_reopens_when_file_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = touch(&dir, "a.zip");
+        let cache = ArchiveCache::default();
+
+        cache.get_or_open(&path, |_| Ok(1u32)).unwrap();
+        let file = std::fs::File::options().write(true).open(&path).unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(60))
+            .unwrap();
+
+        let reopened = cache.get_or_open(&path, |_| Ok(2u32)).unwrap();
+        assert_eq!(*reopened.lock().unwrap(), 2);
+    }
+
+    #[test]
+    fn test_missing_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = ArchiveCache::default();
+
+        let missing = dir.path().join("missing.zip");
+        let result = cache.get_or_open(missing.to_str().unwrap(), |_| Ok(()));
+        assert!(matches!(result, Err(MekuriError::NotFound(_))));
+        assert!(cache.cached_paths().is_empty());
+    }
+
+    #[test]
+    fn test_pages_stay_within_budget() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = touch(&dir, "a.zip");
+        let modified = modified_time(&path).unwrap();
+        let cache = ArchiveCache::new(1, 10);
+
+        cache.store_page(&path, modified, "1.jpg", Arc::new(vec![1; 4]));
+        cache.store_page(&path, modified, "2.jpg", Arc::new(vec![2; 4]));
+        // Reading page 1 makes page 2 the oldest
+        assert!(cache.cached_page(&path, modified, "1.jpg").is_some());
+        cache.store_page(&path, modified, "3.jpg", Arc::new(vec![3; 4]));
+        cache.store_page(&path, modified, "huge.jpg", Arc::new(vec![0; 11]));
+
+        assert!(cache.cached_page(&path, modified, "2.jpg").is_none());
+        assert!(cache.cached_page(&path, modified, "huge.jpg").is_none());
+        assert_eq!(
+            *cache.cached_page(&path, modified, "1.jpg").unwrap(),
+            vec![1; 4]
+        );
+        assert_eq!(
+            *cache.cached_page(&path, modified, "3.jpg").unwrap(),
+            vec![3; 4]
+        );
+
+        let later = modified + Duration::from_secs(1);
+        assert!(cache.cached_page(&path, later, "1.jpg").is_none());
+    }
+}
@@ -0,0 +1,235 @@
+use crate::error::MekuriError;
+use serde::Serialize;
+use std::path::Path;
+use std::str::FromStr;
+
+/// File name of the ComicRack metadata file, matched case-insensitively.
+const COMIC_INFO_NAME: &str = "comicinfo.xml";
+
+/// Whether the archive declares itself as manga, from the ComicInfo `Manga` field.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub enum Manga {
+    #[default]
+    Unknown,
+    No,
+    Yes,
+    /// Manga read right to left
+    YesAndRightToLeft,
+}
+
+/// One `<Page>` element of ComicInfo `<Pages>`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct PageMetadata {
+    /// Index of the page among the archive's images
+    pub image: usize,
+    /// ComicInfo page type such as `FrontCover`, `Story` or `Deleted`
+    pub page_type: Option<String>,
+    pub double_page: bool,
+    pub image_width: Option<u32>,
+    pub image_height: Option<u32>,
+}
+
+/// Metadata read from an archive's `ComicInfo.xml`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ArchiveMetadata {
+    pub title: Option<String>,
+    pub series: Option<String>,
+    /// Issue number; free text in ComicInfo (e.g. "1", "1.5", "Extra")
+    pub number: Option<String>,
+    pub volume: Option<i32>,
+    pub count: Option<i32>,
+    pub summary: Option<String>,
+    pub year: Option<i32>,
+    pub month: Option<u32>,
+    pub day: Option<u32>,
+    pub writer: Option<String>,
+    pub penciller: Option<String>,
+    pub publisher: Option<String>,
+    pub genre: Option<String>,
+    pub tags: Option<String>,
+    pub language_iso: Option<String>,
+    pub page_count: Option<usize>,
+    pub manga: Manga,
+    pub pages: Vec<PageMetadata>,
+}
+
+/// Whether an archive entry is a ComicInfo.xml file.
+pub fn is_comic_info(name: &str) -> bool {
+    Path::new(name)
+        .file_name()
+        .and_then(|n| n.to_str())
+        .is_some_and(|n| n.eq_ignore_ascii_case(COMIC_INFO_NAME))
+}
+
+/// Pick the ComicInfo.xml to read, preferring one at the archive root over nested ones.
+pub fn find_comic_info<'a>(names: impl Iterator<Item = &'a str>) -> Option<&'a str> {
+    names
+        .filter(|name| is_comic_info(name) && !name.contains("__MACOSX"))
+        .min_by_key(|name| name.matches('/').count())
+}
+
+/// Parse the contents of a ComicInfo.xml file.
+///
+/// Unknown elements are ignored and malformed values are treated as missing, since the
+/// files are written by many different taggers.
+pub fn parse(data: &[u8]) -> Result<ArchiveMetadata, MekuriError> {
+    let text = std::str::from_utf8(data)
+        .map_err(|e| MekuriError::Corrupt(format!("Failed to parse ComicInfo.xml: {e}")))?
+        .trim_start_matches('\u{feff}');
+    let document = roxmltree::Document::parse(text)
+        .map_err(|e| MekuriError::Corrupt(format!("Failed to parse ComicInfo.xml: {e}")))?;
+
+    let root = document.root_element();
+    if !root.has_tag_name("ComicInfo") {
+        return Err(MekuriError::Corrupt(format!(
+            "Failed to parse ComicInfo.xml: unexpected root element <{}>",
+            root.tag_name().name()
+        )));
+    }
+
+    let mut metadata = ArchiveMetadata::default();
+    for node in root.children().filter(|n| n.is_element()) {
+        let text = node.text().map(str::trim).filter(|t| !t.is_empty());
+        let value = || text.map(str::to_string);
+
+        match node.tag_name().name() {
+            "Title" => metadata.title = value(),
+            "Series" => metadata.series = value(),
+            "Number" => metadata.number = value(),
+            "Volume" => metadata.volume = parse_number(text),
+            "Count" => metadata.count = parse_number(text),
+            "Summary" => metadata.summary = value(),
+            "Year" => metadata.year = parse_number(text),
+            "Month" => metadata.month = parse_number(text),
+            "Day" => metadata.day = parse_number(text),
+            "Writer" => metadata.writer = value(),
+            "Penciller" => metadata.penciller = value(),
+            "Publisher" => metadata.publisher = value(),
+            "Genre" => metadata.genre = value(),
+            "Tags" => metadata.tags = value(),
+            "LanguageISO" => metadata.language_iso = value(),
+            "PageCount" => metadata.page_count = parse_number(text),
+            "Manga" => metadata.manga = parse_manga(text.unwrap_or("")),
+            "Pages" => metadata.pages = parse_pages(node),
+            _ => {}
+        }
+    }
+    Ok(metadata)
+}
+
+fn parse_number<T: FromStr>(text: Option<&str>) -> Option<T> {
+    text.and_then(|t| t.parse().ok())
+}
+
+fn parse_manga(value: &str) -> Manga {
+    match value {
+        "No" => Manga::No,
+        "Yes" => Manga::Yes,
+        "YesAndRightToLeft" => Manga::YesAndRightToLeft,
+        _ => Manga::Unknown,
+    }
+}
+
+fn parse_pages(pages: roxmltree::Node) -> Vec<PageMetadata> {
+    pages
+        .children()
+        .filter(|n| n.has_tag_name("Page"))
+        .filter_map(|page| {
+            // A page without a valid image index cannot be matched to an entry
+            let image = page.attribute("Image")?.trim().parse().ok()?;
+            let number = |name| page.attribute(name).and_then(|v| v.trim().parse().ok());
+            Some(PageMetadata {
+                image,
+                page_type: page.attribute("Type").map(str::to_string),
+                double_page: page
+                    .attribute("DoublePage")
+                    .is_some_and(|v| v.eq_ignore_ascii_case("true")),
+                image_width: number("ImageWidth"),
+                image_height: number("ImageHeight"),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<ComicInfo xmlns:xsd="http://www.w3.org/2001/XMLSchema" xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance">
+  <Title>The Beginning</Title>
+  <Series>Mekuri Monogatari</Series>
+  <Number>1</Number>
+  <Volume>2</Volume>
+  <Year>2024</Year>
+  <Writer>Someone</Writer>
+  <LanguageISO>ja</LanguageISO>
+  <PageCount>3</PageCount>
+  <Manga>YesAndRightToLeft</Manga>
+  <Pages>
+    <Page Image="0" Type="FrontCover" ImageWidth="1200" ImageHeight="1800" />
+    <Page Image="1" DoublePage="True" />
+    <Page Image="2" Type="Deleted" />
+  </Pages>
+</ComicInfo>"#;
+
+    #[test]
+    fn test_parse_sample() {
+        let metadata = parse(SAMPLE.as_bytes()).unwrap();
+        assert_eq!(metadata.title.as_deref(), Some("The Beginning"));
+        assert_eq!(metadata.series.as_deref(), Some("Mekuri Monogatari"));
+        assert_eq!(metadata.number.as_deref(), Some("1"));
+        assert_eq!(metadata.volume, Some(2));
+        assert_eq!(metadata.year, Some(2024));
+        assert_eq!(metadata.writer.as_deref(), Some("Someone"));
+        assert_eq!(metadata.language_iso.as_deref(), Some("ja"));
+        assert_eq!(metadata.page_count, Some(3));
+        assert_eq!(metadata.manga, Manga::YesAndRightToLeft);
+        assert_eq!(metadata.summary, None);
+
+        assert_eq!(metadata.pages.len(), 3);
+        assert_eq!(metadata.pages[0].page_type.as_deref(), Some("FrontCover"));
+        assert_eq!(metadata.pages[0].image_width, Some(1200));
+        assert!(metadata.pages[1].double_page);
+        assert_eq!(metadata.pages[2].page_type.as_deref(), Some("Deleted"));
+    }
+
+    #[test]
+    fn test_parse_tolerates_bad_values() {
+        let xml = "\u{feff}<ComicInfo><Volume>two</Volume><Manga>maybe</Manga><Series> </Series>\
+                   <Pages><Page Type=\"Story\" /><Page Image=\"4\" /></Pages></ComicInfo>";
+        let metadata = parse(xml.as_bytes()).unwrap();
+        assert_eq!(metadata.volume, None);
+        assert_eq!(metadata.manga, Manga::Unknown);
+        assert_eq!(metadata.series, None);
+        assert_eq!(metadata.pages.len(), 1);
+        assert_eq!(metadata.pages[0].image, 4);
+    }
+
+    #[test]
+    fn test_parse_rejects_malformed_xml() {
+        assert!(matches!(
+            parse(b"<ComicInfo><Title>"),
+            Err(MekuriError::Corrupt(_))
+        ));
+        assert!(parse(b"<Other />")
+            .unwrap_err()
+            .message()
+            .contains("unexpected root"));
+    }
+
+    #[test]
+    fn test_find_comic_info_prefers_root() {
+        let names = [
+            "chapter/ComicInfo.xml",
+            "__MACOSX/ComicInfo.xml",
+            "comicinfo.XML",
+            "001.jpg",
+        ];
+        assert_eq!(
+            find_comic_info(names.iter().copied()),
+            Some("comicinfo.XML")
+        );
+        assert_eq!(find_comic_info(["001.jpg"].iter().copied()), None);
+    }
+}
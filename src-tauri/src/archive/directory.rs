@@ -0,0 +1,218 @@
+use super::image_header::read_dimensions;
+use super::sort::ListedEntry;
+use super::{is_archive_file, is_image_file, EntryInfo, Listing};
+use crate::error::MekuriError;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// List the names of visible regular files directly inside a folder.
+fn file_names(dir_path: &str) -> Result<Vec<String>, MekuriError> {
+    let entries =
+        std::fs::read_dir(dir_path).map_err(|e| MekuriError::io("Failed to read directory", e))?;
+
+    Ok(entries
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            // Skip hidden files (including macOS `._` resource forks)
+            if name.starts_with('.') || !entry.file_type().ok()?.is_file() {
+                return None;
+            }
+            Some(name)
+        })
+        .collect())
+}
+
+/// Resolve an entry name to a path inside the folder, rejecting anything that is
+/// not a plain file name so callers cannot escape the folder. Only the files the folder
+/// is listed with are entries: visible ones that `is_entry` accepts by name, so that no
+/// other file in it (`.env`, keys, notes) can be read through it.
+fn entry_path(
+    dir_path: &str,
+    entry_name: &str,
+    is_entry: fn(&str) -> bool,
+) -> Result<PathBuf, MekuriError> {
+    let is_plain_name = Path::new(entry_name).file_name().and_then(|n| n.to_str())
+        == Some(entry_name)
+        && !entry_name.contains(['/', '\\']);
+    if !is_plain_name || entry_name.starts_with('.') || !is_entry(entry_name) {
+        return Err(MekuriError::NotFound(format!(
+            "Entry not found: {entry_name}"
+        )));
+    }
+    let path = Path::new(dir_path).join(entry_name);
+    if !path.is_file() {
+        return Err(MekuriError::NotFound(format!(
+            "Entry not found: {entry_name}"
+        )));
+    }
+    Ok(path)
+}
+
+/// Check whether a folder directly contains at least one image file.
+pub fn has_images(dir_path: &Path) -> bool {
+    let Ok(entries) = std::fs::read_dir(dir_path) else {
+        return false;
+    };
+    entries.flatten().any(|entry| {
+        let name = entry.file_name().to_string_lossy().to_string();
+        !name.starts_with('.')
+            && is_image_file(&name)
+            && entry.file_type().map(|t| t.is_file()).unwrap_or(false)
+    })
+}
+
+/// List image file names directly inside a folder, sorted by natural order.
+fn image_names(dir_path: &str) -> Result<Vec<String>, MekuriError> {
+    let mut names: Vec<String> = file_names(dir_path)?
+        .into_iter()
+        .filter(|name| is_image_file(name))
+        .collect();
+
+    names.sort_by(|a, b| natord::compare(a, b));
+    Ok(names)
+}
+
+/// List the images directly inside a folder, numbered in natural order. A folder has no
+/// stored order, and its names are unique, so the number is only informational.
+pub fn list_entries(dir_path: &str) -> Result<Vec<ListedEntry>, MekuriError> {
+    Ok(image_names(dir_path)?
+        .into_iter()
+        .enumerate()
+        .map(|(index, name)| {
+            let modified = std::fs::metadata(Path::new(dir_path).join(&name))
+                .and_then(|m| m.modified())
+                .ok()
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map(|since| since.as_secs());
+            ListedEntry::new(index, name, modified)
+        })
+        .collect())
+}
+
+/// List the images and archives directly in a folder.
+pub fn analyze_contents(dir_path: &str) -> Result<Listing, MekuriError> {
+    Ok(Listing::new(file_names(dir_path)?))
+}
+
+/// Resolve an archive inside a folder. It is already on disk, so no extraction is needed.
+pub fn extract_nested_archive(dir_path: &str, nested_name: &str) -> Result<String, MekuriError> {
+    entry_path(dir_path, nested_name, is_archive_file)
+        .map(|path| path.to_string_lossy().to_string())
+}
+
+/// Read the raw bytes of a single image from a folder.
+pub fn read_entry(dir_path: &str, entry_name: &str) -> Result<Vec<u8>, MekuriError> {
+    let path = entry_path(dir_path, entry_name, is_image_file)?;
+    std::fs::read(&path).map_err(|e| MekuriError::io("Failed to read entry", e))
+}
+
+/// Sizes and dimensions of the images in a folder, reading only the start of each file.
+pub fn image_info(dir_path: &str) -> Result<HashMap<String, EntryInfo>, MekuriError> {
+    Ok(image_names(dir_path)?
+        .into_iter()
+        .filter_map(|name| {
+            let file = std::fs::File::open(Path::new(dir_path).join(&name)).ok()?;
+            let size = file.metadata().ok()?.len();
+            let info = EntryInfo::new(None, size, read_dimensions(file));
+            Some((name, info))
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::archive::{get_image_base64, list_images, ArchiveCache};
+    use base64::Engine;
+    use std::fs;
+
+    #[test]
+    fn test_list_images_natural_order() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["10.jpg", "2.jpg", "1.png", "notes.txt", ".hidden.jpg"] {
+            fs::write(dir.path().join(name), b"data").unwrap();
+        }
+        fs::create_dir(dir.path().join("sub.jpg")).unwrap();
+
+        let result = list_images(&ArchiveCache::default(), dir.path().to_str().unwrap()).unwrap();
+        assert_eq!(result, vec!["1.png", "2.jpg", "10.jpg"]);
+    }
+
+    #[test]
+    fn test_get_image_base64_reads_file() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("page.png"), b"fake-png").unwrap();
+
+        let result = get_image_base64(
+            &ArchiveCache::default(),
+            dir.path().to_str().unwrap(),
+            "page.png",
+        )
+        .unwrap();
+        let b64_part = result.strip_prefix("data:image/png;base64,").unwrap();
+        let decoded = base64::engine::general_purpose::STANDARD
+            .decode(b64_part)
+            .unwrap();
+        assert_eq!(decoded, b"fake-png");
+    }
+
+    #[test]
+    fn test_get_image_base64_rejects_paths_outside_folder() {
+        let root = tempfile::tempdir().unwrap();
+        let book = root.path().join("book");
+        fs::create_dir(&book).unwrap();
+        fs::write(root.path().join("secret.jpg"), b"secret").unwrap();
+
+        let book_path = book.to_str().unwrap();
+        for name in ["../secret.jpg", "/etc/passwd", "sub/page.jpg", ""] {
+            let result = get_image_base64(&ArchiveCache::default(), book_path, name);
+            assert!(matches!(result, Err(MekuriError::NotFound(_))), "{name}");
+        }
+    }
+
+    #[test]
+    fn test_read_entry_only_reads_listed_files() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in [".secret", "notes.txt", ".hidden.jpg", "vol1.cbz", "001.jpg"] {
+            fs::write(dir.path().join(name), b"data").unwrap();
+        }
+        let dir_path = dir.path().to_str().unwrap();
+
+        for name in [".secret", "notes.txt", ".hidden.jpg", "vol1.cbz"] {
+            let result = read_entry(dir_path, name);
+            assert!(matches!(result, Err(MekuriError::NotFound(_))), "{name}");
+        }
+        assert_eq!(read_entry(dir_path, "001.jpg").unwrap(), b"data");
+        assert!(matches!(
+            extract_nested_archive(dir_path, "notes.txt"),
+            Err(MekuriError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_analyze_contents_and_nested_archive_path() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("vol2.cbz"), b"").unwrap();
+        fs::write(dir.path().join("vol1.cbz"), b"").unwrap();
+        let dir_path = dir.path().to_str().unwrap();
+
+        let listing = analyze_contents(dir_path).unwrap();
+        assert!(listing.images.is_empty());
+        assert_eq!(listing.archives, vec!["vol1.cbz", "vol2.cbz"]);
+
+        let resolved = extract_nested_archive(dir_path, "vol1.cbz").unwrap();
+        assert_eq!(resolved, dir.path().join("vol1.cbz").to_string_lossy());
+    }
+
+    #[test]
+    fn test_has_images() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(!has_images(dir.path()));
+        fs::write(dir.path().join("notes.txt"), b"").unwrap();
+        assert!(!has_images(dir.path()));
+        fs::write(dir.path().join("001.JPG"), b"").unwrap();
+        assert!(has_images(dir.path()));
+    }
+}
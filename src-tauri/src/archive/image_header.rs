@@ -0,0 +1,199 @@
+use std::io::Read;
+
+/// Prefix sizes tried in turn when looking for the dimensions. Most headers fit in the
+/// first, but a JPEG can carry large EXIF or ICC segments before its frame header.
+const READ_STEPS: &[usize] = &[4 * 1024, 64 * 1024, 256 * 1024];
+
+/// Read just enough of an image to find its pixel dimensions.
+pub fn read_dimensions(mut reader: impl Read) -> Option<(u32, u32)> {
+    let mut buf = Vec::new();
+    for &limit in READ_STEPS {
+        let wanted = (limit - buf.len()) as u64;
+        let read = (&mut reader).take(wanted).read_to_end(&mut buf).ok()?;
+        if let Some(dimensions) = dimensions(&buf) {
+            return Some(dimensions);
+        }
+        if (read as u64) < wanted {
+            // The whole image is already in `buf`
+            return None;
+        }
+    }
+    None
+}
+
+/// Pixel dimensions (width, height) from the first bytes of a JPEG, PNG, GIF, WebP or
+/// AVIF image. EXIF orientation is not applied.
+pub fn dimensions(header: &[u8]) -> Option<(u32, u32)> {
+    if header.starts_with(&[0xFF, 0xD8]) {
+        jpeg_dimensions(header)
+    } else if header.starts_with(b"\x89PNG\r\n\x1a\n") {
+        png_dimensions(header)
+    } else if header.starts_with(b"GIF87a") || header.starts_with(b"GIF89a") {
+        Some((le16(header, 6)?.into(), le16(header, 8)?.into()))
+    } else if header.starts_with(b"RIFF") && header.get(8..12) == Some(b"WEBP") {
+        webp_dimensions(header)
+    } else if header.get(4..8) == Some(b"ftyp") {
+        avif_dimensions(header)
+    } else {
+        None
+    }
+}
+
+/// Walk the marker segments up to the first start-of-frame.
+fn jpeg_dimensions(data: &[u8]) -> Option<(u32, u32)> {
+    let mut pos = 2;
+    loop {
+        if *data.get(pos)? != 0xFF {
+            return None;
+        }
+        // Any number of fill bytes may precede a marker
+        while *data.get(pos + 1)? == 0xFF {
+            pos += 1;
+        }
+        let marker = *data.get(pos + 1)?;
+        pos += 2;
+        match marker {
+            // Markers without a length
+            0x01 | 0xD0..=0xD7 => continue,
+            // Image data or end of image before any frame header
+            0xD9 | 0xDA => return None,
+            // SOF0-SOF15, except DHT (C4), JPG (C8) and DAC (CC)
+            0xC0..=0xCF if !matches!(marker, 0xC4 | 0xC8 | 0xCC) => {
+                let height = be16(data, pos + 3)?;
+                let width = be16(data, pos + 5)?;
+                return Some((width.into(), height.into()));
+            }
+            _ => pos += usize::from(be16(data, pos)?),
+        }
+    }
+}
+
+fn png_dimensions(data: &[u8]) -> Option<(u32, u32)> {
+    if data.get(12..16)? != b"IHDR" {
+        return None;
+    }
+    Some((be32(data, 16)?, be32(data, 20)?))
+}
+
+fn webp_dimensions(data: &[u8]) -> Option<(u32, u32)> {
+    match data.get(12..16)? {
+        b"VP8 " => {
+            // Key frame: 3-byte frame tag, then the start code 9D 01 2A
+            if data.get(23..26)? != [0x9D, 0x01, 0x2A] {
+                return None;
+            }
+            let width = le16(data, 26)? & 0x3FFF;
+            let height = le16(data, 28)? & 0x3FFF;
+            Some((width.into(), height.into()))
+        }
+        b"VP8L" => {
+            if *data.get(20)? != 0x2F {
+                return None;
+            }
+            let bits = u32::from_le_bytes(data.get(21..25)?.try_into().ok()?);
+            Some(((bits & 0x3FFF) + 1, ((bits >> 14) & 0x3FFF) + 1))
+        }
+        b"VP8X" => Some((le24(data, 24)? + 1, le24(data, 27)? + 1)),
+        _ => None,
+    }
+}
+
+/// The `ispe` (image spatial extents) property of the first item, which is the primary
+/// image in files written by common encoders.
+fn avif_dimensions(data: &[u8]) -> Option<(u32, u32)> {
+    let brand = data.get(8..12)?;
+    if brand != b"avif" && brand != b"avis" {
+        return None;
+    }
+    let pos = data.windows(4).position(|window| window == b"ispe")?;
+    // Box type, then version and flags, then width and height
+    Some((be32(data, pos + 8)?, be32(data, pos + 12)?))
+}
+
+fn be16(data: &[u8], pos: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(data.get(pos..pos + 2)?.try_into().ok()?))
+}
+
+fn be32(data: &[u8], pos: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(pos..pos + 4)?.try_into().ok()?))
+}
+
+fn le16(data: &[u8], pos: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(data.get(pos..pos + 2)?.try_into().ok()?))
+}
+
+fn le24(data: &[u8], pos: usize) -> Option<u32> {
+    let bytes = data.get(pos..pos + 3)?;
+    Some(u32::from(bytes[0]) | u32::from(bytes[1]) << 8 | u32::from(bytes[2]) << 16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{ImageFormat, RgbImage};
+    use std::io::Cursor;
+
+    fn encode(width: u32, height: u32, format: ImageFormat) -> Vec<u8> {
+        let image = RgbImage::new(width, height);
+        let mut buf = Cursor::new(Vec::new());
+        image.write_to(&mut buf, format).unwrap();
+        buf.into_inner()
+    }
+
+    #[test]
+    fn test_encoded_images() {
+        for format in [
+            ImageFormat::Jpeg,
+            ImageFormat::Png,
+            ImageFormat::Gif,
+            ImageFormat::WebP,
+        ] {
+            let data = encode(300, 120, format);
+            assert_eq!(dimensions(&data), Some((300, 120)), "{format:?}");
+        }
+    }
+
+    #[test]
+    fn test_jpeg_after_large_segment() {
+        let jpeg = encode(40, 30, ImageFormat::Jpeg);
+        // Insert a 60 KB APP1 segment right after SOI
+        let payload = vec![0u8; 60 * 1024];
+        let mut data = vec![0xFF, 0xD8, 0xFF, 0xE1];
+        data.extend_from_slice(&((payload.len() + 2) as u16).to_be_bytes());
+        data.extend_from_slice(&payload);
+        data.extend_from_slice(&jpeg[2..]);
+
+        assert_eq!(dimensions(&data[..4096]), None);
+        assert_eq!(read_dimensions(Cursor::new(&data)), Some((40, 30)));
+    }
+
+    #[test]
+    fn test_webp_lossy_and_extended_headers() {
+        let mut vp8 = b"RIFF\0\0\0\0WEBPVP8 \0\0\0\0".to_vec();
+        vp8.extend_from_slice(&[0, 0, 0, 0x9D, 0x01, 0x2A]);
+        vp8.extend_from_slice(&640u16.to_le_bytes());
+        vp8.extend_from_slice(&480u16.to_le_bytes());
+        assert_eq!(dimensions(&vp8), Some((640, 480)));
+
+        let mut vp8x = b"RIFF\0\0\0\0WEBPVP8X\0\0\0\0\0\0\0\0".to_vec();
+        vp8x.extend_from_slice(&[0xFF, 0x0F, 0x00, 0x9F, 0x05, 0x00]);
+        assert_eq!(dimensions(&vp8x), Some((4096, 1440)));
+    }
+
+    #[test]
+    fn test_avif_ispe() {
+        let mut avif = b"\0\0\0\x18ftypavif\0\0\0\0mif1avif".to_vec();
+        avif.extend_from_slice(b"\0\0\0\x14ispe\0\0\0\0");
+        avif.extend_from_slice(&1920u32.to_be_bytes());
+        avif.extend_from_slice(&1080u32.to_be_bytes());
+        assert_eq!(dimensions(&avif), Some((1920, 1080)));
+    }
+
+    #[test]
+    fn test_unknown_or_truncated() {
+        assert_eq!(dimensions(b"not an image"), None);
+        assert_eq!(dimensions(b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR\0\0"), None);
+        assert_eq!(dimensions(&[0xFF, 0xD8, 0xFF]), None);
+        assert_eq!(read_dimensions(Cursor::new(b"GIF89a")), None);
+    }
+}
@@ -1,22 +1,96 @@
+mod cache;
+mod comic_info;
+mod directory;
+mod image_header;
+mod name_encoding;
+mod pages;
+mod password;
+mod prefetch;
 mod rar;
+mod sevenz;
+mod signature;
+mod sort;
+mod split_zip;
+mod tar;
+mod temp_dir;
+mod toc;
+mod virtual_path;
+mod volume;
 mod zip;
 
-use serde::Serialize;
+pub use cache::ArchiveCache;
+pub use comic_info::ArchiveMetadata;
+pub use pages::{list_pages, list_pages_with_info, PageEntry, PageInfo};
+pub use prefetch::Prefetcher;
+pub use sort::SortMode;
+pub use toc::{table_of_contents, TocEntry};
+
+use crate::error::MekuriError;
+use base64::Engine;
+use pages::EntryInfo;
+use serde::{Deserialize, Serialize};
+use signature::Signature;
+use sort::ListedEntry;
+use std::collections::HashMap;
 use std::path::Path;
-use std::sync::Mutex;
+use std::sync::Arc;
+use virtual_path::{Extracted, VirtualPath};
 
 const IMAGE_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "webp", "gif", "avif"];
-const ARCHIVE_EXTENSIONS: &[&str] = &["zip", "cbz", "rar", "cbr", "7z"];
+const ARCHIVE_EXTENSIONS: &[&str] = &[
+    "zip", "cbz", "rar", "cbr", "7z", "cb7", "tar", "cbt", "tgz", "tar.gz", "tar.bz2", "tar.xz",
+    "tar.zst",
+];
+/// Tar suffixes, including the compound ones `Path::extension` cannot see.
+const TAR_EXTENSIONS: &[&str] = &[
+    "tar", "cbt", "tgz", "tar.gz", "tar.bz2", "tar.xz", "tar.zst",
+];
 const PDF_EXTENSIONS: &[&str] = &["pdf"];
 
 /// Supported archive format categories.
 enum ArchiveFormat {
     Zip,
     Rar,
+    SevenZip,
+    Tar,
+    /// A plain folder whose image files are read as pages
+    Directory,
+}
+
+/// Detect the archive format from the file header, falling back to the extension
+/// when the header is unreadable or inconclusive. Folders are read as image folders.
+fn detect_format(archive_path: &str) -> Result<ArchiveFormat, MekuriError> {
+    if Path::new(archive_path).is_dir() {
+        return Ok(ArchiveFormat::Directory);
+    }
+    // Only ZIP archives are kept in memory
+    if virtual_path::in_memory(archive_path).is_some() {
+        return Ok(ArchiveFormat::Zip);
+    }
+
+    match signature::sniff_file(Path::new(archive_path)) {
+        Some(Signature::Zip) => Ok(ArchiveFormat::Zip),
+        Some(Signature::Rar) => Ok(ArchiveFormat::Rar),
+        Some(Signature::SevenZip) => Ok(ArchiveFormat::SevenZip),
+        Some(Signature::Tar) => Ok(ArchiveFormat::Tar),
+        Some(Signature::Pdf) => Err(MekuriError::UnsupportedFormat(format!(
+            "Not an archive (PDF document): {archive_path}"
+        ))),
+        // A bare compressed stream says nothing about what is inside it
+        _ => detect_format_from_extension(archive_path),
+    }
 }
 
 /// Detect the archive format from a file path's extension.
-fn detect_format(archive_path: &str) -> Result<ArchiveFormat, String> {
+fn detect_format_from_extension(archive_path: &str) -> Result<ArchiveFormat, MekuriError> {
+    let lower = archive_path.to_lowercase();
+    if TAR_EXTENSIONS
+        .iter()
+        .any(|ext| lower.ends_with(&format!(".{ext}")))
+    {
+        return Ok(ArchiveFormat::Tar);
+    }
+
     let ext = Path::new(archive_path)
         .extension()
         .and_then(|e| e.to_str())
@@ -26,64 +100,503 @@ fn detect_format(archive_path: &str) -> Result<ArchiveFormat, String> {
     match ext.as_str() {
         "zip" | "cbz" => Ok(ArchiveFormat::Zip),
         "rar" | "cbr" => Ok(ArchiveFormat::Rar),
-        _ => Err(format!("Unsupported archive format: .{ext}")),
+        "7z" | "cb7" => Ok(ArchiveFormat::SevenZip),
+        _ => Err(MekuriError::UnsupportedFormat(format!(
+            "Unsupported archive format: .{ext}"
+        ))),
     }
 }
 
-/// Result of analyzing archive contents
+/// How a file should be presented in directory listings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum FileKind {
+    Archive,
+    Pdf,
+    Other,
+}
+
+/// Classify a file by its content, falling back to its name when the header is inconclusive.
+///
+/// Only names with an archive or PDF extension, or with no extension at all, are checked
+/// by content. Other extensions keep their meaning: documents such as `.docx`, `.epub` or
+/// `.jar` are ZIP files too, but not comics.
+pub fn detect_file_kind(path: &Path) -> FileKind {
+    let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+    let by_name = if is_archive_file(name) {
+        FileKind::Archive
+    } else if is_pdf_file(name) {
+        FileKind::Pdf
+    } else {
+        FileKind::Other
+    };
+    if by_name == FileKind::Other && path.extension().is_some() {
+        return FileKind::Other;
+    }
+
+    match signature::sniff_file(path) {
+        Some(Signature::Zip | Signature::Rar | Signature::SevenZip | Signature::Tar) => {
+            FileKind::Archive
+        }
+        Some(Signature::Pdf) => FileKind::Pdf,
+        _ => by_name,
+    }
+}
+
+/// How deep in one another `analyze_contents` opens nested archives.
+const MAX_NESTING: usize = 8;
+/// Largest nested ZIP archive read from memory rather than extracted to a temp file.
+const IN_MEMORY_LIMIT: usize = 64 * 1024 * 1024;
+
+/// The images and archives stored directly in one archive, each in natural order.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Listing {
+    images: Vec<String>,
+    archives: Vec<String>,
+}
+
+impl Listing {
+    /// Sort the names of the files of an archive into images and archives, leaving out
+    /// everything else.
+    fn new(names: impl IntoIterator<Item = String>) -> Self {
+        let mut listing = Self::default();
+        for name in names {
+            if is_image_file(&name) {
+                listing.images.push(name);
+            } else if is_archive_file(&name) {
+                listing.archives.push(name);
+            }
+        }
+        listing.images.sort_by(|a, b| natord::compare(a, b));
+        listing.archives.sort_by(|a, b| natord::compare(a, b));
+        listing
+    }
+}
+
+/// What an archive holds: its images, grouped by folder, and the archives stored in it.
+///
+/// The tree grows one level at a time. The nested archives are only listed, with
+/// `analyzed` unset and nothing in them, until `analyze_contents` is called on their path.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ContentTree {
+    /// Entry name in the parent archive; the file name for an archive on disk
+    pub name: String,
+    /// Virtual path of the archive, which every command taking an archive path accepts
+    pub path: String,
+    /// Whether `image_groups` and `nested` were read
+    pub analyzed: bool,
+    pub image_groups: Vec<ImageGroup>,
+    pub nested: Vec<ContentTree>,
+}
+
+/// The images in one folder of an archive.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ImageGroup {
+    /// Folder path inside the archive; empty for its root
+    pub folder: String,
+    /// Full entry names, in natural order
+    pub names: Vec<String>,
+}
+
+impl ContentTree {
+    fn new(path: &VirtualPath, name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            path: path.to_string(),
+            analyzed: false,
+            image_groups: Vec::new(),
+            nested: Vec::new(),
+        }
+    }
+}
+
+/// An entry of an archive, identified by its position as well as its name.
+///
+/// Names alone are ambiguous: an archive can store the same name twice, and names that
+/// were decoded lossily or differ only in Unicode normalization may not match the entry
+/// they were listed from. The index is the entry's position in the archive's own entry
+/// table (ZIP central directory, RAR and TAR header order, 7z file list; the sorted listing
+/// for folders), so it stays valid as long as the archive is unchanged. The name is
+/// checked again on read, so an id from an archive that changed since fails with
+/// `NotFound` instead of reading another entry.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct EntryId {
+    pub index: usize,
+    pub name: String,
+}
+
+impl EntryId {
+    fn new(index: usize, name: impl Into<String>) -> Self {
+        Self {
+            index,
+            name: name.into(),
+        }
+    }
+
+    fn not_found(&self) -> MekuriError {
+        MekuriError::NotFound(format!("Entry not found: {} (#{})", self.name, self.index))
+    }
+}
+
+/// Progress of a bulk extraction, reported once per extracted page and once at the end.
 #[derive(Debug, Clone, Serialize)]
-#[serde(tag = "type")]
-pub enum ArchiveContents {
-    /// Archive contains images directly
-    Images { names: Vec<String> },
-    /// Archive contains nested archives
-    NestedArchives { names: Vec<String> },
-    /// Archive is empty or has no relevant content
-    Empty,
+pub struct ExtractProgress {
+    pub archive_path: String,
+    /// Page that was just extracted; `None` on the final report
+    pub entry_name: Option<String>,
+    pub extracted: usize,
+    pub total: usize,
+    /// Set on the final report when the pass stopped early
+    pub error: Option<MekuriError>,
 }
 
-/// Global storage for extracted temporary directories
-/// This keeps temp directories alive until the app closes
-static TEMP_DIRS: Mutex<Vec<tempfile::TempDir>> = Mutex::new(Vec::new());
+/// List the image entries of an archive in the order the backend finds them.
+fn listed_entries(
+    cache: &ArchiveCache,
+    archive_path: &str,
+) -> Result<Vec<ListedEntry>, MekuriError> {
+    let file = virtual_path::resolve(cache, archive_path)?;
+    match detect_format(&file)? {
+        ArchiveFormat::Zip => zip::list_entries(&file),
+        ArchiveFormat::Rar => rar::list_entries(&file),
+        ArchiveFormat::SevenZip => sevenz::list_entries(&file),
+        ArchiveFormat::Tar => tar::list_entries(&file),
+        ArchiveFormat::Directory => directory::list_entries(&file),
+    }
+}
+
+/// List image entries in an archive with their ids, sorted by `sort`.
+pub fn list_entries(
+    cache: &ArchiveCache,
+    archive_path: &str,
+    sort: SortMode,
+) -> Result<Vec<EntryId>, MekuriError> {
+    let mut entries = listed_entries(cache, archive_path)?;
+    sort::sort_entries(&mut entries, sort);
+    Ok(entries.into_iter().map(|entry| entry.id).collect())
+}
 
 /// List image entries in an archive, sorted by natural order.
-pub fn list_images(archive_path: &str) -> Result<Vec<String>, String> {
-    match detect_format(archive_path)? {
-        ArchiveFormat::Zip => zip::list_images(archive_path),
-        ArchiveFormat::Rar => rar::list_images(archive_path),
+pub fn list_images(cache: &ArchiveCache, archive_path: &str) -> Result<Vec<String>, MekuriError> {
+    let entries = list_entries(cache, archive_path, SortMode::Natural)?;
+    Ok(entries.into_iter().map(|entry| entry.name).collect())
+}
+
+/// List the images and archives stored directly in an archive.
+fn listing(cache: &ArchiveCache, archive_path: &str) -> Result<Listing, MekuriError> {
+    let file = virtual_path::resolve(cache, archive_path)?;
+    match detect_format(&file)? {
+        ArchiveFormat::Zip => zip::analyze_contents(&file),
+        ArchiveFormat::Rar => rar::analyze_contents(&file),
+        ArchiveFormat::SevenZip => sevenz::analyze_contents(&file),
+        ArchiveFormat::Tar => tar::analyze_contents(&file),
+        ArchiveFormat::Directory => directory::analyze_contents(&file),
     }
 }
 
-/// Analyze archive contents to determine if it contains images or nested archives.
-pub fn analyze_contents(archive_path: &str) -> Result<ArchiveContents, String> {
-    match detect_format(archive_path)? {
-        ArchiveFormat::Zip => zip::analyze_contents(archive_path),
-        ArchiveFormat::Rar => rar::analyze_contents(archive_path),
+/// The file or folder on disk that a path, virtual or not, starts from.
+pub fn root_on_disk(archive_path: &str) -> String {
+    VirtualPath::parse(archive_path).root().to_string()
+}
+
+/// Analyze what an archive holds, listing the archives nested in it without opening them.
+///
+/// Only this archive is read (and extracted from its parents, for a nested one), so an
+/// archive of many others opens without extracting each of them; they are analyzed in
+/// turn when chosen.
+pub fn analyze_contents(
+    cache: &ArchiveCache,
+    archive_path: &str,
+) -> Result<ContentTree, MekuriError> {
+    let path = VirtualPath::parse(archive_path);
+    if path.depth() >= MAX_NESTING {
+        return Err(MekuriError::UnsupportedFormat(format!(
+            "Archive nested too deeply: {path}"
+        )));
     }
+    let name = match path.parent() {
+        Some((_, name)) => name,
+        None => Path::new(archive_path)
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or(archive_path),
+    };
+    let mut tree = ContentTree::new(&path, name);
+    let listing = listing(cache, &tree.path)?;
+    tree.analyzed = true;
+    tree.image_groups = group_by_folder(listing.images);
+    tree.nested = listing
+        .archives
+        .iter()
+        .map(|nested_name| ContentTree::new(&path.join(nested_name), nested_name))
+        .collect();
+    Ok(tree)
 }
 
-/// Extract a nested archive from a parent archive and return the path to the extracted file.
-/// The extracted file is placed in a temporary directory that persists until app closes.
-pub fn extract_nested_archive(parent_path: &str, nested_name: &str) -> Result<String, String> {
-    match detect_format(parent_path)? {
-        ArchiveFormat::Zip => zip::extract_nested_archive(parent_path, nested_name),
-        ArchiveFormat::Rar => rar::extract_nested_archive(parent_path, nested_name),
+/// Group image names in natural order by their folder, folders in natural order with
+/// the root first.
+fn group_by_folder(names: Vec<String>) -> Vec<ImageGroup> {
+    let mut groups: Vec<ImageGroup> = Vec::new();
+    for name in names {
+        let folder = name.rsplit_once('/').map_or("", |(folder, _)| folder);
+        match groups.iter_mut().find(|group| group.folder == folder) {
+            Some(group) => group.names.push(name),
+            None => groups.push(ImageGroup {
+                folder: folder.to_string(),
+                names: vec![name],
+            }),
+        }
     }
+    groups.sort_by(|a, b| natord::compare(&a.folder, &b.folder));
+    groups
 }
 
-/// Store a temp directory to keep it alive
-pub fn store_temp_dir(dir: tempfile::TempDir) {
-    if let Ok(mut dirs) = TEMP_DIRS.lock() {
-        dirs.push(dir);
+/// Extract a nested archive from its parent. `virtual_path::resolve` calls this for each
+/// level of a nested path, with the parent already resolved.
+///
+/// ZIP archives up to `IN_MEMORY_LIMIT` are kept in memory. Anything else is written to a
+/// temporary directory that lives as long as the archive stays resolved: unrar can only
+/// open files, and the 7z and tar backends reopen the file for every read.
+fn extract_nested_archive(parent_path: &str, nested_name: &str) -> Result<Extracted, MekuriError> {
+    let data = match detect_format(parent_path)? {
+        ArchiveFormat::Zip => zip::read_nested_archive(parent_path, nested_name)?,
+        ArchiveFormat::Rar => rar::read_nested_archive(parent_path, nested_name)?,
+        ArchiveFormat::SevenZip => sevenz::read_entry(parent_path, nested_name)?,
+        ArchiveFormat::Tar => tar::read_entry(parent_path, nested_name)?,
+        // Already a file of its own
+        ArchiveFormat::Directory => {
+            return directory::extract_nested_archive(parent_path, nested_name).map(Extracted::File)
+        }
+    };
+    if data.len() <= IN_MEMORY_LIMIT && signature::sniff(&data) == Some(Signature::Zip) {
+        return Ok(Extracted::Memory(data.into()));
     }
+    write_temp_archive(nested_name, &data)
 }
 
-/// Extract a single image from an archive and return it as a Base64-encoded data URL.
-pub fn get_image_base64(archive_path: &str, entry_name: &str) -> Result<String, String> {
-    match detect_format(archive_path)? {
-        ArchiveFormat::Zip => zip::get_image_base64(archive_path, entry_name),
-        ArchiveFormat::Rar => rar::get_image_base64(archive_path, entry_name),
+/// Write an extracted archive to a new temporary directory, under its own file name.
+fn write_temp_archive(nested_name: &str, data: &[u8]) -> Result<Extracted, MekuriError> {
+    let temp_dir = temp_dir::create()?;
+    let file_name = Path::new(nested_name)
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or("archive");
+    let temp_path = temp_dir.path().join(file_name);
+    std::fs::write(&temp_path, data)
+        .map_err(|e| MekuriError::io("Failed to write temp file", e))?;
+
+    let file = temp_path.to_string_lossy().to_string();
+    Ok(Extracted::Temp {
+        _dir: temp_dir,
+        file,
+    })
+}
+
+/// Keep the archive at `archive_path`, and the archives it is nested in, resolved for
+/// `owner`, a viewer window. Those it resolved for the archive it showed before are
+/// released, unless another window still shows them or an archive inside them.
+pub fn open_nested(cache: &ArchiveCache, owner: &str, archive_path: &str) {
+    release(cache, virtual_path::open(owner, archive_path));
+}
+
+/// Release the nested archives `owner` kept, once it is closed.
+pub fn close_nested(cache: &ArchiveCache, owner: &str) {
+    release(cache, virtual_path::close(owner));
+}
+
+/// Delete everything this run extracted to disk, when the app exits.
+pub fn remove_temp_dirs() {
+    temp_dir::remove_session();
+}
+
+/// Delete what earlier runs extracted to disk and could not clean up, e.g. after a crash.
+pub fn sweep_stale_temp_dirs() {
+    temp_dir::sweep_stale();
+}
+
+fn release(cache: &ArchiveCache, released: Vec<virtual_path::Resolved>) {
+    // Open handles would keep Windows from deleting the extracted files, and archives
+    // in memory from being freed
+    for resolved in &released {
+        cache.forget_handle(resolved.location());
     }
+    drop(released);
+}
+
+/// Extract all pages of an archive up front when reading them one by one is expensive.
+/// Only solid RAR archives qualify; returns whether an extraction is running or done.
+/// Progress is reported under `archive_path` as given, virtual or not.
+pub fn start_bulk_extraction(
+    cache: &ArchiveCache,
+    archive_path: &str,
+    on_progress: impl Fn(ExtractProgress) + Send + 'static,
+) -> Result<bool, MekuriError> {
+    let file = virtual_path::resolve(cache, archive_path)?;
+    let reported_path = archive_path.to_string();
+    let on_progress = move |progress: ExtractProgress| {
+        on_progress(ExtractProgress {
+            archive_path: reported_path.clone(),
+            ..progress
+        })
+    };
+    match detect_format(&file)? {
+        ArchiveFormat::Rar => rar::start_bulk_extraction(cache, &file, on_progress),
+        _ => Ok(false),
+    }
+}
+
+/// Read the metadata in an archive's ComicInfo.xml. Returns `None` when the archive has
+/// none; only ZIP and RAR archives are searched.
+pub fn read_metadata(
+    cache: &ArchiveCache,
+    archive_path: &str,
+) -> Result<Option<ArchiveMetadata>, MekuriError> {
+    let file = virtual_path::resolve(cache, archive_path)?;
+    let data = match detect_format(&file)? {
+        ArchiveFormat::Zip => zip::read_comic_info(cache, &file)?,
+        ArchiveFormat::Rar => rar::read_comic_info(cache, &file)?,
+        _ => None,
+    };
+    data.map(|data| comic_info::parse(&data)).transpose()
+}
+
+/// Enter the password of an encrypted archive. It is kept in memory for the rest of the
+/// session and used for every later read of the archive, by the virtual path given, so
+/// a nested archive keeps it when it is extracted again.
+///
+/// The password is checked against the archive first; a wrong one is not kept and fails
+/// with `PasswordRequired`. Only ZIP and RAR archives can be encrypted.
+pub fn set_password(
+    cache: &ArchiveCache,
+    archive_path: &str,
+    password: &str,
+) -> Result<(), MekuriError> {
+    let file = virtual_path::resolve(cache, archive_path)?;
+    let format = detect_format(&file)?;
+    password::remember(archive_path, password);
+    // A RAR handle keeps the password it was opened with
+    cache.forget_handle(&file);
+    let checked = match format {
+        ArchiveFormat::Zip => zip::check_password(cache, &file, password),
+        ArchiveFormat::Rar => rar::check_password(&file),
+        _ => Ok(()),
+    };
+    if checked.is_err() {
+        password::forget(archive_path);
+    }
+    checked
+}
+
+/// Sizes and dimensions of the images in an archive, by entry name. 7z and TAR archives
+/// are not read ahead, so their pages get no info.
+fn image_info(
+    cache: &ArchiveCache,
+    archive_path: &str,
+) -> Result<HashMap<String, EntryInfo>, MekuriError> {
+    let file = virtual_path::resolve(cache, archive_path)?;
+    match detect_format(&file)? {
+        ArchiveFormat::Zip => zip::image_info(cache, &file),
+        ArchiveFormat::Rar => rar::image_info(cache, &file),
+        ArchiveFormat::Directory => directory::image_info(&file),
+        ArchiveFormat::SevenZip | ArchiveFormat::Tar => Ok(HashMap::new()),
+    }
+}
+
+/// Read the raw bytes of a single entry from an archive.
+///
+/// Pages are served from `cache` when they were read (or prefetched) before, and ZIP and
+/// RAR handles are kept there so consecutive pages avoid reopening the file.
+pub fn read_entry(
+    cache: &ArchiveCache,
+    archive_path: &str,
+    entry_name: &str,
+) -> Result<Vec<u8>, MekuriError> {
+    let file = virtual_path::resolve(cache, archive_path)?;
+    // Folder pages are plain files, so keeping them in memory gains nothing
+    if Path::new(&file).is_dir() {
+        return directory::read_entry(&file, entry_name);
+    }
+
+    let modified = cache::modified_time(&file)?;
+    if let Some(data) = cache.cached_page(&file, modified, entry_name) {
+        return Ok(data.to_vec());
+    }
+
+    let data = read_from_archive(cache, &file, entry_name)?;
+    cache.store_page(&file, modified, entry_name, Arc::new(data.clone()));
+    Ok(data)
+}
+
+/// Read a single entry without keeping it in the page cache, for one-off reads such as
+/// covers for the file browser that would otherwise push out the pages being viewed.
+/// ZIP and RAR handles are still reused.
+pub fn read_entry_uncached(
+    cache: &ArchiveCache,
+    archive_path: &str,
+    entry_name: &str,
+) -> Result<Vec<u8>, MekuriError> {
+    let file = virtual_path::resolve(cache, archive_path)?;
+    read_from_archive(cache, &file, entry_name)
+}
+
+fn read_from_archive(
+    cache: &ArchiveCache,
+    file: &str,
+    entry_name: &str,
+) -> Result<Vec<u8>, MekuriError> {
+    match detect_format(file)? {
+        ArchiveFormat::Zip => zip::read_entry(cache, file, entry_name),
+        ArchiveFormat::Rar => rar::read_entry(cache, file, entry_name),
+        ArchiveFormat::SevenZip => sevenz::read_entry(file, entry_name),
+        ArchiveFormat::Tar => tar::read_entry(file, entry_name),
+        ArchiveFormat::Directory => directory::read_entry(file, entry_name),
+    }
+}
+
+/// Read the raw bytes of the entry `id` names, as listed by `list_entries`.
+///
+/// The page cache is keyed by name, so pages read by id bypass it; ZIP and RAR handles
+/// are still reused.
+pub fn read_entry_by_id(
+    cache: &ArchiveCache,
+    archive_path: &str,
+    id: &EntryId,
+) -> Result<Vec<u8>, MekuriError> {
+    let file = virtual_path::resolve(cache, archive_path)?;
+    match detect_format(&file)? {
+        ArchiveFormat::Zip => zip::read_entry_by_id(cache, &file, id),
+        ArchiveFormat::Rar => rar::read_entry_by_id(cache, &file, id),
+        ArchiveFormat::SevenZip => sevenz::read_entry_by_id(&file, id),
+        ArchiveFormat::Tar => tar::read_entry_by_id(&file, id),
+        // Names in a folder are unique, so the name alone finds the file
+        ArchiveFormat::Directory => directory::read_entry(&file, &id.name),
+    }
+}
+
+/// `get_image_base64` for an entry id.
+pub fn get_image_base64_by_id(
+    cache: &ArchiveCache,
+    archive_path: &str,
+    id: &EntryId,
+) -> Result<String, MekuriError> {
+    let data = read_entry_by_id(cache, archive_path, id)?;
+    Ok(to_data_url(&id.name, &data))
+}
+
+/// Extract a single image from an archive and return it as a Base64-encoded data URL.
+pub fn get_image_base64(
+    cache: &ArchiveCache,
+    archive_path: &str,
+    entry_name: &str,
+) -> Result<String, MekuriError> {
+    let data = read_entry(cache, archive_path, entry_name)?;
+    Ok(to_data_url(entry_name, &data))
+}
+
+/// Encode image bytes as a Base64 data URL, guessing the MIME type from the entry name.
+fn to_data_url(entry_name: &str, data: &[u8]) -> String {
+    let mime = mime_type_from_name(entry_name);
+    let b64 = base64::engine::general_purpose::STANDARD.encode(data);
+    format!("data:{mime};base64,{b64}")
 }
 
 /// Check if a filename has an image extension.
@@ -110,6 +623,25 @@ pub fn is_archive_file(name: &str) -> bool {
         .any(|ext| lower.ends_with(&format!(".{ext}")))
 }
 
+/// For a later volume of a multi-volume archive (`book.part2.rar`, `book.r00`, `book.z01`),
+/// the file name of the volume the set is opened from; `None` for anything else.
+pub fn volume_head_name(name: &str) -> Option<String> {
+    volume::head_name(name)
+}
+
+/// The path to open for `archive_path`: the head volume of its set when it is a later
+/// volume and the head exists next to it, otherwise the path itself.
+pub fn first_volume(archive_path: &str) -> String {
+    volume::first_volume(Path::new(archive_path))
+        .map(|path| path.to_string_lossy().to_string())
+        .unwrap_or_else(|| archive_path.to_string())
+}
+
+/// Check if a folder directly contains images and can be read as a book.
+pub fn is_image_folder(path: &Path) -> bool {
+    directory::has_images(path)
+}
+
 /// Guess MIME type from file extension.
 pub fn mime_type_from_name(name: &str) -> &'static str {
     let lower = name.to_lowercase();
@@ -167,34 +699,446 @@ mod tests {
 
     #[test]
     fn test_unsupported_archive_format() {
-        let result = list_images("test.tar");
-        assert!(result.is_err());
-        assert!(result.unwrap_err().contains("Unsupported"));
+        let result = list_images(&ArchiveCache::default(), "test.lzh");
+        assert!(matches!(result, Err(MekuriError::UnsupportedFormat(_))));
     }
 
     #[test]
     fn test_nonexistent_archive() {
-        let result = list_images("nonexistent.zip");
+        let result = list_images(&ArchiveCache::default(), "nonexistent.zip");
         assert!(result.is_err());
     }
 
     #[test]
     fn test_nonexistent_rar_archive() {
-        let result = list_images("nonexistent.rar");
+        let result = list_images(&ArchiveCache::default(), "nonexistent.rar");
         assert!(result.is_err());
     }
 
     #[test]
     fn test_cbr_dispatches_to_rar() {
-        let result = list_images("nonexistent.cbr");
-        assert!(result.is_err());
-        // Should not say "Unsupported" — it should be a file-not-found error
-        assert!(!result.unwrap_err().contains("Unsupported"));
+        let result = list_images(&ArchiveCache::default(), "nonexistent.cbr");
+        // Should not be "Unsupported" — it should be a file-not-found error
+        assert!(matches!(result, Err(MekuriError::NotFound(_))));
+    }
+
+    #[test]
+    fn test_7z_and_cb7_dispatch_to_sevenz() {
+        for path in ["nonexistent.7z", "nonexistent.cb7"] {
+            let result = list_images(&ArchiveCache::default(), path);
+            assert!(matches!(result, Err(MekuriError::NotFound(_))), "{path}");
+        }
+    }
+
+    #[test]
+    fn test_tar_variants_dispatch_to_tar() {
+        for path in [
+            "nonexistent.tar",
+            "nonexistent.cbt",
+            "nonexistent.tgz",
+            "nonexistent.tar.gz",
+            "nonexistent.tar.bz2",
+            "nonexistent.tar.xz",
+            "nonexistent.TAR.ZST",
+        ] {
+            let result = list_images(&ArchiveCache::default(), path);
+            assert!(matches!(result, Err(MekuriError::NotFound(_))), "{path}");
+        }
+    }
+
+    #[test]
+    fn test_is_archive_file_compound_tar_extensions() {
+        assert!(is_archive_file("book.tar.zst"));
+        assert!(is_archive_file("book.tgz"));
+        assert!(is_archive_file("book.CBT"));
+        assert!(!is_archive_file("notes.gz"));
+        assert!(!is_archive_file("notes.zst"));
+    }
+
+    #[test]
+    fn test_detect_format_prefers_content_over_extension() {
+        let dir = tempfile::tempdir().unwrap();
+
+        // A RAR renamed to .cbz
+        let rar_as_cbz = dir.path().join("book.cbz");
+        std::fs::write(&rar_as_cbz, b"Rar!\x1a\x07\x01\x00rest-of-archive").unwrap();
+        assert!(matches!(
+            detect_format(rar_as_cbz.to_str().unwrap()),
+            Ok(ArchiveFormat::Rar)
+        ));
+
+        // A 7z named .zip
+        let sevenz_as_zip = dir.path().join("book.zip");
+        std::fs::write(&sevenz_as_zip, b"7z\xbc\xaf\x27\x1c\x00\x04").unwrap();
+        assert!(matches!(
+            detect_format(sevenz_as_zip.to_str().unwrap()),
+            Ok(ArchiveFormat::SevenZip)
+        ));
+
+        // A PDF named .cbr is not an archive
+        let pdf_as_cbr = dir.path().join("book.cbr");
+        std::fs::write(&pdf_as_cbr, b"%PDF-1.7").unwrap();
+        assert!(detect_format(pdf_as_cbr.to_str().unwrap()).is_err());
+
+        // Unrecognized content falls back to the extension
+        let unknown = dir.path().join("book.cbr");
+        std::fs::write(&unknown, b"garbage").unwrap();
+        assert!(matches!(
+            detect_format(unknown.to_str().unwrap()),
+            Ok(ArchiveFormat::Rar)
+        ));
+    }
+
+    #[test]
+    fn test_zip_renamed_to_cbr_is_listed() {
+        use std::io::Write;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("book.cbr");
+        let mut writer = ::zip::ZipWriter::new(std::fs::File::create(&path).unwrap());
+        writer
+            .start_file("page1.jpg", ::zip::write::SimpleFileOptions::default())
+            .unwrap();
+        writer.write_all(b"fake-jpg").unwrap();
+        writer.finish().unwrap();
+
+        let result = list_images(&ArchiveCache::default(), path.to_str().unwrap()).unwrap();
+        assert_eq!(result, vec!["page1.jpg"]);
+    }
+
+    #[test]
+    fn test_uncached_read_leaves_page_cache_alone() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("book.cbz");
+        std::fs::write(&path, zip_bytes(&[("001.jpg", b"cover")])).unwrap();
+        let path = path.to_str().unwrap();
+        let cache = ArchiveCache::default();
+        let modified = cache::modified_time(path).unwrap();
+
+        assert_eq!(
+            read_entry_uncached(&cache, path, "001.jpg").unwrap(),
+            b"cover"
+        );
+        assert!(cache.cached_page(path, modified, "001.jpg").is_none());
+
+        read_entry(&cache, path, "001.jpg").unwrap();
+        assert!(cache.cached_page(path, modified, "001.jpg").is_some());
+    }
+
+    #[test]
+    fn test_detect_file_kind() {
+        let dir = tempfile::tempdir().unwrap();
+        let write = |name: &str, data: &[u8]| {
+            let path = dir.path().join(name);
+            std::fs::write(&path, data).unwrap();
+            path
+        };
+
+        assert_eq!(
+            detect_file_kind(&write("a.cbz", b"Rar!\x1a\x07\x00")),
+            FileKind::Archive
+        );
+        assert_eq!(
+            detect_file_kind(&write("noext", b"PK\x03\x04")),
+            FileKind::Archive
+        );
+        assert_eq!(
+            detect_file_kind(&write("a.zip", b"%PDF-1.4")),
+            FileKind::Pdf
+        );
+        assert_eq!(detect_file_kind(&write("b.zip", b"")), FileKind::Archive);
+        assert_eq!(detect_file_kind(&write("a.pdf", b"")), FileKind::Pdf);
+        assert_eq!(
+            detect_file_kind(&write("a.jpg", b"PK\x03\x04")),
+            FileKind::Other
+        );
+        assert_eq!(
+            detect_file_kind(&write("a.gz", b"\x1f\x8b\x08")),
+            FileKind::Other
+        );
+        assert_eq!(
+            detect_file_kind(&write("a.docx", b"PK\x03\x04")),
+            FileKind::Other
+        );
+        assert_eq!(
+            detect_file_kind(&write("a.epub", b"PK\x03\x04")),
+            FileKind::Other
+        );
+        assert_eq!(detect_file_kind(&write("noext2", b"text")), FileKind::Other);
+        assert_eq!(detect_file_kind(&write("a.txt", b"text")), FileKind::Other);
+    }
+
+    #[test]
+    fn test_folder_dispatches_to_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("002.jpg"), b"2").unwrap();
+        std::fs::write(dir.path().join("001.jpg"), b"1").unwrap();
+
+        let result = list_images(&ArchiveCache::default(), dir.path().to_str().unwrap()).unwrap();
+        assert_eq!(result, vec!["001.jpg", "002.jpg"]);
+    }
+
+    fn zip_bytes(entries: &[(&str, &[u8])]) -> Vec<u8> {
+        use std::io::Write;
+
+        let mut writer = ::zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        for (name, data) in entries {
+            writer
+                .start_file(*name, ::zip::write::SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(data).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn test_analyze_nested_tree_and_read_through_virtual_paths() {
+        let volume = zip_bytes(&[
+            ("ch2/001.jpg", b"ch2-1"),
+            ("ch1/002.jpg", b"ch1-2"),
+            ("ch1/001.jpg", b"ch1-1"),
+        ]);
+        let inner_box = zip_bytes(&[("vol2.cbz", &zip_bytes(&[("001.jpg", b"deep")]))]);
+        let dir = tempfile::tempdir().unwrap();
+        let outer = dir.path().join("omnibus.zip");
+        std::fs::write(
+            &outer,
+            zip_bytes(&[
+                ("cover.jpg", b"cover"),
+                ("vol1.cbz", &volume),
+                ("box.zip", &inner_box),
+                ("broken.cbz", b"not a zip"),
+            ]),
+        )
+        .unwrap();
+        let outer = outer.to_str().unwrap();
+
+        let tree = analyze_contents(&ArchiveCache::default(), outer).unwrap();
+        assert_eq!(tree.name, "omnibus.zip");
+        assert_eq!(tree.path, outer);
+        assert_eq!(
+            tree.image_groups,
+            [ImageGroup {
+                folder: String::new(),
+                names: vec!["cover.jpg".to_string()],
+            }]
+        );
+        let names: Vec<&str> = tree.nested.iter().map(|n| n.name.as_str()).collect();
+        assert_eq!(names, ["box.zip", "broken.cbz", "vol1.cbz"]);
+
+        let [inner_box, broken, vol1] = &tree.nested[..] else {
+            panic!("unexpected tree: {tree:?}");
+        };
+        // Nested archives are only listed until they are chosen
+        assert!(tree.analyzed);
+        assert!(!vol1.analyzed && vol1.image_groups.is_empty());
+        assert_eq!(vol1.path, format!("{outer}!/vol1.cbz"));
+        assert!(virtual_path::in_memory(&vol1.path).is_none());
+
+        assert!(matches!(
+            analyze_contents(&ArchiveCache::default(), &broken.path),
+            Err(MekuriError::Corrupt(_))
+        ));
+        let vol1 = analyze_contents(&ArchiveCache::default(), &vol1.path).unwrap();
+        assert_eq!(vol1.name, "vol1.cbz");
+        let folders: Vec<&str> = vol1
+            .image_groups
+            .iter()
+            .map(|g| g.folder.as_str())
+            .collect();
+        assert_eq!(folders, ["ch1", "ch2"]);
+        assert_eq!(vol1.image_groups[0].names, ["ch1/001.jpg", "ch1/002.jpg"]);
+
+        let inner_box = analyze_contents(&ArchiveCache::default(), &inner_box.path).unwrap();
+        let vol2 = &inner_box.nested[0];
+        assert_eq!(vol2.path, format!("{outer}!/box.zip!/vol2.cbz"));
+        assert_eq!(
+            list_images(&ArchiveCache::default(), &vol2.path).unwrap(),
+            ["001.jpg"]
+        );
+        let cache = ArchiveCache::default();
+        assert_eq!(read_entry(&cache, &vol2.path, "001.jpg").unwrap(), b"deep");
+        assert_eq!(
+            read_entry(&cache, &vol1.path, "ch2/001.jpg").unwrap(),
+            b"ch2-1"
+        );
+    }
+
+    #[test]
+    fn test_nested_zips_are_read_from_memory() {
+        let mut tar = ::tar::Builder::new(Vec::new());
+        let mut header = ::tar::Header::new_gnu();
+        header.set_size(4);
+        header.set_cksum();
+        tar.append_data(&mut header, "001.jpg", &b"page"[..])
+            .unwrap();
+        let tar = tar.into_inner().unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let outer = dir.path().join("outer.zip");
+        std::fs::write(
+            &outer,
+            zip_bytes(&[
+                ("inner.cbz", &zip_bytes(&[("001.jpg", b"zip-page")])),
+                ("inner.tar", &tar),
+            ]),
+        )
+        .unwrap();
+        let outer = outer.to_str().unwrap();
+
+        let zip_path = format!("{outer}!/inner.cbz");
+        assert_eq!(
+            virtual_path::resolve(&ArchiveCache::default(), &zip_path).unwrap(),
+            zip_path
+        );
+        assert!(!Path::new(&zip_path).exists());
+        let cache = ArchiveCache::default();
+        assert_eq!(
+            read_entry(&cache, &zip_path, "001.jpg").unwrap(),
+            b"zip-page"
+        );
+
+        // Other formats are still read from a file
+        let tar_path = format!("{outer}!/inner.tar");
+        let extracted = virtual_path::resolve(&ArchiveCache::default(), &tar_path).unwrap();
+        assert!(extracted.ends_with("inner.tar"));
+        assert!(Path::new(&extracted).is_file());
+        assert_eq!(read_entry(&cache, &tar_path, "001.jpg").unwrap(), b"page");
+    }
+
+    #[test]
+    fn test_extracted_archives_are_released_with_their_windows() {
+        let mut tar = ::tar::Builder::new(Vec::new());
+        let mut header = ::tar::Header::new_gnu();
+        header.set_size(4);
+        header.set_cksum();
+        tar.append_data(&mut header, "001.jpg", &b"page"[..])
+            .unwrap();
+        let tar = tar.into_inner().unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let outer = dir.path().join("outer.zip");
+        std::fs::write(&outer, zip_bytes(&[("inner.tar", &tar)])).unwrap();
+        let outer = outer.to_str().unwrap();
+        let other = dir.path().join("other");
+        std::fs::create_dir(&other).unwrap();
+        let other = other.to_str().unwrap();
+
+        let cache = ArchiveCache::default();
+        let tar_path = format!("{outer}!/inner.tar");
+        open_nested(&cache, "viewer-a", &tar_path);
+        open_nested(&cache, "viewer-b", &tar_path);
+        let extracted = virtual_path::resolve(&ArchiveCache::default(), &tar_path).unwrap();
+        // Extracted once, however often it is asked for
+        assert_eq!(
+            virtual_path::resolve(&ArchiveCache::default(), &tar_path).unwrap(),
+            extracted
+        );
+        assert!(Path::new(&extracted)
+            .ancestors()
+            .any(|dir| dir.to_string_lossy().contains("/mekuri-")));
+
+        // Kept while another window still shows the archive
+        close_nested(&cache, "viewer-a");
+        assert!(Path::new(&extracted).is_file());
+        open_nested(&cache, "viewer-b", other);
+        assert!(!Path::new(&extracted).exists());
+
+        // Extracted again when needed after all
+        assert_eq!(read_entry(&cache, &tar_path, "001.jpg").unwrap(), b"page");
+        close_nested(&cache, "viewer-b");
+    }
+
+    #[test]
+    fn test_only_archives_shown_stay_resolved() {
+        let mut tar = ::tar::Builder::new(Vec::new());
+        let mut header = ::tar::Header::new_gnu();
+        header.set_size(4);
+        header.set_cksum();
+        tar.append_data(&mut header, "001.jpg", &b"page"[..])
+            .unwrap();
+        let tar = tar.into_inner().unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let outer = dir.path().join("omnibus.zip");
+        let inner_box = zip_bytes(&[("vol2.tar", &tar)]);
+        std::fs::write(
+            &outer,
+            zip_bytes(&[("vol1.tar", &tar), ("box.zip", &inner_box)]),
+        )
+        .unwrap();
+        let outer = outer.to_str().unwrap();
+        let vol1 = format!("{outer}!/vol1.tar");
+        let inner_box = format!("{outer}!/box.zip");
+        let vol2 = format!("{inner_box}!/vol2.tar");
+
+        let cache = ArchiveCache::default();
+        open_nested(&cache, "viewer-shown", &vol2);
+        let vol2_file = virtual_path::resolve(&ArchiveCache::default(), &vol2).unwrap();
+        assert!(virtual_path::in_memory(&inner_box).is_some());
+
+        // Choosing another volume lets go of the one before and what it was read through
+        open_nested(&cache, "viewer-shown", &vol1);
+        let vol1_file = virtual_path::resolve(&ArchiveCache::default(), &vol1).unwrap();
+        assert!(!Path::new(&vol2_file).exists());
+        assert!(virtual_path::in_memory(&inner_box).is_none());
+
+        assert!(Path::new(&vol1_file).is_file());
+        close_nested(&cache, "viewer-shown");
+        assert!(!Path::new(&vol1_file).exists());
+    }
+
+    #[test]
+    fn test_password_of_nested_archive_outlasts_its_extraction() {
+        let fixture = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/encrypted-headers.rar"
+        );
+        let dir = tempfile::tempdir().unwrap();
+        let outer = dir.path().join("outer.zip");
+        let rar = std::fs::read(fixture).unwrap();
+        std::fs::write(&outer, zip_bytes(&[("locked.cbr", &rar)])).unwrap();
+        let outer = outer.to_str().unwrap();
+        let rar_path = format!("{outer}!/locked.cbr");
+
+        let cache = ArchiveCache::default();
+        open_nested(&cache, "viewer-password", outer);
+        set_password(&cache, &rar_path, "secret").unwrap();
+        let extracted = virtual_path::resolve(&ArchiveCache::default(), &rar_path).unwrap();
+        assert_eq!(
+            list_images(&ArchiveCache::default(), &rar_path).unwrap(),
+            ["001.jpg"]
+        );
+
+        // Closing the window deletes the extracted file; the next one is another file
+        close_nested(&cache, "viewer-password");
+        assert!(!Path::new(&extracted).exists());
+        open_nested(&cache, "viewer-password", outer);
+        assert_ne!(
+            virtual_path::resolve(&ArchiveCache::default(), &rar_path).unwrap(),
+            extracted
+        );
+        assert_eq!(
+            list_images(&ArchiveCache::default(), &rar_path).unwrap(),
+            ["001.jpg"]
+        );
+        close_nested(&cache, "viewer-password");
     }
 
     #[test]
     fn test_rar_get_image_nonexistent() {
-        let result = get_image_base64("nonexistent.rar", "image.jpg");
+        let result = get_image_base64(&ArchiveCache::default(), "nonexistent.rar", "image.jpg");
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_bulk_extraction_skips_non_rar() {
+        let dir = tempfile::tempdir().unwrap();
+        let result = start_bulk_extraction(
+            &ArchiveCache::default(),
+            dir.path().to_str().unwrap(),
+            |_| panic!("no progress expected"),
+        );
+        assert!(!result.unwrap());
+    }
 }
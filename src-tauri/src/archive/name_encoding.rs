@@ -0,0 +1,143 @@
+use encoding_rs::{Encoding, EUC_KR, GBK, SHIFT_JIS};
+
+/// Tells whether a character is unlikely in a name written in a given encoding.
+type Unlikely = fn(char) -> bool;
+
+/// Legacy encodings tried for names that are not UTF-8, preferred in this order when
+/// they fit the names equally well. `SHIFT_JIS` is the CP932 superset Windows writes.
+const LEGACY_ENCODINGS: [(&Encoding, Unlikely); 3] = [
+    (SHIFT_JIS, is_halfwidth_katakana),
+    (EUC_KR, is_han),
+    (GBK, |_| false),
+];
+
+/// Upper half of code page 437, the encoding ZIP names fall back to per APPNOTE.
+const CP437_HIGH: &str = concat!(
+    "ÇüéâäàåçêëèïîìÄÅ",
+    "ÉæÆôöòûùÿÖÜ¢£¥₧ƒ",
+    "áíóúñÑªº¿⌐¬½¼¡«»",
+    "░▒▓│┤╡╢╖╕╣║╗╝╜╛┐",
+    "└┴┬├─┼╞╟╚╔╩╦╠═╬╧",
+    "╨╤╥╙╘╒╓╫╪┘┌█▄▌▐▀",
+    "αßΓπΣσµτΦΘΩδ∞φε∩",
+    "≡±≥≤⌠⌡÷≈°∙·√ⁿ²■\u{a0}",
+);
+
+/// Decode the raw entry names of one archive.
+///
+/// Names that are valid UTF-8 are taken as they are, whether or not the entry has the
+/// UTF-8 flag; plenty of tools write UTF-8 without setting it. The rest were written in
+/// the creating system's code page, which the archive does not record, so they are
+/// decoded together with the legacy encoding that fits all of them best (Shift_JIS,
+/// EUC-KR or GBK), or as CP437 when none decodes them all cleanly.
+pub fn decode_names(raw_names: &[Vec<u8>]) -> Vec<String> {
+    let legacy: Vec<&[u8]> = raw_names
+        .iter()
+        .filter(|raw| std::str::from_utf8(raw).is_err())
+        .map(Vec::as_slice)
+        .collect();
+    let encoding = guess_encoding(&legacy);
+
+    raw_names
+        .iter()
+        .map(|raw| match std::str::from_utf8(raw) {
+            Ok(name) => name.to_string(),
+            Err(_) => match encoding {
+                Some(encoding) => encoding.decode_without_bom_handling(raw).0.into_owned(),
+                None => decode_cp437(raw),
+            },
+        })
+        .collect()
+}
+
+/// The legacy encoding that decodes every name without errors and with the fewest
+/// characters unlikely in a file name written in it.
+fn guess_encoding(names: &[&[u8]]) -> Option<&'static Encoding> {
+    if names.is_empty() {
+        return None;
+    }
+    LEGACY_ENCODINGS
+        .iter()
+        .filter_map(|&(encoding, unlikely)| {
+            let mut score = 0;
+            for name in names {
+                let decoded = encoding.decode_without_bom_handling_and_without_replacement(name)?;
+                score += decoded
+                    .chars()
+                    .filter(|&c| unlikely(c) || is_never_in_names(c))
+                    .count();
+            }
+            Some((score, encoding))
+        })
+        // min_by_key keeps the first of equal scores, i.e. the preferred encoding
+        .min_by_key(|&(score, _)| score)
+        .map(|(_, encoding)| encoding)
+}
+
+/// C1 controls and private use characters, which decoding with the wrong code page
+/// produces but real names do not contain.
+fn is_never_in_names(c: char) -> bool {
+    matches!(c, '\u{80}'..='\u{9f}' | '\u{e000}'..='\u{f8ff}')
+}
+
+/// Half-width katakana: rare in Japanese names, but what the lead bytes of Korean and
+/// Chinese text turn into when read as Shift_JIS.
+fn is_halfwidth_katakana(c: char) -> bool {
+    matches!(c, '\u{ff61}'..='\u{ff9f}')
+}
+
+/// Han characters: rare in Korean names, but common when Chinese text is read as EUC-KR.
+fn is_han(c: char) -> bool {
+    matches!(c, '\u{4e00}'..='\u{9fff}' | '\u{f900}'..='\u{faff}')
+}
+
+fn decode_cp437(raw: &[u8]) -> String {
+    raw.iter()
+        .map(|&b| match b {
+            0x00..=0x7f => b as char,
+            _ => CP437_HIGH.chars().nth(usize::from(b - 0x80)).unwrap_or('?'),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(encoding: &'static Encoding, name: &str) -> Vec<u8> {
+        let (bytes, _, unmappable) = encoding.encode(name);
+        assert!(!unmappable, "{name}");
+        bytes.into_owned()
+    }
+
+    #[test]
+    fn test_utf8_names_are_kept() {
+        let raw = vec![b"01.jpg".to_vec(), "表紙.jpg".as_bytes().to_vec()];
+        assert_eq!(decode_names(&raw), vec!["01.jpg", "表紙.jpg"]);
+    }
+
+    #[test]
+    fn test_decodes_legacy_encodings() {
+        for (encoding, names) in [
+            (SHIFT_JIS, ["第01話/表紙.jpg", "カラー口絵.png"]),
+            (EUC_KR, ["제1화/표지.jpg", "컬러 일러스트.png"]),
+            (GBK, ["第一话/封面.jpg", "彩色插图.png"]),
+        ] {
+            let mut raw: Vec<Vec<u8>> = names.iter().map(|n| encode(encoding, n)).collect();
+            raw.push(b"ascii.jpg".to_vec());
+            assert_eq!(
+                decode_names(&raw),
+                [names[0], names[1], "ascii.jpg"],
+                "{}",
+                encoding.name()
+            );
+        }
+    }
+
+    #[test]
+    fn test_falls_back_to_cp437() {
+        let raw = vec![b"Caf\x82 \xe1.jpg".to_vec()];
+        assert_eq!(decode_names(&raw), vec!["Café ß.jpg"]);
+        assert_eq!(CP437_HIGH.chars().count(), 128);
+    }
+}
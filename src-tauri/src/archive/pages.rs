@@ -0,0 +1,321 @@
+use super::comic_info::ArchiveMetadata;
+use super::sort::{self, SortMode};
+use super::{image_info, listed_entries, read_metadata, ArchiveCache};
+use crate::error::MekuriError;
+use serde::Serialize;
+
+/// ComicInfo page types with a meaning for the layout.
+const FRONT_COVER: &str = "FrontCover";
+const DELETED: &str = "Deleted";
+
+/// An image of an archive in reading order, with the layout hints from its metadata.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PageEntry {
+    pub name: String,
+    /// Marked `FrontCover`; shown on its own
+    pub cover: bool,
+    /// A two-page spread stored as one wide image; shown on its own
+    pub double_page: bool,
+    /// Marked `Deleted`; kept in the list so callers can decide whether to hide it
+    pub deleted: bool,
+}
+
+/// Sizes and pixel dimensions of an image entry, as far as the backend can tell them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct EntryInfo {
+    /// Size stored in the archive; `None` when the format does not record it
+    pub compressed_size: Option<u64>,
+    pub size: Option<u64>,
+    /// Read from the image header; `None` for formats the header parser does not know
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+}
+
+impl EntryInfo {
+    pub(super) fn new(
+        compressed_size: Option<u64>,
+        size: u64,
+        dimensions: Option<(u32, u32)>,
+    ) -> Self {
+        Self {
+            compressed_size,
+            size: Some(size),
+            width: dimensions.map(|(width, _)| width),
+            height: dimensions.map(|(_, height)| height),
+        }
+    }
+}
+
+/// A page with the sizes and dimensions of its image.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PageInfo {
+    #[serde(flatten)]
+    pub page: PageEntry,
+    #[serde(flatten)]
+    pub info: EntryInfo,
+}
+
+/// List the pages of an archive in `sort` order, flagged by its ComicInfo.xml when present.
+///
+/// In natural order, a ComicInfo.xml that lists every page also decides their order;
+/// any other mode is an explicit choice and keeps its own order. Without usable metadata
+/// this is `list_images` sorted by `sort` with no flags set.
+pub fn list_pages(
+    cache: &ArchiveCache,
+    archive_path: &str,
+    sort: SortMode,
+) -> Result<Vec<PageEntry>, MekuriError> {
+    let mut entries = listed_entries(cache, archive_path)?;
+    // ComicInfo numbers pages in natural order, whichever order they are shown in
+    sort::sort_entries(&mut entries, SortMode::Natural);
+    let names = entries.iter().map(|entry| entry.id.name.clone()).collect();
+    // A broken ComicInfo.xml only loses the hints, not the pages
+    let metadata = read_metadata(cache, archive_path).ok().flatten();
+    let natural = sort == SortMode::Natural;
+    let pages = apply_hints(names, metadata.as_ref(), natural);
+    if natural {
+        return Ok(pages);
+    }
+
+    let mut sorted: Vec<_> = entries.into_iter().zip(pages).collect();
+    sorted.sort_by(|(a, _), (b, _)| sort::compare(a, b, sort));
+    Ok(sorted.into_iter().map(|(_, page)| page).collect())
+}
+
+/// `list_pages` with the sizes and pixel dimensions of every page.
+///
+/// Dimensions come from the first bytes of each image. RAR entries can only be read whole,
+/// so pages of a solid RAR archive are measured only once they have been decompressed.
+pub fn list_pages_with_info(
+    cache: &ArchiveCache,
+    archive_path: &str,
+    sort: SortMode,
+) -> Result<Vec<PageInfo>, MekuriError> {
+    let pages = list_pages(cache, archive_path, sort)?;
+    let mut infos = image_info(cache, archive_path)?;
+    Ok(pages
+        .into_iter()
+        .map(|page| PageInfo {
+            info: infos.remove(&page.name).unwrap_or_default(),
+            page,
+        })
+        .collect())
+}
+
+/// Flag `names` (in natural order) by the `<Pages>` of `metadata`, and with `reorder`,
+/// reorder them when the metadata lists every page.
+///
+/// ComicInfo numbers pages by their index in the sorted image list and lists them in
+/// reading order. Some taggers only list the pages they have something to say about, so a
+/// partial list flags pages without moving them. References to images that do not exist
+/// and repeated references are ignored.
+fn apply_hints(
+    names: Vec<String>,
+    metadata: Option<&ArchiveMetadata>,
+    reorder: bool,
+) -> Vec<PageEntry> {
+    let mut pages: Vec<PageEntry> = names
+        .into_iter()
+        .map(|name| PageEntry {
+            name,
+            cover: false,
+            double_page: false,
+            deleted: false,
+        })
+        .collect();
+
+    let mut order = Vec::with_capacity(pages.len());
+    let mut seen = vec![false; pages.len()];
+    for hint in metadata.map(|m| m.pages.as_slice()).unwrap_or_default() {
+        let Some(page) = pages.get_mut(hint.image) else {
+            continue;
+        };
+        if std::mem::replace(&mut seen[hint.image], true) {
+            continue;
+        }
+        let page_type = hint.page_type.as_deref();
+        page.cover = page_type == Some(FRONT_COVER);
+        page.deleted = page_type == Some(DELETED);
+        page.double_page = hint.double_page;
+        order.push(hint.image);
+    }
+
+    if !reorder || order.len() < pages.len() {
+        return pages;
+    }
+    let mut slots: Vec<Option<PageEntry>> = pages.into_iter().map(Some).collect();
+    order
+        .iter()
+        .filter_map(|&index| slots[index].take())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::archive::comic_info::PageMetadata;
+    use std::io::Write;
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|n| n.to_string()).collect()
+    }
+
+    fn hint(image: usize, page_type: Option<&str>, double_page: bool) -> PageMetadata {
+        PageMetadata {
+            image,
+            page_type: page_type.map(str::to_string),
+            double_page,
+            ..Default::default()
+        }
+    }
+
+    fn page_names(pages: &[PageEntry]) -> Vec<&str> {
+        pages.iter().map(|p| p.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_without_metadata_keeps_natural_order() {
+        let pages = apply_hints(names(&["1.jpg", "2.jpg"]), None, true);
+        assert_eq!(page_names(&pages), vec!["1.jpg", "2.jpg"]);
+        assert!(pages
+            .iter()
+            .all(|p| !p.cover && !p.double_page && !p.deleted));
+    }
+
+    #[test]
+    fn test_flags_pages_from_hints() {
+        let metadata = ArchiveMetadata {
+            pages: vec![
+                hint(0, Some("FrontCover"), false),
+                hint(1, Some("Story"), true),
+                hint(2, Some("Deleted"), false),
+            ],
+            ..Default::default()
+        };
+        let pages = apply_hints(names(&["1.jpg", "2.jpg", "3.jpg"]), Some(&metadata), true);
+
+        assert!(pages[0].cover);
+        assert!(pages[1].double_page && !pages[1].cover);
+        assert!(pages[2].deleted);
+    }
+
+    #[test]
+    fn test_hints_reorder_pages() {
+        let metadata = ArchiveMetadata {
+            // The cover is stored last but read first; image 9 does not exist; 2 is repeated
+            pages: vec![
+                hint(3, Some("FrontCover"), false),
+                hint(0, None, false),
+                hint(9, None, false),
+                hint(2, None, false),
+                hint(2, None, true),
+                hint(1, None, false),
+            ],
+            ..Default::default()
+        };
+        let pages = apply_hints(
+            names(&["1.jpg", "2.jpg", "3.jpg", "cover.jpg"]),
+            Some(&metadata),
+            true,
+        );
+
+        assert_eq!(
+            page_names(&pages),
+            vec!["cover.jpg", "1.jpg", "3.jpg", "2.jpg"]
+        );
+        assert!(pages[0].cover);
+        assert!(!pages[2].double_page);
+    }
+
+    #[test]
+    fn test_list_pages_reads_comic_info() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let mut writer = zip::ZipWriter::new(std::io::BufWriter::new(file.as_file()));
+        let options = zip::write::SimpleFileOptions::default();
+        let xml = r#"<ComicInfo><Pages><Page Image="1" DoublePage="true" /></Pages></ComicInfo>"#;
+        for (name, data) in [
+            ("01.jpg", b"a".as_slice()),
+            ("02.jpg", b"b"),
+            ("ComicInfo.xml", xml.as_bytes()),
+        ] {
+            writer.start_file(name, options).unwrap();
+            writer.write_all(data).unwrap();
+        }
+        writer.finish().unwrap();
+
+        let pages = list_pages(
+            &ArchiveCache::default(),
+            file.path().to_str().unwrap(),
+            SortMode::Natural,
+        )
+        .unwrap();
+        // A partial list flags pages in place
+        assert_eq!(page_names(&pages), vec!["01.jpg", "02.jpg"]);
+        assert!(!pages[0].double_page);
+        assert!(pages[1].double_page);
+    }
+
+    #[test]
+    fn test_chosen_sort_mode_keeps_its_order() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let mut writer = zip::ZipWriter::new(std::io::BufWriter::new(file.as_file()));
+        let options = zip::write::SimpleFileOptions::default();
+        let xml = r#"<ComicInfo><Pages><Page Image="1" Type="FrontCover" /><Page Image="0" /></Pages></ComicInfo>"#;
+        for (name, data) in [
+            ("ComicInfo.xml", xml.as_bytes()),
+            ("p2.jpg", b"a"),
+            ("p10.jpg", b"b"),
+        ] {
+            writer.start_file(name, options).unwrap();
+            writer.write_all(data).unwrap();
+        }
+        writer.finish().unwrap();
+        let path = file.path().to_str().unwrap();
+        let cache = ArchiveCache::default();
+
+        // The complete page list reorders natural order...
+        let pages = list_pages(&cache, path, SortMode::Natural).unwrap();
+        assert_eq!(page_names(&pages), vec!["p10.jpg", "p2.jpg"]);
+        // ...but not an order picked by the reader, which still gets the flags
+        let pages = list_pages(&cache, path, SortMode::Lexicographic).unwrap();
+        assert_eq!(page_names(&pages), vec!["p10.jpg", "p2.jpg"]);
+        let pages = list_pages(&cache, path, SortMode::ArchiveOrder).unwrap();
+        assert_eq!(page_names(&pages), vec!["p2.jpg", "p10.jpg"]);
+        assert!(pages[1].cover && !pages[0].cover);
+    }
+
+    #[test]
+    fn test_list_pages_with_info_reads_dimensions() {
+        let mut png = std::io::Cursor::new(Vec::new());
+        image::RgbImage::new(64, 48)
+            .write_to(&mut png, image::ImageFormat::Png)
+            .unwrap();
+        let png = png.into_inner();
+
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let mut writer = zip::ZipWriter::new(std::io::BufWriter::new(file.as_file()));
+        let options = zip::write::SimpleFileOptions::default();
+        for (name, data) in [("01.png", png.as_slice()), ("02.jpg", b"broken")] {
+            writer.start_file(name, options).unwrap();
+            writer.write_all(data).unwrap();
+        }
+        writer.finish().unwrap();
+
+        let pages = list_pages_with_info(
+            &ArchiveCache::default(),
+            file.path().to_str().unwrap(),
+            SortMode::Natural,
+        )
+        .unwrap();
+        assert_eq!(pages[0].page.name, "01.png");
+        assert_eq!(pages[0].info.size, Some(png.len() as u64));
+        assert!(pages[0].info.compressed_size.is_some());
+        assert_eq!(
+            (pages[0].info.width, pages[0].info.height),
+            (Some(64), Some(48))
+        );
+        // Unreadable headers keep the sizes but not the dimensions
+        assert_eq!(pages[1].info.size, Some(6));
+        assert_eq!(pages[1].info.width, None);
+    }
+}
@@ -0,0 +1,68 @@
+use super::virtual_path;
+use crate::error::MekuriError;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+/// Passwords entered for encrypted archives, by virtual path. A nested archive may be
+/// extracted to a different file each time it is opened, so its password is not tied to
+/// the file. Kept in memory only, so they last until the app closes.
+static PASSWORDS: Mutex<BTreeMap<String, String>> = Mutex::new(BTreeMap::new());
+
+/// Remember the password of an archive, replacing any earlier one.
+pub fn remember(archive_path: &str, password: &str) {
+    if let Ok(mut passwords) = PASSWORDS.lock() {
+        passwords.insert(key(archive_path), password.to_string());
+    }
+}
+
+/// Forget the password of an archive, e.g. after it turned out to be wrong.
+pub fn forget(archive_path: &str) {
+    if let Ok(mut passwords) = PASSWORDS.lock() {
+        passwords.remove(&key(archive_path));
+    }
+}
+
+/// The password entered for an archive, if any. Takes the virtual path or what it
+/// resolved to, as the backends only see the latter.
+pub fn lookup(archive_path: &str) -> Option<String> {
+    let key = key(archive_path);
+    PASSWORDS.lock().ok()?.get(&key).cloned()
+}
+
+fn key(archive_path: &str) -> String {
+    virtual_path::virtual_path_of(archive_path)
+}
+
+/// Fail with `PasswordRequired` when an archive has encrypted pages and no password has
+/// been entered for it, so callers can ask for one before reading any page.
+pub fn require(archive_path: &str, encrypted: bool) -> Result<(), MekuriError> {
+    if encrypted && lookup(archive_path).is_none() {
+        return Err(MekuriError::PasswordRequired(format!(
+            "Password required to open archive: {archive_path}"
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_remember_and_forget() {
+        let path = "/test/password/book.cbz";
+        assert!(require(path, false).is_ok());
+        assert!(matches!(
+            require(path, true),
+            Err(MekuriError::PasswordRequired(_))
+        ));
+
+        remember(path, "first");
+        remember(path, "second");
+        assert_eq!(lookup(path).as_deref(), Some("second"));
+        assert!(require(path, true).is_ok());
+
+        forget(path);
+        assert_eq!(lookup(path), None);
+    }
+}
@@ -0,0 +1,211 @@
+use super::{read_entry, ArchiveCache};
+use std::sync::{Arc, Condvar, Mutex};
+
+/// Number of background threads reading pages.
+const DEFAULT_WORKERS: usize = 2;
+
+/// A page waiting to be read into the cache.
+struct Job {
+    archive_path: String,
+    entry_name: String,
+    priority: u8,
+    /// Submission order, so pages of equal priority are read in the order requested
+    seq: u64,
+}
+
+#[derive(Default)]
+struct Queue {
+    jobs: Vec<Job>,
+    next_seq: u64,
+    shutdown: bool,
+}
+
+struct Shared {
+    cache: ArchiveCache,
+    queue: Mutex<Queue>,
+    available: Condvar,
+}
+
+/// Reads upcoming pages into the `ArchiveCache` on background threads, so they are
+/// already in memory when the viewer asks for them.
+///
+/// Each request replaces the pages still pending for that archive, which cancels
+/// prefetching for spreads the viewer has jumped away from.
+pub struct Prefetcher {
+    shared: Arc<Shared>,
+}
+
+impl Prefetcher {
+    /// Start the worker threads. They share `cache` with the page requests.
+    pub fn new(cache: ArchiveCache) -> Self {
+        let shared = Arc::new(Shared {
+            cache,
+            queue: Mutex::new(Queue::default()),
+            available: Condvar::new(),
+        });
+        for _ in 0..DEFAULT_WORKERS {
+            let shared = shared.clone();
+            std::thread::spawn(move || worker(&shared));
+        }
+        Self { shared }
+    }
+
+    /// Queue pages of an archive for reading, dropping any still pending for it.
+    /// Higher `priority` requests are served first; `entries` are read in order.
+    pub fn prefetch(&self, archive_path: &str, entries: Vec<String>, priority: u8) {
+        let Ok(mut queue) = self.shared.queue.lock() else {
+            return;
+        };
+        queue.jobs.retain(|job| job.archive_path != archive_path);
+        for entry_name in entries {
+            let seq = queue.next_seq;
+            queue.next_seq += 1;
+            queue.jobs.push(Job {
+                archive_path: archive_path.to_string(),
+                entry_name,
+                priority,
+                seq,
+            });
+        }
+        self.shared.available.notify_all();
+    }
+
+    /// Drop all pending pages of an archive. Pages already being read still finish.
+    pub fn cancel(&self, archive_path: &str) {
+        if let Ok(mut queue) = self.shared.queue.lock() {
+            queue.jobs.retain(|job| job.archive_path != archive_path);
+        }
+    }
+
+    #[cfg(test)]
+    fn pending(&self) -> usize {
+        self.shared.queue.lock().unwrap().jobs.len()
+    }
+}
+
+impl Drop for Prefetcher {
+    fn drop(&mut self) {
+        if let Ok(mut queue) = self.shared.queue.lock() {
+            queue.shutdown = true;
+        }
+        self.shared.available.notify_all();
+    }
+}
+
+/// Take the most urgent job, blocking until one is queued. Returns `None` on shutdown.
+fn next_job(shared: &Shared) -> Option<Job> {
+    let mut queue = shared.queue.lock().ok()?;
+    loop {
+        if queue.shutdown {
+            return None;
+        }
+        let next = queue
+            .jobs
+            .iter()
+            .enumerate()
+            .max_by(|(_, a), (_, b)| a.priority.cmp(&b.priority).then(b.seq.cmp(&a.seq)))
+            .map(|(index, _)| index);
+        if let Some(index) = next {
+            return Some(queue.jobs.remove(index));
+        }
+        queue = shared.available.wait(queue).ok()?;
+    }
+}
+
+fn worker(shared: &Shared) {
+    while let Some(job) = next_job(shared) {
+        // Failures surface when the viewer requests the page itself
+        let _ = read_entry(&shared.cache, &job.archive_path, &job.entry_name);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::time::{Duration, Instant};
+
+    fn create_test_zip(entries: &[(&str, &[u8])]) -> tempfile::NamedTempFile {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let mut writer = zip::ZipWriter::new(std::io::BufWriter::new(file.as_file()));
+        let options = zip::write::SimpleFileOptions::default();
+
+        for (name, data) in entries {
+            writer.start_file(*name, options).unwrap();
+            writer.write_all(data).unwrap();
+        }
+        writer.finish().unwrap();
+        file
+    }
+
+    fn is_cached(cache: &ArchiveCache, path: &str, entry_name: &str) -> bool {
+        let modified = super::super::cache::modified_time(path).unwrap();
+        cache.cached_page(path, modified, entry_name).is_some()
+    }
+
+    #[test]
+    fn test_prefetch_fills_cache() {
+        let zip_file = create_test_zip(&[("01.jpg", b"first"), ("02.jpg", b"second")]);
+        let path = zip_file.path().to_str().unwrap();
+        let cache = ArchiveCache::default();
+        let prefetcher = Prefetcher::new(cache.clone());
+
+        prefetcher.prefetch(path, vec!["01.jpg".into(), "02.jpg".into()], 0);
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !(is_cached(&cache, path, "01.jpg") && is_cached(&cache, path, "02.jpg")) {
+            assert!(Instant::now() < deadline, "pages were not prefetched");
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(read_entry(&cache, path, "02.jpg").unwrap(), b"second");
+    }
+
+    /// A prefetcher without worker threads, so queued jobs stay pending.
+    fn idle_prefetcher() -> Prefetcher {
+        Prefetcher {
+            shared: Arc::new(Shared {
+                cache: ArchiveCache::default(),
+                queue: Mutex::new(Queue::default()),
+                available: Condvar::new(),
+            }),
+        }
+    }
+
+    #[test]
+    fn test_new_request_and_cancel_drop_pending_pages() {
+        let prefetcher = idle_prefetcher();
+
+        prefetcher.prefetch("a.zip", vec!["1.jpg".into(), "2.jpg".into()], 0);
+        prefetcher.prefetch("b.zip", vec!["1.jpg".into()], 1);
+        assert_eq!(prefetcher.pending(), 3);
+
+        // Jumping elsewhere in `a.zip` replaces its pending pages
+        prefetcher.prefetch("a.zip", vec!["9.jpg".into()], 0);
+        assert_eq!(prefetcher.pending(), 2);
+
+        prefetcher.cancel("b.zip");
+        assert_eq!(prefetcher.pending(), 1);
+    }
+
+    #[test]
+    fn test_next_job_prefers_priority_then_order() {
+        let prefetcher = idle_prefetcher();
+        let shared = &prefetcher.shared;
+        {
+            let mut queue = shared.queue.lock().unwrap();
+            for (seq, (entry, priority)) in [("a", 0), ("b", 1), ("c", 1)].iter().enumerate() {
+                queue.jobs.push(Job {
+                    archive_path: "x.zip".into(),
+                    entry_name: entry.to_string(),
+                    priority: *priority,
+                    seq: seq as u64,
+                });
+            }
+        }
+
+        let order: Vec<String> = (0..3)
+            .map(|_| next_job(shared).unwrap().entry_name)
+            .collect();
+        assert_eq!(order, vec!["b", "c", "a"]);
+    }
+}
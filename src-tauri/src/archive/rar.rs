@@ -1,166 +1,756 @@
-use super::{is_archive_file, is_image_file, mime_type_from_name, store_temp_dir, ArchiveContents};
-use base64::Engine;
-use std::io::Write;
-use std::path::Path;
-use unrar::Archive;
-
-/// List image file names inside a RAR archive, sorted by natural order.
-pub fn list_images(archive_path: &str) -> Result<Vec<String>, String> {
-    let archive = Archive::new(archive_path)
-        .open_for_listing()
-        .map_err(|e| format!("Failed to open RAR archive: {e}"))?;
-
-    let mut names: Vec<String> = archive
-        .filter_map(|entry| {
-            let entry = entry.ok()?;
-            let name = entry.filename.to_string_lossy().to_string();
-            if entry.is_file() && is_image_file(&name) && !name.contains("__MACOSX") {
-                Some(name)
-            } else {
-                None
-            }
-        })
-        .collect();
+use super::comic_info::find_comic_info;
+use super::image_header::{dimensions, read_dimensions};
+use super::password;
+use super::sort::ListedEntry;
+use super::volume;
+use super::{is_image_file, ArchiveCache, EntryId, EntryInfo, ExtractProgress, Listing};
+use crate::error::MekuriError;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Condvar, Mutex, PoisonError};
+use unrar::error::{Code, UnrarError, UnrarResult};
+use unrar::{Archive, CursorBeforeHeader, OpenArchive, Process};
 
-    names.sort_by(|a, b| natord::compare(a, b));
-    Ok(names)
+/// Sort an unrar error into a `MekuriError`, prefixing its message with `context`.
+fn rar_error(context: &str, err: UnrarError) -> MekuriError {
+    let message = format!("{context}: {err}");
+    match err.code {
+        Code::MissingPassword | Code::BadPassword => MekuriError::PasswordRequired(message),
+        Code::BadData | Code::EReference => MekuriError::Corrupt(message),
+        Code::BadArchive | Code::UnknownFormat => MekuriError::UnsupportedFormat(message),
+        // A missing archive, or a missing next volume while reading
+        Code::EOpen => MekuriError::NotFound(message),
+        _ => MekuriError::Io(message),
+    }
 }
 
-/// Analyze RAR archive contents to determine if it contains images or nested archives.
-pub fn analyze_contents(archive_path: &str) -> Result<ArchiveContents, String> {
-    let archive = Archive::new(archive_path)
-        .open_for_listing()
-        .map_err(|e| format!("Failed to open RAR archive: {e}"))?;
+/// Sort an error from reading an entry. RAR4 cannot check a password before decrypting, so
+/// a wrong one shows up as bad data; on an encrypted entry that is reported as such.
+fn read_error(archive_path: &str, encrypted: bool, err: UnrarError) -> MekuriError {
+    if encrypted && err.code == Code::BadData {
+        return MekuriError::PasswordRequired(format!(
+            "Incorrect password for archive: {archive_path}"
+        ));
+    }
+    rar_error("Failed to read entry", err)
+}
 
-    let mut images: Vec<String> = Vec::new();
-    let mut nested_archives: Vec<String> = Vec::new();
+/// Open a RAR archive with `open` (listing or processing), supplying the password entered
+/// for it, if any.
+///
+/// unrar moves on to the next volume by itself, but only reads the volumes from the one it
+/// is given, so a later volume is opened from the first volume of its set instead.
+fn open_archive<T>(
+    archive_path: &str,
+    open: impl FnOnce(Archive) -> UnrarResult<T>,
+) -> Result<T, MekuriError> {
+    let password = password::lookup(archive_path);
+    let path = volume::first_volume(Path::new(archive_path))
+        .unwrap_or_else(|| PathBuf::from(archive_path));
+    let archive = match &password {
+        Some(password) => Archive::with_password(&path, password),
+        None => Archive::new(&path),
+    };
+    open(archive).map_err(|e| rar_error("Failed to open RAR archive", e))
+}
+
+/// List the image entries of a RAR archive with their header positions.
+pub fn list_entries(archive_path: &str) -> Result<Vec<ListedEntry>, MekuriError> {
+    let archive = open_archive(archive_path, |archive| archive.open_for_listing())?;
 
-    for entry in archive {
+    let mut encrypted = false;
+    let mut entries: Vec<ListedEntry> = Vec::new();
+    for (position, entry) in archive.enumerate() {
         let entry = match entry {
             Ok(e) => e,
+            // Encrypted headers cannot be listed without the right password
+            Err(e) if matches!(e.code, Code::MissingPassword | Code::BadPassword) => {
+                return Err(rar_error("Failed to read RAR header", e));
+            }
             Err(_) => continue,
         };
-        if !entry.is_file() {
-            continue;
-        }
         let name = entry.filename.to_string_lossy().to_string();
-        if name.contains("__MACOSX") {
-            continue;
-        }
-
-        if is_image_file(&name) {
-            images.push(name);
-        } else if is_archive_file(&name) {
-            nested_archives.push(name);
+        if entry.is_file() && is_image_file(&name) && !name.contains("__MACOSX") {
+            encrypted |= entry.is_encrypted();
+            // unrar reports the DOS date and time, which sort chronologically
+            let modified = Some(u64::from(entry.file_time));
+            entries.push(ListedEntry::new(position, name, modified));
         }
     }
+    password::require(archive_path, encrypted)?;
+    Ok(entries)
+}
 
-    // If we have images, return them
-    if !images.is_empty() {
-        images.sort_by(|a, b| natord::compare(a, b));
-        return Ok(ArchiveContents::Images { names: images });
-    }
-
-    // If we have nested archives, return them
-    if !nested_archives.is_empty() {
-        nested_archives.sort_by(|a, b| natord::compare(a, b));
-        return Ok(ArchiveContents::NestedArchives {
-            names: nested_archives,
-        });
-    }
+/// List the images and nested archives in a RAR archive.
+pub fn analyze_contents(archive_path: &str) -> Result<Listing, MekuriError> {
+    let archive = open_archive(archive_path, |archive| archive.open_for_listing())?;
 
-    Ok(ArchiveContents::Empty)
+    let files = archive
+        .filter_map(Result::ok)
+        .filter(|entry| entry.is_file())
+        .map(|entry| entry.filename.to_string_lossy().to_string())
+        .filter(|name| !name.contains("__MACOSX"));
+    Ok(Listing::new(files))
 }
 
-/// Extract a nested archive from a RAR file and return the path to the extracted file.
-pub fn extract_nested_archive(parent_path: &str, nested_name: &str) -> Result<String, String> {
-    let archive = Archive::new(parent_path)
-        .open_for_processing()
-        .map_err(|e| format!("Failed to open RAR archive: {e}"))?;
+/// Read a nested archive stored in a RAR archive.
+pub fn read_nested_archive(parent_path: &str, nested_name: &str) -> Result<Vec<u8>, MekuriError> {
+    let archive = open_archive(parent_path, |archive| archive.open_for_processing())?;
 
     let mut cursor = Some(archive);
 
     while let Some(open) = cursor {
         let header = open
             .read_header()
-            .map_err(|e| format!("Failed to read RAR header: {e}"))?;
+            .map_err(|e| rar_error("Failed to read RAR header", e))?;
 
         match header {
             Some(header) => {
                 let name = header.entry().filename.to_string_lossy().to_string();
                 if name == nested_name {
+                    let encrypted = header.entry().is_encrypted();
                     let (data, _) = header
                         .read()
-                        .map_err(|e| format!("Failed to read entry: {e}"))?;
-
-                    // Create temp directory and write file
-                    let temp_dir = tempfile::tempdir()
-                        .map_err(|e| format!("Failed to create temp directory: {e}"))?;
-
-                    let file_name = Path::new(&nested_name)
-                        .file_name()
-                        .and_then(|n| n.to_str())
-                        .unwrap_or("archive");
-                    let temp_path = temp_dir.path().join(file_name);
-
-                    let mut file = std::fs::File::create(&temp_path)
-                        .map_err(|e| format!("Failed to create temp file: {e}"))?;
-                    file.write_all(&data)
-                        .map_err(|e| format!("Failed to write temp file: {e}"))?;
-
-                    let result = temp_path.to_string_lossy().to_string();
-
-                    // Store temp dir to keep it alive
-                    store_temp_dir(temp_dir);
-
-                    return Ok(result);
+                        .map_err(|e| read_error(parent_path, encrypted, e))?;
+                    return Ok(data);
                 }
                 // Skip this entry
                 let next = header
                     .skip()
-                    .map_err(|e| format!("Failed to skip entry: {e}"))?;
+                    .map_err(|e| rar_error("Failed to skip entry", e))?;
                 cursor = Some(next);
             }
             None => break,
         }
     }
 
-    Err(format!("Nested archive not found: {nested_name}"))
+    Err(MekuriError::NotFound(format!(
+        "Nested archive not found: {nested_name}"
+    )))
 }
 
-/// Extract a single image from a RAR archive and return it as a Base64 data URL.
-pub fn get_image_base64(archive_path: &str, entry_name: &str) -> Result<String, String> {
-    let archive = Archive::new(archive_path)
-        .open_for_processing()
-        .map_err(|e| format!("Failed to open RAR archive: {e}"))?;
+/// Header position of every entry in a RAR archive, plus a cursor left just after the
+/// most recently read entry.
+///
+/// RAR has no central directory and unrar can only walk headers forward, so reading the
+/// next page continues from the cursor instead of rescanning from the start. Jumping
+/// backwards reopens the archive.
+pub(super) struct EntryIndex {
+    path: String,
+    /// Name of the entry at each header position
+    names: Vec<String>,
+    /// Position of the first entry of each name
+    positions: HashMap<String, usize>,
+    cursor: Option<Cursor>,
+    /// Solid archives compress entries as one stream, so reaching an entry decompresses
+    /// everything before it
+    solid: bool,
+    image_count: usize,
+    bulk: Option<Arc<BulkExtraction>>,
+}
 
-    let mut cursor = Some(archive);
+struct Cursor {
+    archive: OpenArchive<Process, CursorBeforeHeader>,
+    /// Position of the header the archive will read next
+    next: usize,
+}
+
+// SAFETY: the unrar handle is not tied to the thread that opened it. The cursor lives
+// behind the cache's per-archive mutex, so it is only ever used by one thread at a time.
+unsafe impl Send for Cursor {}
+
+impl EntryIndex {
+    fn open(archive_path: &str) -> Result<Self, MekuriError> {
+        let archive = open_archive(archive_path, |archive| archive.open_for_listing())?;
+
+        let solid = archive.is_solid();
+        let mut names = Vec::new();
+        let mut positions = HashMap::new();
+        let mut image_count = 0;
+        for (position, entry) in archive.enumerate() {
+            let entry = entry.map_err(|e| rar_error("Failed to read RAR header", e))?;
+            let name = entry.filename.to_string_lossy().to_string();
+            if is_bulk_target(entry.is_file(), &name) {
+                image_count += 1;
+            }
+            positions.entry(name.clone()).or_insert(position);
+            names.push(name);
+        }
+
+        Ok(Self {
+            path: archive_path.to_string(),
+            names,
+            positions,
+            cursor: None,
+            solid,
+            image_count,
+            bulk: None,
+        })
+    }
+
+    fn position_of(&self, entry_name: &str) -> Result<usize, MekuriError> {
+        self.positions
+            .get(entry_name)
+            .copied()
+            .ok_or_else(|| MekuriError::NotFound(format!("Entry not found: {entry_name}")))
+    }
+
+    /// Position of the entry `id` names, if the entry there still has its name.
+    fn check_id(&self, id: &EntryId) -> Result<usize, MekuriError> {
+        match self.names.get(id.index) {
+            Some(name) if *name == id.name => Ok(id.index),
+            _ => Err(id.not_found()),
+        }
+    }
+
+    /// Read the entry at header position `target`.
+    fn read(&mut self, target: usize) -> Result<Vec<u8>, MekuriError> {
+        let (mut archive, mut position) = match self.cursor.take() {
+            Some(cursor) if cursor.next <= target => (cursor.archive, cursor.next),
+            _ => (
+                open_archive(&self.path, |archive| archive.open_for_processing())?,
+                0,
+            ),
+        };
+
+        loop {
+            let header = archive
+                .read_header()
+                .map_err(|e| rar_error("Failed to read RAR header", e))?
+                .ok_or_else(|| {
+                    MekuriError::NotFound(format!("Entry not found: {}", self.names[target]))
+                })?;
+
+            if position == target {
+                let encrypted = header.entry().is_encrypted();
+                let (data, rest) = header
+                    .read()
+                    .map_err(|e| read_error(&self.path, encrypted, e))?;
+                self.cursor = Some(Cursor {
+                    archive: rest,
+                    next: target + 1,
+                });
+                return Ok(data);
+            }
+
+            // Skip this entry
+            archive = header
+                .skip()
+                .map_err(|e| rar_error("Failed to skip entry", e))?;
+            position += 1;
+        }
+    }
+}
+
+/// Whether an entry is extracted by the bulk pass.
+fn is_bulk_target(is_file: bool, name: &str) -> bool {
+    is_file && is_image_file(name) && !name.contains("__MACOSX")
+}
+
+/// Images of a solid RAR archive extracted to a temp directory by one sequential pass.
+///
+/// Page reads wait here for their entry instead of decompressing the archive again.
+pub(super) struct BulkExtraction {
+    dir: tempfile::TempDir,
+    state: Mutex<BulkState>,
+    progress: Condvar,
+}
+
+#[derive(Default)]
+struct BulkState {
+    extracted: HashMap<String, PathBuf>,
+    finished: bool,
+}
+
+impl BulkExtraction {
+    /// Extract every image in stored order, reporting each one as it lands on disk.
+    fn run(
+        &self,
+        archive_path: &str,
+        total: usize,
+        on_progress: &dyn Fn(ExtractProgress),
+    ) -> Result<(), MekuriError> {
+        let archive = open_archive(archive_path, |archive| archive.open_for_processing())?;
+
+        let mut cursor = Some(archive);
+        while let Some(open) = cursor {
+            let Some(header) = open
+                .read_header()
+                .map_err(|e| rar_error("Failed to read RAR header", e))?
+            else {
+                break;
+            };
+
+            let name = header.entry().filename.to_string_lossy().to_string();
+            if !is_bulk_target(header.entry().is_file(), &name) {
+                // Skipping still decompresses a solid stream, but only this once
+                cursor = Some(
+                    header
+                        .skip()
+                        .map_err(|e| rar_error("Failed to skip entry", e))?,
+                );
+                continue;
+            }
+
+            let encrypted = header.entry().is_encrypted();
+            let (data, next) = header
+                .read()
+                .map_err(|e| read_error(archive_path, encrypted, e))?;
+            cursor = Some(next);
+
+            let Ok(mut state) = self.state.lock() else {
+                break;
+            };
+            // Entry names may contain folders, so files are numbered instead
+            let path = self.dir.path().join(state.extracted.len().to_string());
+            std::fs::write(&path, &data)
+                .map_err(|e| MekuriError::io("Failed to write temp file", e))?;
+            state.extracted.entry(name.clone()).or_insert(path);
+            let extracted = state.extracted.len();
+            drop(state);
+            self.progress.notify_all();
+
+            on_progress(ExtractProgress {
+                archive_path: archive_path.to_string(),
+                entry_name: Some(name),
+                extracted,
+                total,
+                error: None,
+            });
+        }
+        Ok(())
+    }
+
+    /// Mark the pass as done so waiting reads fall back to the archive.
+    fn finish(&self) {
+        // A pass that panicked while holding the lock must still let the reads go
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        state.finished = true;
+        drop(state);
+        self.progress.notify_all();
+    }
+
+    /// Where an entry was extracted to, if it has been already.
+    fn extracted_path(&self, entry_name: &str) -> Option<PathBuf> {
+        self.state.lock().ok()?.extracted.get(entry_name).cloned()
+    }
+
+    /// Wait until an entry has been extracted and read it back.
+    /// Returns `None` when the pass ended without extracting it.
+    fn wait_for(&self, entry_name: &str) -> Option<Result<Vec<u8>, MekuriError>> {
+        let mut state = self.state.lock().ok()?;
+        loop {
+            if let Some(path) = state.extracted.get(entry_name) {
+                return Some(
+                    std::fs::read(path).map_err(|e| MekuriError::io("Failed to read entry", e)),
+                );
+            }
+            if state.finished {
+                return None;
+            }
+            state = self.progress.wait(state).ok()?;
+        }
+    }
+}
+
+/// Finishes a bulk pass when dropped, so that reads waiting on it are not left hanging
+/// when its thread panics.
+struct FinishOnDrop(Arc<BulkExtraction>);
+
+impl Drop for FinishOnDrop {
+    fn drop(&mut self) {
+        self.0.finish();
+    }
+}
+
+/// Start extracting every image of a solid RAR archive on a background thread.
+///
+/// Returns `false` without doing anything for non-solid archives, whose pages are cheap to
+/// reach individually. Calling it again while the pass is running or done is a no-op.
+pub fn start_bulk_extraction(
+    cache: &ArchiveCache,
+    archive_path: &str,
+    on_progress: impl Fn(ExtractProgress) + Send + 'static,
+) -> Result<bool, MekuriError> {
+    let index = cache.get_or_open(archive_path, EntryIndex::open)?;
+    let mut index = index
+        .lock()
+        .map_err(|e| MekuriError::lock("Failed to read RAR archive", e))?;
+    if !index.solid {
+        return Ok(false);
+    }
+    if index.bulk.is_some() {
+        return Ok(true);
+    }
 
+    let bulk = Arc::new(BulkExtraction {
+        dir: super::temp_dir::create()?,
+        state: Mutex::new(BulkState::default()),
+        progress: Condvar::new(),
+    });
+    index.bulk = Some(bulk.clone());
+
+    let archive_path = archive_path.to_string();
+    let total = index.image_count;
+    std::thread::spawn(move || {
+        let finish = FinishOnDrop(bulk.clone());
+        let result = bulk.run(&archive_path, total, &on_progress);
+        drop(finish);
+
+        let extracted = bulk.state.lock().map(|s| s.extracted.len()).unwrap_or(0);
+        on_progress(ExtractProgress {
+            archive_path,
+            entry_name: None,
+            extracted,
+            total,
+            error: result.err(),
+        });
+    });
+    Ok(true)
+}
+
+/// Read the raw bytes of a single entry from a RAR archive.
+///
+/// While a bulk extraction is running, this waits for the entry to be extracted rather
+/// than decompressing the solid stream a second time.
+pub fn read_entry(
+    cache: &ArchiveCache,
+    archive_path: &str,
+    entry_name: &str,
+) -> Result<Vec<u8>, MekuriError> {
+    read_located(cache, archive_path, entry_name, |index| {
+        index.position_of(entry_name)
+    })
+}
+
+/// Read the raw bytes of the entry `id` names from a RAR archive.
+pub fn read_entry_by_id(
+    cache: &ArchiveCache,
+    archive_path: &str,
+    id: &EntryId,
+) -> Result<Vec<u8>, MekuriError> {
+    read_located(cache, archive_path, &id.name, |index| index.check_id(id))
+}
+
+/// Read the entry named `entry_name` at the position `locate` finds in the entry index.
+fn read_located(
+    cache: &ArchiveCache,
+    archive_path: &str,
+    entry_name: &str,
+    locate: impl FnOnce(&EntryIndex) -> Result<usize, MekuriError>,
+) -> Result<Vec<u8>, MekuriError> {
+    let index = cache.get_or_open(archive_path, EntryIndex::open)?;
+    let (position, bulk) = {
+        let index = index
+            .lock()
+            .map_err(|e| MekuriError::lock("Failed to read RAR archive", e))?;
+        let position = locate(&index)?;
+        // The bulk pass keeps only the first entry of each name
+        let first = index.positions.get(entry_name) == Some(&position);
+        (position, index.bulk.clone().filter(|_| first))
+    };
+    // Wait without holding the index, so pages already extracted stay readable
+    if let Some(result) = bulk.and_then(|bulk| bulk.wait_for(entry_name)) {
+        return result;
+    }
+
+    let mut index = index
+        .lock()
+        .map_err(|e| MekuriError::lock("Failed to read RAR archive", e))?;
+    index.read(position)
+}
+
+/// Read the archive's ComicInfo.xml, if it has one.
+pub fn read_comic_info(
+    cache: &ArchiveCache,
+    archive_path: &str,
+) -> Result<Option<Vec<u8>>, MekuriError> {
+    let index = cache.get_or_open(archive_path, EntryIndex::open)?;
+    let mut index = index
+        .lock()
+        .map_err(|e| MekuriError::lock("Failed to read RAR archive", e))?;
+    let Some(name) =
+        find_comic_info(index.positions.keys().map(String::as_str)).map(str::to_string)
+    else {
+        return Ok(None);
+    };
+    let position = index.position_of(&name)?;
+    index.read(position).map(Some)
+}
+
+/// Check the password entered for a RAR archive by opening it and decrypting its first
+/// encrypted file. Archives with encrypted headers are already checked when opened.
+pub fn check_password(archive_path: &str) -> Result<(), MekuriError> {
+    let mut cursor = Some(open_archive(archive_path, |archive| {
+        archive.open_for_processing()
+    })?);
     while let Some(open) = cursor {
-        let header = open
+        let Some(header) = open
             .read_header()
-            .map_err(|e| format!("Failed to read RAR header: {e}"))?;
+            .map_err(|e| rar_error("Failed to read RAR header", e))?
+        else {
+            break;
+        };
+        if header.entry().is_file() && header.entry().is_encrypted() {
+            header
+                .test()
+                .map_err(|e| read_error(archive_path, true, e))?;
+            break;
+        }
+        cursor = Some(
+            header
+                .skip()
+                .map_err(|e| rar_error("Failed to skip entry", e))?,
+        );
+    }
+    Ok(())
+}
 
-        match header {
-            Some(header) => {
-                let name = header.entry().filename.to_string_lossy().to_string();
-                if name == entry_name {
-                    let (data, _) = header
-                        .read()
-                        .map_err(|e| format!("Failed to read entry: {e}"))?;
-                    let mime = mime_type_from_name(entry_name);
-                    let b64 = base64::engine::general_purpose::STANDARD.encode(&data);
-                    return Ok(format!("data:{mime};base64,{b64}"));
-                }
-                // Skip this entry
-                let next = header
+/// Sizes and dimensions of every image.
+///
+/// unrar can only read entries whole. In a solid archive that decompresses every page
+/// before the one read, so its pages are measured only once the bulk pass has extracted
+/// them or they were read for display, and get sizes alone until then. Pages of other
+/// archives are read in one pass. RAR headers as exposed by unrar carry no packed size, so
+/// `compressed_size` is always `None`.
+pub fn image_info(
+    cache: &ArchiveCache,
+    archive_path: &str,
+) -> Result<HashMap<String, EntryInfo>, MekuriError> {
+    let index = cache.get_or_open(archive_path, EntryIndex::open)?;
+    let (solid, bulk) = {
+        let index = index
+            .lock()
+            .map_err(|e| MekuriError::lock("Failed to read RAR archive", e))?;
+        (index.solid, index.bulk.clone())
+    };
+    let modified = super::cache::modified_time(archive_path)?;
+    let known_dimensions = |name: &str| match bulk.as_ref().and_then(|b| b.extracted_path(name)) {
+        Some(path) => std::fs::File::open(path).ok().and_then(read_dimensions),
+        None => cache
+            .cached_page(archive_path, modified, name)
+            .and_then(|data| dimensions(&data)),
+    };
+
+    let archive = open_archive(archive_path, |archive| archive.open_for_listing())?;
+    let mut infos = HashMap::new();
+    for entry in archive.filter_map(Result::ok) {
+        let name = entry.filename.to_string_lossy().to_string();
+        if is_bulk_target(entry.is_file(), &name) && !infos.contains_key(&name) {
+            let info = EntryInfo::new(None, entry.unpacked_size, known_dimensions(&name));
+            infos.insert(name, info);
+        }
+    }
+    if !solid {
+        measure_pages(archive_path, &mut infos)?;
+    }
+    Ok(infos)
+}
+
+/// Read the dimensions of the pages in `infos` that have none, in one pass over a
+/// non-solid archive.
+fn measure_pages(
+    archive_path: &str,
+    infos: &mut HashMap<String, EntryInfo>,
+) -> Result<(), MekuriError> {
+    let mut cursor = Some(open_archive(archive_path, |archive| {
+        archive.open_for_processing()
+    })?);
+    while let Some(open) = cursor {
+        let Some(header) = open
+            .read_header()
+            .map_err(|e| rar_error("Failed to read RAR header", e))?
+        else {
+            break;
+        };
+        let name = header.entry().filename.to_string_lossy().to_string();
+        let Some(info) = infos
+            .get_mut(&name)
+            .filter(|info| info.width.is_none() && header.entry().is_file())
+        else {
+            cursor = Some(
+                header
                     .skip()
-                    .map_err(|e| format!("Failed to skip entry: {e}"))?;
-                cursor = Some(next);
-            }
-            None => break,
+                    .map_err(|e| rar_error("Failed to skip entry", e))?,
+            );
+            continue;
+        };
+        // A page that cannot be read leaves the rest with sizes alone, as it would fail
+        // to show anyway
+        let Ok((data, next)) = header.read() else {
+            break;
+        };
+        if let Some((width, height)) = dimensions(&data) {
+            info.width = Some(width);
+            info.height = Some(height);
         }
+        cursor = Some(next);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bulk_extraction() -> BulkExtraction {
+        BulkExtraction {
+            dir: tempfile::tempdir().unwrap(),
+            state: Mutex::new(BulkState::default()),
+            progress: Condvar::new(),
+        }
+    }
+
+    #[test]
+    fn test_wait_for_blocks_until_entry_is_extracted() {
+        let bulk = Arc::new(bulk_extraction());
+        let path = bulk.dir.path().join("0");
+        std::fs::write(&path, b"page-1").unwrap();
+
+        let waiter = {
+            let bulk = bulk.clone();
+            std::thread::spawn(move || bulk.wait_for("page1.jpg"))
+        };
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        bulk.state
+            .lock()
+            .unwrap()
+            .extracted
+            .insert("page1.jpg".into(), path);
+        bulk.progress.notify_all();
+
+        let result = waiter.join().unwrap().unwrap();
+        assert_eq!(result.unwrap(), b"page-1");
+    }
+
+    #[test]
+    fn test_wait_for_gives_up_after_finish() {
+        let bulk = bulk_extraction();
+        bulk.finish();
+        assert!(bulk.wait_for("missing.jpg").is_none());
+    }
+
+    #[test]
+    fn test_wait_for_gives_up_when_pass_panics() {
+        let bulk = Arc::new(bulk_extraction());
+        let waiter = {
+            let bulk = bulk.clone();
+            std::thread::spawn(move || bulk.wait_for("page1.jpg"))
+        };
+        std::thread::sleep(std::time::Duration::from_millis(20));
+
+        // Panicking with the state locked poisons it as well
+        let pass = {
+            let bulk = bulk.clone();
+            std::thread::spawn(move || {
+                let _finish = FinishOnDrop(bulk.clone());
+                let _state = bulk.state.lock().unwrap();
+                panic!("bulk pass failed");
+            })
+        };
+        assert!(pass.join().is_err());
+        assert!(waiter.join().unwrap().is_none());
+        assert!(bulk.wait_for("page1.jpg").is_none());
+    }
+
+    #[test]
+    fn test_is_bulk_target() {
+        assert!(is_bulk_target(true, "book/01.jpg"));
+        assert!(!is_bulk_target(false, "book/01.jpg"));
+        assert!(!is_bulk_target(true, "book/notes.txt"));
+        assert!(!is_bulk_target(true, "__MACOSX/book/._01.jpg"));
     }
 
-    Err(format!("Entry not found: {entry_name}"))
+    /// An archive made by `tests/fixtures/make_rar_fixtures.py`, as no RAR archives can
+    /// be written here.
+    fn fixture(name: &str) -> String {
+        format!("{}/tests/fixtures/{name}", env!("CARGO_MANIFEST_DIR"))
+    }
+
+    /// A copy of the archive with headers encrypted with "secret", as passwords are
+    /// remembered by path.
+    fn encrypted_headers_rar() -> (tempfile::TempDir, String) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("locked.cbr");
+        std::fs::copy(fixture("encrypted-headers.rar"), &path).unwrap();
+        let path = path.to_str().unwrap().to_string();
+        (dir, path)
+    }
+
+    #[test]
+    fn test_analyze_contents_keeps_images_next_to_archives() {
+        let listing = analyze_contents(&fixture("mixed-content.rar")).unwrap();
+        assert_eq!(listing.images, ["cover.jpg"]);
+        assert_eq!(listing.archives, ["vol1.cbz"]);
+    }
+
+    fn dimensions_of(infos: &HashMap<String, EntryInfo>, name: &str) -> (Option<u32>, Option<u32>) {
+        (infos[name].width, infos[name].height)
+    }
+
+    #[test]
+    fn test_image_info_measures_pages() {
+        let infos = image_info(&ArchiveCache::default(), &fixture("pages.rar")).unwrap();
+        assert_eq!(dimensions_of(&infos, "001.jpg"), (Some(300), Some(200)));
+        assert_eq!(dimensions_of(&infos, "002.jpg"), (Some(200), Some(300)));
+        assert_eq!(infos["001.jpg"].compressed_size, None);
+        assert!(infos["001.jpg"].size.unwrap() > 0);
+    }
+
+    #[test]
+    fn test_image_info_of_solid_archive_measures_decoded_pages() {
+        let path = fixture("solid-pages.rar");
+        let cache = ArchiveCache::default();
+        let infos = image_info(&cache, &path).unwrap();
+        assert_eq!(dimensions_of(&infos, "001.jpg"), (None, None));
+
+        // A page read for display is measured from the page cache
+        crate::archive::read_entry(&cache, &path, "001.jpg").unwrap();
+        let infos = image_info(&cache, &path).unwrap();
+        assert_eq!(dimensions_of(&infos, "001.jpg"), (Some(300), Some(200)));
+        assert_eq!(dimensions_of(&infos, "002.jpg"), (None, None));
+
+        // The rest once the bulk pass has extracted them
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let progress = move |progress: ExtractProgress| {
+            let _ = sender.send(progress);
+        };
+        assert!(start_bulk_extraction(&cache, &path, progress).unwrap());
+        while receiver.recv().unwrap().entry_name.is_some() {}
+        let infos = image_info(&cache, &path).unwrap();
+        assert_eq!(dimensions_of(&infos, "002.jpg"), (Some(200), Some(300)));
+    }
+
+    #[test]
+    fn test_encrypted_headers_need_password() {
+        let (_dir, path) = encrypted_headers_rar();
+        let cache = ArchiveCache::default();
+
+        assert!(matches!(
+            crate::archive::list_images(&ArchiveCache::default(), &path),
+            Err(MekuriError::PasswordRequired(_))
+        ));
+        crate::archive::set_password(&cache, &path, "secret").unwrap();
+        assert_eq!(
+            crate::archive::list_images(&ArchiveCache::default(), &path).unwrap(),
+            ["001.jpg"]
+        );
+        assert_eq!(
+            crate::archive::read_entry(&cache, &path, "001.jpg").unwrap(),
+            b"\xff\xd8\xff\xe0fake-jpg"
+        );
+    }
+
+    #[test]
+    fn test_wrong_password_for_encrypted_headers_is_not_kept() {
+        let (_dir, path) = encrypted_headers_rar();
+
+        let result = crate::archive::set_password(&ArchiveCache::default(), &path, "wrong");
+        assert!(matches!(result, Err(MekuriError::PasswordRequired(_))));
+        assert_eq!(password::lookup(&path), None);
+        assert!(matches!(
+            crate::archive::list_images(&ArchiveCache::default(), &path),
+            Err(MekuriError::PasswordRequired(_))
+        ));
+    }
 }
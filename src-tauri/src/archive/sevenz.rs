@@ -0,0 +1,215 @@
+use super::sort::ListedEntry;
+use super::{is_image_file, EntryId, Listing};
+use crate::error::MekuriError;
+use sevenz_rust::{Archive, BlockDecoder};
+use std::fs::File;
+
+/// Sort a 7z error into a `MekuriError`, prefixing its message with `context`.
+fn sevenz_error(context: &str, err: sevenz_rust::Error) -> MekuriError {
+    let message = format!("{context}: {err}");
+    match err {
+        sevenz_rust::Error::Io(e, _) | sevenz_rust::Error::FileOpen(e, _) => {
+            MekuriError::io(context, e)
+        }
+        sevenz_rust::Error::PasswordRequired | sevenz_rust::Error::MaybeBadPassword(_) => {
+            MekuriError::PasswordRequired(message)
+        }
+        sevenz_rust::Error::UnsupportedVersion { .. }
+        | sevenz_rust::Error::UnsupportedCompressionMethod(_)
+        | sevenz_rust::Error::Unsupported(_)
+        | sevenz_rust::Error::ExternalUnsupported => MekuriError::UnsupportedFormat(message),
+        sevenz_rust::Error::MaxMemLimited { .. } | sevenz_rust::Error::Other(_) => {
+            MekuriError::Io(message)
+        }
+        _ => MekuriError::Corrupt(message),
+    }
+}
+
+/// Open a 7z archive and read its header (entry table) without decoding any data.
+fn open_archive(archive_path: &str) -> Result<(File, Archive), MekuriError> {
+    let mut file =
+        File::open(archive_path).map_err(|e| MekuriError::io("Failed to open archive", e))?;
+    let len = file
+        .metadata()
+        .map_err(|e| MekuriError::io("Failed to open archive", e))?
+        .len();
+    let archive = Archive::read(&mut file, len, &[])
+        .map_err(|e| sevenz_error("Failed to read 7z archive", e))?;
+    Ok((file, archive))
+}
+
+/// Iterate over the indices and names of file entries in a 7z archive, skipping
+/// directories and macOS metadata.
+fn file_entries(archive: &Archive) -> impl Iterator<Item = (usize, &str)> {
+    archive
+        .files
+        .iter()
+        .enumerate()
+        .filter(|(_, entry)| !entry.is_directory())
+        .map(|(index, entry)| (index, entry.name()))
+        .filter(|(_, name)| !name.contains("__MACOSX"))
+}
+
+/// Read the raw bytes of a single entry from a 7z archive.
+pub fn read_entry(archive_path: &str, entry_name: &str) -> Result<Vec<u8>, MekuriError> {
+    let (mut file, archive) = open_archive(archive_path)?;
+
+    let file_index = archive
+        .files
+        .iter()
+        .position(|entry| !entry.is_directory() && entry.name() == entry_name)
+        .ok_or_else(|| MekuriError::NotFound(format!("Entry not found: {entry_name}")))?;
+    read_file(&mut file, &archive, file_index)
+}
+
+/// Read the raw bytes of the entry `id` names from a 7z archive.
+pub fn read_entry_by_id(archive_path: &str, id: &EntryId) -> Result<Vec<u8>, MekuriError> {
+    let (mut file, archive) = open_archive(archive_path)?;
+
+    match archive.files.get(id.index) {
+        Some(entry) if !entry.is_directory() && entry.name() == id.name => {}
+        _ => return Err(id.not_found()),
+    }
+    read_file(&mut file, &archive, id.index)
+}
+
+/// Read the entry at `file_index` of the archive's file list.
+///
+/// 7z archives are usually solid, so only the folder (solid block) holding the entry is
+/// decoded, and the entries preceding it within that block are drained and discarded.
+fn read_file(
+    file: &mut File,
+    archive: &Archive,
+    file_index: usize,
+) -> Result<Vec<u8>, MekuriError> {
+    let target = &archive.files[file_index];
+    // Entries without a folder have no stream (zero-length files)
+    let Some(folder_index) = archive.stream_map.file_folder_index[file_index] else {
+        return Ok(Vec::new());
+    };
+
+    let mut found: Option<Vec<u8>> = None;
+    BlockDecoder::new(folder_index, archive, &[], file)
+        .for_each_entries(&mut |entry, data| {
+            // The decoder hands out the archive's own entries, so identity picks out this
+            // one even among entries of the same name
+            if std::ptr::eq(entry, target) {
+                let mut buf = Vec::with_capacity(entry.size() as usize);
+                data.read_to_end(&mut buf).map_err(sevenz_rust::Error::io)?;
+                found = Some(buf);
+                return Ok(false);
+            }
+            // Later entries in the block start where this one ends, so it must be consumed
+            std::io::copy(data, &mut std::io::sink()).map_err(sevenz_rust::Error::io)?;
+            Ok(true)
+        })
+        .map_err(|e| sevenz_error("Failed to read entry", e))?;
+
+    found.ok_or_else(|| MekuriError::NotFound(format!("Entry not found: {}", target.name())))
+}
+
+/// List the image entries of a 7z archive with their indices in its file list.
+pub fn list_entries(archive_path: &str) -> Result<Vec<ListedEntry>, MekuriError> {
+    let (_, archive) = open_archive(archive_path)?;
+
+    Ok(file_entries(&archive)
+        .filter(|(_, name)| is_image_file(name))
+        .map(|(index, name)| {
+            let entry = &archive.files[index];
+            let modified = entry
+                .has_last_modified_date
+                .then(|| entry.last_modified_date.to_raw());
+            ListedEntry::new(index, name, modified)
+        })
+        .collect())
+}
+
+/// List the images and nested archives in a 7z archive.
+pub fn analyze_contents(archive_path: &str) -> Result<Listing, MekuriError> {
+    let (_, archive) = open_archive(archive_path)?;
+    Ok(Listing::new(
+        file_entries(&archive).map(|(_, name)| name.to_string()),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::archive::{get_image_base64, list_images, ArchiveCache};
+    use base64::Engine;
+    use sevenz_rust::{SevenZArchiveEntry, SevenZWriter};
+
+    /// Create a temporary 7z file with the given entries for testing.
+    fn create_test_7z(entries: &[(&str, &[u8])]) -> tempfile::NamedTempFile {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let mut writer = SevenZWriter::new(file.reopen().unwrap()).unwrap();
+
+        for (name, data) in entries {
+            let mut entry = SevenZArchiveEntry::new();
+            entry.name = name.to_string();
+            entry.has_stream = true;
+            writer.push_archive_entry(entry, Some(*data)).unwrap();
+        }
+        writer.finish().unwrap();
+        file
+    }
+
+    #[test]
+    fn test_list_images_filters_and_sorts() {
+        let archive = create_test_7z(&[
+            ("page10.jpg", b"fake-jpg-10"),
+            ("page2.jpg", b"fake-jpg-2"),
+            ("readme.txt", b"not an image"),
+            ("page1.png", b"fake-png-1"),
+            ("__MACOSX/._page1.png", b"macos metadata"),
+        ]);
+
+        let result =
+            list_images(&ArchiveCache::default(), archive.path().to_str().unwrap()).unwrap();
+        assert_eq!(result, vec!["page1.png", "page2.jpg", "page10.jpg"]);
+    }
+
+    #[test]
+    fn test_get_image_base64_returns_data_url() {
+        let archive = create_test_7z(&[
+            ("cover.jpg", b"fake-cover"),
+            ("image.png", b"fake-png-data"),
+            ("last.jpg", b"fake-last"),
+        ]);
+
+        let result = get_image_base64(
+            &ArchiveCache::default(),
+            archive.path().to_str().unwrap(),
+            "image.png",
+        )
+        .unwrap();
+        let b64_part = result.strip_prefix("data:image/png;base64,").unwrap();
+        let decoded = base64::engine::general_purpose::STANDARD
+            .decode(b64_part)
+            .unwrap();
+        assert_eq!(decoded, b"fake-png-data");
+    }
+
+    #[test]
+    fn test_get_image_base64_entry_not_found() {
+        let archive = create_test_7z(&[("image.png", b"data")]);
+
+        let result = get_image_base64(
+            &ArchiveCache::default(),
+            archive.path().to_str().unwrap(),
+            "nonexistent.png",
+        );
+        assert!(matches!(result, Err(MekuriError::NotFound(_))));
+    }
+
+    #[test]
+    fn test_analyze_nested_archives() {
+        let archive = create_test_7z(&[("vol2.cbz", b"inner-2"), ("vol1.cbz", b"inner-1")]);
+        let path = archive.path().to_str().unwrap();
+
+        let listing = analyze_contents(path).unwrap();
+        assert!(listing.images.is_empty());
+        assert_eq!(listing.archives, vec!["vol1.cbz", "vol2.cbz"]);
+        assert_eq!(read_entry(path, "vol2.cbz").unwrap(), b"inner-2");
+    }
+}
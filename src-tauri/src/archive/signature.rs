@@ -0,0 +1,103 @@
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+/// Number of leading bytes read when sniffing; the tar magic sits at offset 257.
+const HEADER_LEN: usize = 512;
+const TAR_MAGIC_OFFSET: usize = 257;
+
+/// File formats recognized from their leading magic bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Signature {
+    Zip,
+    Rar,
+    SevenZip,
+    Tar,
+    Pdf,
+    /// Compressed streams; only meaningful as a tar wrapper
+    Gzip,
+    Bzip2,
+    Xz,
+    Zstd,
+}
+
+/// Identify a format from the first bytes of a file.
+pub fn sniff(header: &[u8]) -> Option<Signature> {
+    let starts = |magic: &[u8]| header.starts_with(magic);
+
+    if starts(b"PK\x03\x04") || starts(b"PK\x05\x06") || starts(b"PK\x07\x08") {
+        Some(Signature::Zip)
+    } else if starts(b"Rar!\x1a\x07") {
+        Some(Signature::Rar)
+    } else if starts(b"7z\xbc\xaf\x27\x1c") {
+        Some(Signature::SevenZip)
+    } else if starts(b"%PDF") {
+        Some(Signature::Pdf)
+    } else if starts(b"\x1f\x8b") {
+        Some(Signature::Gzip)
+    } else if starts(b"BZh") {
+        Some(Signature::Bzip2)
+    } else if starts(b"\xfd7zXZ\x00") {
+        Some(Signature::Xz)
+    } else if starts(b"\x28\xb5\x2f\xfd") {
+        Some(Signature::Zstd)
+    } else if header.get(TAR_MAGIC_OFFSET..TAR_MAGIC_OFFSET + 5) == Some(b"ustar") {
+        Some(Signature::Tar)
+    } else {
+        None
+    }
+}
+
+/// Read the header of a file and identify its format.
+/// Returns `None` when the file cannot be read or the header is not recognized.
+pub fn sniff_file(path: &Path) -> Option<Signature> {
+    let file = File::open(path).ok()?;
+    let mut header = Vec::with_capacity(HEADER_LEN);
+    file.take(HEADER_LEN as u64).read_to_end(&mut header).ok()?;
+    sniff(&header)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sniff_known_signatures() {
+        assert_eq!(sniff(b"PK\x03\x04rest"), Some(Signature::Zip));
+        assert_eq!(sniff(b"PK\x05\x06"), Some(Signature::Zip));
+        assert_eq!(sniff(b"Rar!\x1a\x07\x00"), Some(Signature::Rar));
+        assert_eq!(sniff(b"Rar!\x1a\x07\x01\x00"), Some(Signature::Rar));
+        assert_eq!(
+            sniff(b"7z\xbc\xaf\x27\x1c\x00\x04"),
+            Some(Signature::SevenZip)
+        );
+        assert_eq!(sniff(b"%PDF-1.7"), Some(Signature::Pdf));
+        assert_eq!(sniff(b"\x1f\x8b\x08"), Some(Signature::Gzip));
+        assert_eq!(sniff(b"BZh91AY"), Some(Signature::Bzip2));
+        assert_eq!(sniff(b"\xfd7zXZ\x00\x00"), Some(Signature::Xz));
+        assert_eq!(sniff(b"\x28\xb5\x2f\xfd\x00"), Some(Signature::Zstd));
+    }
+
+    #[test]
+    fn test_sniff_tar_magic_at_offset() {
+        let mut header = vec![0u8; HEADER_LEN];
+        header[TAR_MAGIC_OFFSET..TAR_MAGIC_OFFSET + 6].copy_from_slice(b"ustar\x00");
+        assert_eq!(sniff(&header), Some(Signature::Tar));
+    }
+
+    #[test]
+    fn test_sniff_inconclusive() {
+        assert_eq!(sniff(b""), None);
+        assert_eq!(sniff(b"PK"), None);
+        assert_eq!(sniff(b"plain text file"), None);
+        assert_eq!(sniff(&[0u8; HEADER_LEN]), None);
+    }
+
+    #[test]
+    fn test_sniff_file_missing() {
+        assert_eq!(
+            sniff_file(Path::new("/tmp/nonexistent_mekuri_sniff.zip")),
+            None
+        );
+    }
+}
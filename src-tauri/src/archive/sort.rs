@@ -0,0 +1,148 @@
+use super::EntryId;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+
+/// How the images of an archive are ordered.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SortMode {
+    /// Natural order of the full path in the archive (`2.jpg` before `10.jpg`)
+    #[default]
+    Natural,
+    /// Plain string order of the full path
+    Lexicographic,
+    /// Folders in natural order, then the files of each folder in natural order; files at
+    /// the root come first
+    DirectoryThenName,
+    /// The order the entries are stored in. Folders have none, so their images stay in
+    /// natural order
+    ArchiveOrder,
+    /// Oldest modification time first; entries without one come last, in natural order
+    ModifiedTime,
+}
+
+/// An image entry as a backend lists it, with what the sort modes need.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct ListedEntry {
+    pub id: EntryId,
+    /// Modification time as a key that orders the entries of one archive. Its unit depends
+    /// on the format (DOS time for ZIP and RAR, seconds or 100ns ticks elsewhere)
+    pub modified: Option<u64>,
+}
+
+impl ListedEntry {
+    pub fn new(index: usize, name: impl Into<String>, modified: Option<u64>) -> Self {
+        Self {
+            id: EntryId::new(index, name),
+            modified,
+        }
+    }
+}
+
+/// Sort entries by `mode`.
+pub(super) fn sort_entries(entries: &mut [ListedEntry], mode: SortMode) {
+    entries.sort_by(|a, b| compare(a, b, mode));
+}
+
+/// Order two entries by `mode`. Entries that compare equal under it fall back to natural
+/// order, then to stored order, so sorting never depends on the order they were listed in.
+pub(super) fn compare(a: &ListedEntry, b: &ListedEntry, mode: SortMode) -> Ordering {
+    let (x, y) = (&a.id.name, &b.id.name);
+    let primary = match mode {
+        SortMode::Natural => Ordering::Equal,
+        SortMode::Lexicographic => x.cmp(y),
+        SortMode::DirectoryThenName => {
+            let (x_dir, x_file) = split_dir(x);
+            let (y_dir, y_file) = split_dir(y);
+            // The root has no folder name and sorts before every folder
+            natord::compare(x_dir, y_dir).then_with(|| natord::compare(x_file, y_file))
+        }
+        SortMode::ArchiveOrder => a.id.index.cmp(&b.id.index),
+        SortMode::ModifiedTime => match (a.modified, b.modified) {
+            (Some(x_time), Some(y_time)) => x_time.cmp(&y_time),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        },
+    };
+    primary
+        .then_with(|| natord::compare(x, y))
+        .then(a.id.index.cmp(&b.id.index))
+}
+
+/// Split an entry path into its folder (empty at the root) and file name.
+fn split_dir(name: &str) -> (&str, &str) {
+    name.rsplit_once('/').unwrap_or(("", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted(entries: &[(&str, Option<u64>)], mode: SortMode) -> Vec<String> {
+        let mut listed: Vec<ListedEntry> = entries
+            .iter()
+            .enumerate()
+            .map(|(index, &(name, modified))| ListedEntry::new(index, name, modified))
+            .collect();
+        sort_entries(&mut listed, mode);
+        listed.into_iter().map(|entry| entry.id.name).collect()
+    }
+
+    #[test]
+    fn test_name_orders() {
+        let entries = [
+            ("p10.jpg", None),
+            ("p1b.jpg", None),
+            ("p2.jpg", None),
+            ("p1a.jpg", None),
+            ("P3.jpg", None),
+        ];
+        assert_eq!(
+            sorted(&entries, SortMode::Natural),
+            ["P3.jpg", "p1a.jpg", "p1b.jpg", "p2.jpg", "p10.jpg"]
+        );
+        assert_eq!(
+            sorted(&entries, SortMode::Lexicographic),
+            ["P3.jpg", "p10.jpg", "p1a.jpg", "p1b.jpg", "p2.jpg"]
+        );
+    }
+
+    #[test]
+    fn test_directory_then_name() {
+        let entries = [
+            ("ch10/001.jpg", None),
+            ("ch2/002.jpg", None),
+            ("ch2/001.jpg", None),
+            ("cover.jpg", None),
+            ("ch2 extra/001.jpg", None),
+        ];
+        assert_eq!(
+            sorted(&entries, SortMode::DirectoryThenName),
+            [
+                "cover.jpg",
+                "ch2/001.jpg",
+                "ch2/002.jpg",
+                "ch2 extra/001.jpg",
+                "ch10/001.jpg"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_archive_order_and_modified_time() {
+        let entries = [
+            ("b.jpg", Some(30)),
+            ("c.jpg", None),
+            ("a.jpg", Some(20)),
+            ("d.jpg", Some(20)),
+        ];
+        assert_eq!(
+            sorted(&entries, SortMode::ArchiveOrder),
+            ["b.jpg", "c.jpg", "a.jpg", "d.jpg"]
+        );
+        assert_eq!(
+            sorted(&entries, SortMode::ModifiedTime),
+            ["a.jpg", "d.jpg", "b.jpg", "c.jpg"]
+        );
+    }
+}
@@ -0,0 +1,368 @@
+use super::{virtual_path, volume};
+use crate::error::MekuriError;
+use std::fs::File;
+use std::io::{self, Cursor, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::sync::Arc;
+
+const EOCD_SIGNATURE: &[u8] = b"PK\x05\x06";
+const CENTRAL_HEADER_SIGNATURE: &[u8] = b"PK\x01\x02";
+const EOCD_LEN: usize = 22;
+const CENTRAL_HEADER_LEN: usize = 46;
+/// The end of central directory record is followed by a comment of at most this many bytes.
+const MAX_COMMENT_LEN: usize = u16::MAX as usize;
+
+/// A ZIP archive: a single file, the parts of a split archive read as one, or a nested
+/// archive kept in memory.
+pub(super) enum ZipSource {
+    Single(File),
+    Split(Joined),
+    Memory(Cursor<Arc<[u8]>>),
+}
+
+/// Open the file at `archive_path`, joining it with its `.z01`, `.z02`, ... parts when it
+/// is the last part of a split archive. Nested archives kept in memory are read from there.
+pub(super) fn open(archive_path: &str) -> Result<ZipSource, MekuriError> {
+    if let Some((data, _)) = virtual_path::in_memory(archive_path) {
+        return Ok(ZipSource::Memory(Cursor::new(data)));
+    }
+    let path = Path::new(archive_path);
+    let file = File::open(path).map_err(|e| MekuriError::io("Failed to open archive", e))?;
+    let parts = volume::split_zip_parts(path);
+    if parts.is_empty() {
+        return Ok(ZipSource::Single(file));
+    }
+
+    let mut disks = parts
+        .iter()
+        .map(|part| File::open(part).map_err(|e| MekuriError::io("Failed to open ZIP volume", e)))
+        .collect::<Result<Vec<_>, _>>()?;
+    disks.push(file);
+    Joined::new(disks).map(ZipSource::Split)
+}
+
+impl Read for ZipSource {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Self::Single(file) => file.read(buf),
+            Self::Split(joined) => joined.read(buf),
+            Self::Memory(cursor) => cursor.read(buf),
+        }
+    }
+}
+
+impl Seek for ZipSource {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match self {
+            Self::Single(file) => file.seek(pos),
+            Self::Split(joined) => joined.seek(pos),
+            Self::Memory(cursor) => cursor.seek(pos),
+        }
+    }
+}
+
+/// The parts (disks) of a split ZIP laid end to end.
+///
+/// Offsets in a split archive are relative to the disk they point into, which the zip
+/// crate does not support. The central directory is therefore rewritten in memory with
+/// offsets into the joined stream, as if the archive had never been split.
+pub(super) struct Joined {
+    segments: Vec<Segment>,
+    len: u64,
+    pos: u64,
+}
+
+struct Segment {
+    /// Offset of the segment in the joined stream
+    start: u64,
+    len: u64,
+    data: SegmentData,
+}
+
+enum SegmentData {
+    File(File),
+    Memory(Vec<u8>),
+}
+
+impl Joined {
+    fn new(mut disks: Vec<File>) -> Result<Self, MekuriError> {
+        let sizes = disks
+            .iter()
+            .map(|disk| disk.metadata().map(|m| m.len()))
+            .collect::<io::Result<Vec<_>>>()
+            .map_err(|e| MekuriError::io("Failed to open ZIP volume", e))?;
+        // Offset of each disk in the joined stream
+        let bases: Vec<u64> = sizes
+            .iter()
+            .scan(0, |offset, size| {
+                let base = *offset;
+                *offset += size;
+                Some(base)
+            })
+            .collect();
+
+        let last = disks.len() - 1;
+        let eocd = read_eocd(&mut disks[last], sizes[last])?;
+        let cd_disk = usize::from(le16(&eocd, 6));
+        let entries = le16(&eocd, 10);
+        let cd_size = le32(&eocd, 12);
+        let cd_offset = le32(&eocd, 16);
+        if entries == u16::MAX || cd_size == u32::MAX || cd_offset == u32::MAX {
+            return Err(MekuriError::UnsupportedFormat(
+                "Split ZIP64 archives are not supported".to_string(),
+            ));
+        }
+        if cd_disk > last || u64::from(cd_offset) + u64::from(cd_size) > sizes[cd_disk] {
+            return Err(MekuriError::UnsupportedFormat(
+                "Split ZIP with a central directory spanning volumes is not supported".to_string(),
+            ));
+        }
+
+        let mut directory = vec![0; cd_size as usize];
+        disks[cd_disk]
+            .seek(SeekFrom::Start(cd_offset.into()))
+            .and_then(|_| disks[cd_disk].read_exact(&mut directory))
+            .map_err(|e| MekuriError::io("Failed to read ZIP central directory", e))?;
+        rebase_directory(&mut directory, &bases)?;
+
+        let directory_start = to_u32(bases[cd_disk] + u64::from(cd_offset))?;
+        let mut tail = directory;
+        let eocd_start = tail.len();
+        tail.extend_from_slice(&eocd);
+        // Everything on disk 0, all entries on it, directory at its joined offset
+        tail[eocd_start + 4..eocd_start + 8].fill(0);
+        tail[eocd_start + 8..eocd_start + 10].copy_from_slice(&entries.to_le_bytes());
+        tail[eocd_start + 16..eocd_start + 20].copy_from_slice(&directory_start.to_le_bytes());
+
+        // Disks up to the one holding the directory, which is cut where the directory
+        // starts; later disks only hold the rest of the end record.
+        let mut segments = Vec::new();
+        for (disk, file) in disks.into_iter().enumerate().take(cd_disk + 1) {
+            let len = if disk == cd_disk {
+                cd_offset.into()
+            } else {
+                sizes[disk]
+            };
+            segments.push(Segment {
+                start: bases[disk],
+                len,
+                data: SegmentData::File(file),
+            });
+        }
+        segments.push(Segment {
+            start: u64::from(directory_start),
+            len: tail.len() as u64,
+            data: SegmentData::Memory(tail),
+        });
+
+        Ok(Self {
+            len: u64::from(directory_start) + segments.last().map_or(0, |s| s.len),
+            segments,
+            pos: 0,
+        })
+    }
+}
+
+/// Read the end of central directory record, with its comment, from the last disk.
+fn read_eocd(disk: &mut File, size: u64) -> Result<Vec<u8>, MekuriError> {
+    let tail_len = size.min((EOCD_LEN + MAX_COMMENT_LEN) as u64);
+    let mut tail = vec![0; tail_len as usize];
+    disk.seek(SeekFrom::Start(size - tail_len))
+        .and_then(|_| disk.read_exact(&mut tail))
+        .map_err(|e| MekuriError::io("Failed to read ZIP volume", e))?;
+
+    // The last signature whose comment runs exactly to the end of the file
+    (0..tail.len().saturating_sub(EOCD_LEN - 1))
+        .rev()
+        .find(|&pos| {
+            tail[pos..].starts_with(EOCD_SIGNATURE)
+                && pos + EOCD_LEN + usize::from(le16(&tail, pos + 20)) == tail.len()
+        })
+        .map(|pos| tail[pos..].to_vec())
+        .ok_or_else(|| {
+            MekuriError::Corrupt("End of central directory not found in split ZIP".to_string())
+        })
+}
+
+/// Point every central directory entry at disk 0, adding the joined offset of the disk
+/// its local header is on.
+fn rebase_directory(directory: &mut [u8], bases: &[u64]) -> Result<(), MekuriError> {
+    let corrupt = || MekuriError::Corrupt("Invalid central directory in split ZIP".to_string());
+    let mut pos = 0;
+    while pos < directory.len() {
+        let header = directory
+            .get(pos..pos + CENTRAL_HEADER_LEN)
+            .filter(|header| header.starts_with(CENTRAL_HEADER_SIGNATURE))
+            .ok_or_else(corrupt)?;
+        let disk = usize::from(le16(header, 34));
+        let offset = le32(header, 42);
+        if offset == u32::MAX {
+            return Err(MekuriError::UnsupportedFormat(
+                "Split ZIP64 archives are not supported".to_string(),
+            ));
+        }
+        let base = *bases.get(disk).ok_or_else(corrupt)?;
+        let joined = to_u32(base + u64::from(offset))?;
+        let next = pos
+            + CENTRAL_HEADER_LEN
+            + usize::from(le16(header, 28))
+            + usize::from(le16(header, 30))
+            + usize::from(le16(header, 32));
+
+        directory[pos + 34..pos + 36].fill(0);
+        directory[pos + 42..pos + 46].copy_from_slice(&joined.to_le_bytes());
+        pos = next;
+    }
+    Ok(())
+}
+
+/// Joined offsets must still fit the 32-bit fields of a non-ZIP64 archive.
+fn to_u32(offset: u64) -> Result<u32, MekuriError> {
+    u32::try_from(offset).map_err(|_| {
+        MekuriError::UnsupportedFormat("Split ZIP larger than 4 GiB is not supported".to_string())
+    })
+}
+
+fn le16(data: &[u8], pos: usize) -> u16 {
+    u16::from_le_bytes([data[pos], data[pos + 1]])
+}
+
+fn le32(data: &[u8], pos: usize) -> u32 {
+    u32::from_le_bytes([data[pos], data[pos + 1], data[pos + 2], data[pos + 3]])
+}
+
+impl Read for Joined {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let pos = self.pos;
+        let Some(segment) = self
+            .segments
+            .iter_mut()
+            .find(|segment| pos < segment.start + segment.len)
+        else {
+            return Ok(0);
+        };
+
+        let offset = pos - segment.start;
+        let wanted = (segment.len - offset).min(buf.len() as u64) as usize;
+        let read = match &mut segment.data {
+            SegmentData::File(file) => {
+                file.seek(SeekFrom::Start(offset))?;
+                file.read(&mut buf[..wanted])?
+            }
+            SegmentData::Memory(data) => {
+                let start = offset as usize;
+                buf[..wanted].copy_from_slice(&data[start..start + wanted]);
+                wanted
+            }
+        };
+        self.pos += read as u64;
+        Ok(read)
+    }
+}
+
+impl Seek for Joined {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(delta) => self.len.checked_add_signed(delta),
+            SeekFrom::Current(delta) => self.pos.checked_add_signed(delta),
+        }
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Invalid seek position"))?;
+        self.pos = target;
+        Ok(target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::archive::{list_images, read_entry, ArchiveCache};
+    use std::io::Write;
+
+    /// Split an ordinary single-disk ZIP into two disks at byte `at`, which must fall
+    /// before the central directory, the way split-archive tools lay them out.
+    fn split(zip: &[u8], at: usize) -> (Vec<u8>, Vec<u8>) {
+        let eocd = zip.len() - EOCD_LEN;
+        let cd_offset = le32(zip, eocd + 16) as usize;
+        assert!(at < cd_offset);
+
+        // The first disk starts with the split signature
+        let mut first = b"PK\x07\x08".to_vec();
+        first.extend_from_slice(&zip[..at]);
+
+        let mut directory = zip[cd_offset..eocd].to_vec();
+        let mut pos = 0;
+        while pos < directory.len() {
+            let offset = le32(&directory, pos + 42) as usize;
+            let (disk, offset) = if offset < at {
+                (0u16, offset + 4)
+            } else {
+                (1, offset - at)
+            };
+            directory[pos + 34..pos + 36].copy_from_slice(&disk.to_le_bytes());
+            directory[pos + 42..pos + 46].copy_from_slice(&(offset as u32).to_le_bytes());
+            pos += CENTRAL_HEADER_LEN
+                + usize::from(le16(&directory, pos + 28))
+                + usize::from(le16(&directory, pos + 30))
+                + usize::from(le16(&directory, pos + 32));
+        }
+
+        let mut second = zip[at..cd_offset].to_vec();
+        let directory_offset = second.len() as u32;
+        second.extend_from_slice(&directory);
+        let mut end = zip[eocd..].to_vec();
+        end[4..6].copy_from_slice(&1u16.to_le_bytes());
+        end[6..8].copy_from_slice(&1u16.to_le_bytes());
+        end[16..20].copy_from_slice(&directory_offset.to_le_bytes());
+        second.extend_from_slice(&end);
+        (first, second)
+    }
+
+    fn zip_bytes(entries: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut writer = zip::ZipWriter::new(io::Cursor::new(Vec::new()));
+        // Stored, so the split point lands inside the first entry's data
+        let options = zip::write::SimpleFileOptions::default()
+            .compression_method(zip::CompressionMethod::Stored);
+        for (name, data) in entries {
+            writer.start_file(*name, options).unwrap();
+            writer.write_all(data).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn test_reads_split_zip() {
+        let first_page = vec![1u8; 300];
+        let second_page = vec![2u8; 300];
+        let zip = zip_bytes(&[("01.jpg", &first_page), ("02.jpg", &second_page)]);
+        // Split in the middle of the first page's data
+        let (z01, last) = split(&zip, 200);
+
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("book.z01"), z01).unwrap();
+        std::fs::write(dir.path().join("book.zip"), last).unwrap();
+        let path = dir.path().join("book.zip");
+        let path = path.to_str().unwrap();
+
+        assert_eq!(
+            list_images(&ArchiveCache::default(), path).unwrap(),
+            vec!["01.jpg", "02.jpg"]
+        );
+        let cache = ArchiveCache::default();
+        assert_eq!(read_entry(&cache, path, "01.jpg").unwrap(), first_page);
+        assert_eq!(read_entry(&cache, path, "02.jpg").unwrap(), second_page);
+    }
+
+    #[test]
+    fn test_missing_part_is_not_joined() {
+        let zip = zip_bytes(&[("01.jpg", b"page")]);
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("book.zip");
+        std::fs::write(&path, zip).unwrap();
+
+        assert!(matches!(
+            open(path.to_str().unwrap()).unwrap(),
+            ZipSource::Single(_)
+        ));
+    }
+}
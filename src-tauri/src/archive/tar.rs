@@ -0,0 +1,315 @@
+use super::signature::{self, Signature};
+use super::sort::ListedEntry;
+use super::{is_image_file, EntryId, Listing};
+use crate::error::MekuriError;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+/// Compression layer wrapped around a tar stream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Compression {
+    None,
+    Gzip,
+    Bzip2,
+    Xz,
+    Zstd,
+}
+
+/// Determine the compression layer from a tar file name.
+fn compression_from_name(archive_path: &str) -> Compression {
+    let lower = archive_path.to_lowercase();
+    if lower.ends_with(".tar.gz") || lower.ends_with(".tgz") {
+        Compression::Gzip
+    } else if lower.ends_with(".tar.bz2") {
+        Compression::Bzip2
+    } else if lower.ends_with(".tar.xz") {
+        Compression::Xz
+    } else if lower.ends_with(".tar.zst") {
+        Compression::Zstd
+    } else {
+        Compression::None
+    }
+}
+
+/// Determine the compression layer from the file header, falling back to the name.
+fn detect_compression(archive_path: &str) -> Compression {
+    match signature::sniff_file(Path::new(archive_path)) {
+        Some(Signature::Gzip) => Compression::Gzip,
+        Some(Signature::Bzip2) => Compression::Bzip2,
+        Some(Signature::Xz) => Compression::Xz,
+        Some(Signature::Zstd) => Compression::Zstd,
+        Some(Signature::Tar) => Compression::None,
+        _ => compression_from_name(archive_path),
+    }
+}
+
+/// Open a (possibly compressed) tar archive for sequential reading.
+///
+/// Tar has no central directory, so every operation streams the archive from the start.
+fn open_archive(archive_path: &str) -> Result<tar::Archive<Box<dyn Read>>, MekuriError> {
+    let file =
+        File::open(archive_path).map_err(|e| MekuriError::io("Failed to open archive", e))?;
+    let reader = BufReader::new(file);
+
+    let stream: Box<dyn Read> = match detect_compression(archive_path) {
+        Compression::None => Box::new(reader),
+        Compression::Gzip => Box::new(flate2::read::MultiGzDecoder::new(reader)),
+        Compression::Bzip2 => Box::new(bzip2::read::MultiBzDecoder::new(reader)),
+        Compression::Xz => Box::new(liblzma::read::XzDecoder::new_multi_decoder(reader)),
+        Compression::Zstd => Box::new(
+            zstd::stream::read::Decoder::with_buffer(reader)
+                .map_err(|e| MekuriError::io("Failed to read zstd stream", e))?,
+        ),
+    };
+
+    Ok(tar::Archive::new(stream))
+}
+
+/// Collect the positions, names and modification times of all regular file entries in a
+/// tar archive, in stored order.
+fn file_entries(archive_path: &str) -> Result<Vec<(usize, String, Option<u64>)>, MekuriError> {
+    let mut archive = open_archive(archive_path)?;
+    let entries = archive
+        .entries()
+        .map_err(|e| MekuriError::io("Failed to read TAR archive", e))?;
+
+    let mut files = Vec::new();
+    for (position, entry) in entries.enumerate() {
+        let entry = entry.map_err(|e| MekuriError::io("Failed to read TAR entry", e))?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        // Use the raw bytes so names keep their `/` separators on every platform
+        let name = String::from_utf8_lossy(&entry.path_bytes()).to_string();
+        if !name.contains("__MACOSX") {
+            files.push((position, name, entry.header().mtime().ok()));
+        }
+    }
+    Ok(files)
+}
+
+/// Read the first regular file entry for which `wanted(position, name)` holds.
+fn read_where(
+    archive_path: &str,
+    mut wanted: impl FnMut(usize, &str) -> bool,
+) -> Result<Option<Vec<u8>>, MekuriError> {
+    let mut archive = open_archive(archive_path)?;
+    let entries = archive
+        .entries()
+        .map_err(|e| MekuriError::io("Failed to read TAR archive", e))?;
+
+    for (position, entry) in entries.enumerate() {
+        let mut entry = entry.map_err(|e| MekuriError::io("Failed to read TAR entry", e))?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        if wanted(position, &String::from_utf8_lossy(&entry.path_bytes())) {
+            let mut buf = Vec::with_capacity(entry.size() as usize);
+            entry
+                .read_to_end(&mut buf)
+                .map_err(|e| MekuriError::io("Failed to read entry", e))?;
+            return Ok(Some(buf));
+        }
+    }
+    Ok(None)
+}
+
+/// Read the raw bytes of a single entry from a tar archive.
+pub fn read_entry(archive_path: &str, entry_name: &str) -> Result<Vec<u8>, MekuriError> {
+    read_where(archive_path, |_, name| name == entry_name)?
+        .ok_or_else(|| MekuriError::NotFound(format!("Entry not found: {entry_name}")))
+}
+
+/// Read the raw bytes of the entry `id` names from a tar archive.
+pub fn read_entry_by_id(archive_path: &str, id: &EntryId) -> Result<Vec<u8>, MekuriError> {
+    read_where(archive_path, |position, name| {
+        position == id.index && name == id.name
+    })?
+    .ok_or_else(|| id.not_found())
+}
+
+/// List the image entries of a tar archive with their positions in stored order.
+pub fn list_entries(archive_path: &str) -> Result<Vec<ListedEntry>, MekuriError> {
+    Ok(file_entries(archive_path)?
+        .into_iter()
+        .filter(|(_, name, _)| is_image_file(name))
+        .map(|(position, name, modified)| ListedEntry::new(position, name, modified))
+        .collect())
+}
+
+/// List the images and nested archives in a tar archive.
+pub fn analyze_contents(archive_path: &str) -> Result<Listing, MekuriError> {
+    let files = file_entries(archive_path)?;
+    Ok(Listing::new(files.into_iter().map(|(_, name, _)| name)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::archive::{get_image_base64, list_images, ArchiveCache, SortMode};
+    use base64::Engine;
+    use std::io::Write;
+
+    /// Build an uncompressed tar stream with the given entries.
+    fn build_tar(entries: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(Vec::new());
+        for (name, data) in entries {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append_data(&mut header, name, *data).unwrap();
+        }
+        builder.into_inner().unwrap()
+    }
+
+    /// Write a tar archive with the given suffix, compressed to match it.
+    fn create_test_tar(suffix: &str, entries: &[(&str, &[u8])]) -> tempfile::NamedTempFile {
+        let raw = build_tar(entries);
+        let file = tempfile::Builder::new().suffix(suffix).tempfile().unwrap();
+
+        let data = match compression_from_name(suffix) {
+            Compression::None => raw,
+            Compression::Gzip => {
+                let mut enc =
+                    flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+                enc.write_all(&raw).unwrap();
+                enc.finish().unwrap()
+            }
+            Compression::Bzip2 => {
+                let mut enc =
+                    bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::default());
+                enc.write_all(&raw).unwrap();
+                enc.finish().unwrap()
+            }
+            Compression::Xz => {
+                let mut enc = liblzma::write::XzEncoder::new(Vec::new(), 6);
+                enc.write_all(&raw).unwrap();
+                enc.finish().unwrap()
+            }
+            Compression::Zstd => zstd::stream::encode_all(raw.as_slice(), 0).unwrap(),
+        };
+        std::fs::write(file.path(), data).unwrap();
+        file
+    }
+
+    #[test]
+    fn test_compression_from_name() {
+        assert_eq!(compression_from_name("a.tar"), Compression::None);
+        assert_eq!(compression_from_name("a.cbt"), Compression::None);
+        assert_eq!(compression_from_name("a.tar.gz"), Compression::Gzip);
+        assert_eq!(compression_from_name("a.TGZ"), Compression::Gzip);
+        assert_eq!(compression_from_name("a.tar.bz2"), Compression::Bzip2);
+        assert_eq!(compression_from_name("a.tar.xz"), Compression::Xz);
+        assert_eq!(compression_from_name("a.tar.zst"), Compression::Zstd);
+    }
+
+    #[test]
+    fn test_list_images_filters_and_sorts() {
+        let archive = create_test_tar(
+            ".cbt",
+            &[
+                ("book/page10.jpg", b"fake-jpg-10"),
+                ("book/page2.jpg", b"fake-jpg-2"),
+                ("book/readme.txt", b"not an image"),
+                ("book/page1.png", b"fake-png-1"),
+            ],
+        );
+
+        let result =
+            list_images(&ArchiveCache::default(), archive.path().to_str().unwrap()).unwrap();
+        assert_eq!(
+            result,
+            vec!["book/page1.png", "book/page2.jpg", "book/page10.jpg"]
+        );
+    }
+
+    #[test]
+    fn test_get_image_from_every_compression() {
+        for suffix in [".tar", ".tar.gz", ".tgz", ".tar.bz2", ".tar.xz", ".tar.zst"] {
+            let archive = create_test_tar(
+                suffix,
+                &[
+                    ("cover.jpg", b"fake-cover"),
+                    ("image.png", b"fake-png-data"),
+                ],
+            );
+
+            let result = get_image_base64(
+                &ArchiveCache::default(),
+                archive.path().to_str().unwrap(),
+                "image.png",
+            )
+            .unwrap();
+            let b64_part = result.strip_prefix("data:image/png;base64,").unwrap();
+            let decoded = base64::engine::general_purpose::STANDARD
+                .decode(b64_part)
+                .unwrap();
+            assert_eq!(decoded, b"fake-png-data", "{suffix}");
+        }
+    }
+
+    #[test]
+    fn test_compressed_cbt_is_detected_by_content() {
+        let gz = create_test_tar(".tar.gz", &[("page1.jpg", b"fake-jpg")]);
+        let cbt = tempfile::Builder::new().suffix(".cbt").tempfile().unwrap();
+        std::fs::copy(gz.path(), cbt.path()).unwrap();
+
+        let result = list_images(&ArchiveCache::default(), cbt.path().to_str().unwrap()).unwrap();
+        assert_eq!(result, vec!["page1.jpg"]);
+    }
+
+    #[test]
+    fn test_get_image_base64_entry_not_found() {
+        let archive = create_test_tar(".tar", &[("image.png", b"data")]);
+
+        let result = get_image_base64(
+            &ArchiveCache::default(),
+            archive.path().to_str().unwrap(),
+            "nonexistent.png",
+        );
+        assert!(matches!(result, Err(MekuriError::NotFound(_))));
+    }
+
+    #[test]
+    fn test_duplicate_names_are_read_by_id() {
+        let archive = create_test_tar(
+            ".tar",
+            &[
+                ("01.jpg", b"first"),
+                ("notes.txt", b""),
+                ("01.jpg", b"again"),
+            ],
+        );
+        let path = archive.path().to_str().unwrap();
+
+        let entries =
+            crate::archive::list_entries(&ArchiveCache::default(), path, SortMode::Natural)
+                .unwrap();
+        assert_eq!(
+            entries,
+            vec![EntryId::new(0, "01.jpg"), EntryId::new(2, "01.jpg")]
+        );
+        assert_eq!(read_entry_by_id(path, &entries[0]).unwrap(), b"first");
+        assert_eq!(read_entry_by_id(path, &entries[1]).unwrap(), b"again");
+        assert!(matches!(
+            read_entry_by_id(path, &EntryId::new(1, "01.jpg")),
+            Err(MekuriError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_analyze_nested_archives() {
+        let archive = create_test_tar(
+            ".tar.zst",
+            &[("vol2.cbz", b"inner-2"), ("vol1.cbz", b"inner-1")],
+        );
+        let path = archive.path().to_str().unwrap();
+
+        let listing = analyze_contents(path).unwrap();
+        assert!(listing.images.is_empty());
+        assert_eq!(listing.archives, vec!["vol1.cbz", "vol2.cbz"]);
+        assert_eq!(read_entry(path, "vol1.cbz").unwrap(), b"inner-1");
+    }
+}
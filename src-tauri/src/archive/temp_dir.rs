@@ -0,0 +1,136 @@
+use crate::error::MekuriError;
+use fs4::fs_std::FileExt;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Name prefix of the directory each run of the app extracts into.
+const PREFIX: &str = "mekuri-";
+
+/// File in the session directory that the running app keeps locked, so other instances
+/// can tell a live session from one left behind.
+const LOCK_FILE: &str = ".lock";
+
+/// How old an unlocked session directory must be to be swept. A new session creates its
+/// directory before it can lock the file in it; this leaves that moment alone.
+const STALE_AFTER: Duration = Duration::from_secs(60);
+
+/// Directory under which everything extracted by this run lives, created on first use.
+static SESSION: Mutex<Option<Session>> = Mutex::new(None);
+
+struct Session {
+    dir: tempfile::TempDir,
+    _lock: File,
+}
+
+impl Session {
+    fn create() -> Result<Self, MekuriError> {
+        let dir = tempfile::Builder::new()
+            .prefix(PREFIX)
+            .tempdir()
+            .map_err(|e| MekuriError::io("Failed to create temp directory", e))?;
+        let lock = File::create(dir.path().join(LOCK_FILE))
+            .map_err(|e| MekuriError::io("Failed to create temp directory", e))?;
+        lock.lock_exclusive()
+            .map_err(|e| MekuriError::io("Failed to lock temp directory", e))?;
+        Ok(Self { dir, _lock: lock })
+    }
+}
+
+/// Create a temporary directory inside this run's session directory. It is deleted when
+/// dropped, with the whole session directory on exit, or by the next run's sweep when
+/// the app did not get to clean up (a crash, `panic = "abort"`, a forced quit).
+pub(super) fn create() -> Result<tempfile::TempDir, MekuriError> {
+    let mut guard = SESSION
+        .lock()
+        .map_err(|e| MekuriError::lock("Failed to create temp directory", e))?;
+    let session = match &mut *guard {
+        Some(session) => session,
+        none => none.insert(Session::create()?),
+    };
+    tempfile::Builder::new()
+        .tempdir_in(session.dir.path())
+        .map_err(|e| MekuriError::io("Failed to create temp directory", e))
+}
+
+/// Delete this run's session directory with everything extracted into it.
+pub(super) fn remove_session() {
+    if let Ok(mut session) = SESSION.lock() {
+        session.take();
+    }
+}
+
+/// Delete the session directories that earlier runs left behind. Those of instances
+/// still running are kept: their lock file is held, or they were only just created.
+pub(super) fn sweep_stale() {
+    let own = SESSION
+        .lock()
+        .ok()
+        .and_then(|session| session.as_ref().map(|s| s.dir.path().to_path_buf()));
+    for dir in stale_sessions(&std::env::temp_dir(), own.as_deref(), STALE_AFTER) {
+        if let Err(e) = std::fs::remove_dir_all(&dir) {
+            eprintln!("mekuri: failed to remove {}: {e}", dir.display());
+        }
+    }
+}
+
+fn stale_sessions(temp_dir: &Path, own: Option<&Path>, min_age: Duration) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(temp_dir) else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .filter(|entry| entry.file_name().to_string_lossy().starts_with(PREFIX))
+        .filter(|entry| entry.file_type().is_ok_and(|t| t.is_dir()))
+        .filter(|entry| is_older_than(entry, min_age))
+        .map(|entry| entry.path())
+        .filter(|path| Some(path.as_path()) != own && !is_locked(path))
+        .collect()
+}
+
+fn is_older_than(entry: &std::fs::DirEntry, age: Duration) -> bool {
+    entry
+        .metadata()
+        .and_then(|metadata| metadata.modified())
+        .is_ok_and(|modified| modified.elapsed().is_ok_and(|elapsed| elapsed >= age))
+}
+
+/// Whether another process holds the lock file of a session directory.
+fn is_locked(dir: &Path) -> bool {
+    let Ok(lock) = File::open(dir.join(LOCK_FILE)) else {
+        return false;
+    };
+    matches!(lock.try_lock_exclusive(), Ok(false))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_unlocked_sessions_are_stale() {
+        let temp = tempfile::tempdir().unwrap();
+        let session = |name: &str| {
+            let dir = temp.path().join(name);
+            std::fs::create_dir(&dir).unwrap();
+            dir
+        };
+        let crashed = session("mekuri-crashed");
+        File::create(crashed.join(LOCK_FILE)).unwrap();
+        let unfinished = session("mekuri-unfinished");
+        let running = session("mekuri-running");
+        let lock = File::create(running.join(LOCK_FILE)).unwrap();
+        lock.lock_exclusive().unwrap();
+        let own = session("mekuri-own");
+        session("other");
+
+        let mut stale = stale_sessions(temp.path(), Some(&own), Duration::ZERO);
+        stale.sort();
+        assert_eq!(stale, [crashed, unfinished]);
+
+        // A session that may still be setting up its lock is left alone
+        let recent = stale_sessions(temp.path(), Some(&own), Duration::from_secs(3600));
+        assert!(recent.is_empty());
+    }
+}
@@ -0,0 +1,126 @@
+use super::{list_pages, ArchiveCache, SortMode};
+use crate::error::MekuriError;
+use serde::Serialize;
+
+/// A folder of pages in an archive, as an entry of its table of contents.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TocEntry {
+    /// Name of the folder itself
+    pub name: String,
+    /// Full path of the folder in the archive
+    pub path: String,
+    /// Index of the folder's first page among the pages shown
+    pub first_page: usize,
+    /// Pages in the folder and its subfolders. Sort modes other than by name can spread
+    /// them out, so they are not always the `page_count` pages from `first_page`
+    pub page_count: usize,
+    /// Subfolders, in the order of their first pages
+    pub children: Vec<TocEntry>,
+}
+
+impl TocEntry {
+    fn new(name: &str, path: &str, first_page: usize) -> Self {
+        Self {
+            name: name.to_string(),
+            path: path.to_string(),
+            first_page,
+            page_count: 0,
+            children: Vec::new(),
+        }
+    }
+}
+
+/// The folders of an archive with where their pages start, for jumping between chapters.
+///
+/// Page indices count the pages as the viewer shows them: sorted by `sort`, without the
+/// pages ComicInfo marks deleted unless it marks them all (`visiblePages` in
+/// `src/utils/comicInfo.ts`). Pages at the root belong to no entry.
+pub fn table_of_contents(
+    cache: &ArchiveCache,
+    archive_path: &str,
+    sort: SortMode,
+) -> Result<Vec<TocEntry>, MekuriError> {
+    let pages = list_pages(cache, archive_path, sort)?;
+    let all_deleted = pages.iter().all(|page| page.deleted);
+    let shown = pages.iter().filter(|page| all_deleted || !page.deleted);
+    Ok(build(shown.map(|page| page.name.as_str())))
+}
+
+fn build<'a>(names: impl IntoIterator<Item = &'a str>) -> Vec<TocEntry> {
+    let mut toc: Vec<TocEntry> = Vec::new();
+    for (index, name) in names.into_iter().enumerate() {
+        let Some((folder, _)) = name.rsplit_once('/') else {
+            continue;
+        };
+        let mut level = &mut toc;
+        let mut path_end = 0;
+        for part in folder.split('/') {
+            path_end += part.len();
+            let path = &folder[..path_end];
+            path_end += 1;
+
+            let position = match level.iter().position(|entry| entry.name == part) {
+                Some(position) => position,
+                None => {
+                    level.push(TocEntry::new(part, path, index));
+                    level.len() - 1
+                }
+            };
+            let entry = &mut level[position];
+            entry.page_count += 1;
+            level = &mut entry.children;
+        }
+    }
+    toc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn outline(entries: &[TocEntry], depth: usize, lines: &mut Vec<String>) {
+        for entry in entries {
+            lines.push(format!(
+                "{}{} @{} x{}",
+                "  ".repeat(depth),
+                entry.path,
+                entry.first_page,
+                entry.page_count
+            ));
+            outline(&entry.children, depth + 1, lines);
+        }
+    }
+
+    #[test]
+    fn test_build_folder_hierarchy() {
+        let toc = build([
+            "cover.jpg",
+            "vol1/ch01/001.jpg",
+            "vol1/ch01/002.jpg",
+            "vol1/ch02/001.jpg",
+            "vol1/extra.jpg",
+            "vol2/ch03/001.jpg",
+        ]);
+        let mut lines = Vec::new();
+        outline(&toc, 0, &mut lines);
+        assert_eq!(
+            lines,
+            [
+                "vol1 @1 x4",
+                "  vol1/ch01 @1 x2",
+                "  vol1/ch02 @3 x1",
+                "vol2 @5 x1",
+                "  vol2/ch03 @5 x1",
+            ]
+        );
+        assert_eq!(toc[0].children[1].name, "ch02");
+    }
+
+    #[test]
+    fn test_scattered_folder_starts_at_its_first_page() {
+        let toc = build(["b/1.jpg", "a/1.jpg", "b/2.jpg"]);
+        let mut lines = Vec::new();
+        outline(&toc, 0, &mut lines);
+        assert_eq!(lines, ["b @0 x2", "a @1 x1"]);
+    }
+}
@@ -0,0 +1,418 @@
+use super::cache::modified_time;
+use super::{is_archive_file, ArchiveCache};
+use crate::error::MekuriError;
+use std::collections::HashSet;
+use std::fmt;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+/// Separates an archive from the path of an entry inside it.
+pub const SEPARATOR: &str = "!/";
+
+/// How many bytes of nested archives are kept in memory at most, for the archives open in
+/// all windows together. Beyond it the least recently resolved ones are dropped and read
+/// from their parent again when next needed.
+const MEMORY_BUDGET: usize = 512 * 1024 * 1024;
+
+/// The nested archives resolved so far, least recently resolved first.
+static RESOLVED: Mutex<Vec<Resolved>> = Mutex::new(Vec::new());
+
+/// Held while extracting, so that a nested archive asked for by several threads at once
+/// is only extracted once.
+static EXTRACTING: Mutex<()> = Mutex::new(());
+
+/// The archive each owner (a viewer window) has open, by owner.
+static OPENED: Mutex<Vec<(String, VirtualPath)>> = Mutex::new(Vec::new());
+
+/// A nested archive taken out of its parent.
+pub(super) enum Extracted {
+    /// Already a file of its own
+    File(String),
+    /// Written to a file in a temporary directory, deleted when the directory is dropped
+    Temp {
+        _dir: tempfile::TempDir,
+        file: String,
+    },
+    /// Kept in memory and opened under its virtual path
+    Memory(Arc<[u8]>),
+}
+
+pub(super) struct Resolved {
+    /// Virtual path of the archive
+    path: String,
+    /// The file or folder on disk the virtual path starts from
+    root: String,
+    /// Modification time of the file on disk it was extracted from
+    root_modified: SystemTime,
+    extracted: Extracted,
+}
+
+impl Resolved {
+    /// What the backends open: the extracted file, or the virtual path itself for an
+    /// archive in memory.
+    pub(super) fn location(&self) -> &str {
+        match &self.extracted {
+            Extracted::File(file) | Extracted::Temp { file, .. } => file,
+            Extracted::Memory(_) => &self.path,
+        }
+    }
+
+    fn memory_size(&self) -> usize {
+        match &self.extracted {
+            Extracted::File(_) | Extracted::Temp { .. } => 0,
+            Extracted::Memory(data) => data.len(),
+        }
+    }
+}
+
+/// A path into archives nested in one another: a file or folder on disk, then the entry
+/// names leading down from it, joined with `!/` (`outer.zip!/inner.rar!/ch1/001.jpg`).
+///
+/// A `!` followed by `/` can also appear in real names, so the root only ends at the
+/// first `!/` after which the part before it exists on disk, and a nested name only ends
+/// at a `!/` that follows an archive name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VirtualPath {
+    root: String,
+    nested: Vec<String>,
+}
+
+impl VirtualPath {
+    pub fn parse(path: &str) -> Self {
+        let root_end = path
+            .match_indices(SEPARATOR)
+            .map(|(at, _)| at)
+            .find(|&at| Path::new(&path[..at]).exists());
+        let Some(root_end) = root_end else {
+            return Self {
+                root: path.to_string(),
+                nested: Vec::new(),
+            };
+        };
+
+        let mut nested = Vec::new();
+        let mut name = String::new();
+        for part in path[root_end + SEPARATOR.len()..].split(SEPARATOR) {
+            if !name.is_empty() {
+                name.push_str(SEPARATOR);
+            }
+            name.push_str(part);
+            if is_archive_file(&name) {
+                nested.push(std::mem::take(&mut name));
+            }
+        }
+        if !name.is_empty() {
+            nested.push(name);
+        }
+        Self {
+            root: path[..root_end].to_string(),
+            nested,
+        }
+    }
+
+    /// The path of the entry `name` inside the archive at this path.
+    pub fn join(&self, name: &str) -> Self {
+        let mut nested = self.nested.clone();
+        nested.push(name.to_string());
+        Self {
+            root: self.root.clone(),
+            nested,
+        }
+    }
+
+    /// The file or folder on disk this path starts from.
+    pub fn root(&self) -> &str {
+        &self.root
+    }
+
+    /// How many archives down from the path on disk this path goes.
+    pub fn depth(&self) -> usize {
+        self.nested.len()
+    }
+
+    /// This path and the paths of the archives it points into, outermost first.
+    fn with_parents(&self) -> impl Iterator<Item = String> + '_ {
+        (0..=self.nested.len()).map(|depth| {
+            Self {
+                root: self.root.clone(),
+                nested: self.nested[..depth].to_vec(),
+            }
+            .to_string()
+        })
+    }
+
+    /// The archive this path points into and the entry name inside it; `None` for a path
+    /// on disk.
+    pub fn parent(&self) -> Option<(Self, &str)> {
+        let (name, nested) = self.nested.split_last()?;
+        let parent = Self {
+            root: self.root.clone(),
+            nested: nested.to_vec(),
+        };
+        Some((parent, name))
+    }
+}
+
+impl fmt::Display for VirtualPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.root)?;
+        for name in &self.nested {
+            write!(f, "{SEPARATOR}{name}")?;
+        }
+        Ok(())
+    }
+}
+
+/// What to open for the archive at `path`. Paths on disk are returned as they are.
+/// Nested archives are extracted once, level by level, and reused until the outermost
+/// archive changes or nobody has it open any more: the extracted file, or for a ZIP
+/// archive kept in memory the virtual path, which the ZIP backend looks up with
+/// `in_memory`.
+pub fn resolve(cache: &ArchiveCache, path: &str) -> Result<String, MekuriError> {
+    let path = VirtualPath::parse(path);
+    if path.nested.is_empty() {
+        return Ok(path.root);
+    }
+    let root_modified = modified_time(&path.root)?;
+    if let Some(location) = lookup(&path.to_string(), root_modified) {
+        return Ok(location);
+    }
+    let _extracting = EXTRACTING
+        .lock()
+        .map_err(|e| MekuriError::lock("Failed to extract nested archive", e))?;
+    resolve_nested(cache, &path, root_modified)
+}
+
+fn resolve_nested(
+    cache: &ArchiveCache,
+    path: &VirtualPath,
+    root_modified: SystemTime,
+) -> Result<String, MekuriError> {
+    let Some((parent, name)) = path.parent() else {
+        return Ok(path.root.clone());
+    };
+    let key = path.to_string();
+    if let Some(location) = lookup(&key, root_modified) {
+        return Ok(location);
+    }
+
+    let parent_location = resolve_nested(cache, &parent, root_modified)?;
+    let resolved = Resolved {
+        path: key,
+        root: path.root.clone(),
+        root_modified,
+        extracted: super::extract_nested_archive(&parent_location, name)?,
+    };
+    let location = resolved.location().to_string();
+    let evicted = match RESOLVED.lock() {
+        Ok(mut all) => {
+            all.retain(|r| r.path != resolved.path);
+            all.push(resolved);
+            evict(&mut all, MEMORY_BUDGET)
+        }
+        Err(_) => Vec::new(),
+    };
+    // The handles opened on them would otherwise keep their data in memory
+    super::release(cache, evicted);
+    Ok(location)
+}
+
+/// Find a resolved archive and mark it as the most recently used.
+fn lookup(key: &str, root_modified: SystemTime) -> Option<String> {
+    let mut all = RESOLVED.lock().ok()?;
+    let index = all
+        .iter()
+        .position(|r| r.path == key && r.root_modified == root_modified)?;
+    let resolved = all.remove(index);
+    let location = resolved.location().to_string();
+    all.push(resolved);
+    Some(location)
+}
+
+/// Take out the least recently resolved archives in memory until the rest fit in
+/// `budget`; the most recent one is always kept. Returns the archives taken out.
+fn evict(all: &mut Vec<Resolved>, budget: usize) -> Vec<Resolved> {
+    let mut total: usize = all.iter().map(Resolved::memory_size).sum();
+    let mut evicted = Vec::new();
+    let mut index = 0;
+    while total > budget && index + 1 < all.len() {
+        let size = all[index].memory_size();
+        if size > 0 {
+            total -= size;
+            evicted.push(all.remove(index));
+        } else {
+            index += 1;
+        }
+    }
+    evicted
+}
+
+/// The data and modification time of a nested archive kept in memory, by virtual path.
+pub(super) fn in_memory(path: &str) -> Option<(Arc<[u8]>, SystemTime)> {
+    let all = RESOLVED.lock().ok()?;
+    all.iter()
+        .find(|r| r.path == path)
+        .and_then(|r| match &r.extracted {
+            Extracted::Memory(data) => Some((data.clone(), r.root_modified)),
+            Extracted::File(_) | Extracted::Temp { .. } => None,
+        })
+}
+
+/// The virtual path of the nested archive that resolved to `location`, or `location`
+/// itself for anything else.
+pub(super) fn virtual_path_of(location: &str) -> String {
+    RESOLVED
+        .lock()
+        .ok()
+        .and_then(|all| {
+            all.iter()
+                .find(|r| r.location() == location)
+                .map(|r| r.path.clone())
+        })
+        .unwrap_or_else(|| location.to_string())
+}
+
+/// Record that `owner` now shows the archive at `path`, so it and the archives it is
+/// nested in are kept for it. Returns the archives released by moving away from the one
+/// shown before.
+pub(super) fn open(owner: &str, path: &str) -> Vec<Resolved> {
+    let path = VirtualPath::parse(path);
+    let previous = match OPENED.lock() {
+        Ok(mut opened) => match opened.iter_mut().find(|(o, _)| o == owner) {
+            Some((_, opened_path)) => Some(std::mem::replace(opened_path, path)),
+            None => {
+                opened.push((owner.to_string(), path));
+                None
+            }
+        },
+        Err(_) => None,
+    };
+    previous.map(|path| release(&path)).unwrap_or_default()
+}
+
+/// Forget what `owner` had open. Returns the archives nobody else has open any more.
+pub(super) fn close(owner: &str) -> Vec<Resolved> {
+    let path = OPENED.lock().ok().and_then(|mut opened| {
+        let index = opened.iter().position(|(o, _)| o == owner)?;
+        Some(opened.remove(index).1)
+    });
+    path.map(|path| release(&path)).unwrap_or_default()
+}
+
+/// Take out the archives resolved under the file on disk `path` starts from, except those
+/// an owner still has open or reads another open archive through. Moving between the
+/// archives of an omnibus thus keeps only the one shown.
+fn release(path: &VirtualPath) -> Vec<Resolved> {
+    let Ok(opened) = OPENED.lock() else {
+        return Vec::new();
+    };
+    let in_use: HashSet<String> = opened
+        .iter()
+        .flat_map(|(_, path)| path.with_parents())
+        .collect();
+    drop(opened);
+    let Ok(mut all) = RESOLVED.lock() else {
+        return Vec::new();
+    };
+    let (released, kept) = std::mem::take(&mut *all)
+        .into_iter()
+        .partition(|r| r.root == path.root && !in_use.contains(&r.path));
+    *all = kept;
+    released
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_nested_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let outer = dir.path().join("Wow!").join("outer.zip");
+        std::fs::create_dir(outer.parent().unwrap()).unwrap();
+        std::fs::write(&outer, b"").unwrap();
+        let outer = outer.to_str().unwrap();
+
+        let path = VirtualPath::parse(&format!("{outer}!/inner!/x.rar!/ch1/001.jpg"));
+        assert_eq!(path.root, outer);
+        assert_eq!(path.nested, ["inner!/x.rar", "ch1/001.jpg"]);
+        assert_eq!(
+            path.to_string(),
+            format!("{outer}!/inner!/x.rar!/ch1/001.jpg")
+        );
+
+        let (parent, name) = path.parent().unwrap();
+        assert_eq!(name, "ch1/001.jpg");
+        assert_eq!(parent, VirtualPath::parse(outer).join("inner!/x.rar"));
+    }
+
+    #[test]
+    fn test_evict_drops_least_recent_archives_in_memory() {
+        // Paths of real files, so that the cache can hold handles on them
+        let dir = tempfile::tempdir().unwrap();
+        let path_of = |name: &str| {
+            let path = dir.path().join(name);
+            std::fs::write(&path, b"").unwrap();
+            path.to_str().unwrap().to_string()
+        };
+        let resolved = |path: &str, data: Option<Arc<[u8]>>| Resolved {
+            path: path.to_string(),
+            root: "/tmp".to_string(),
+            root_modified: SystemTime::UNIX_EPOCH,
+            extracted: match data {
+                None => Extracted::File(format!("/tmp/{path}")),
+                Some(data) => Extracted::Memory(data),
+            },
+        };
+        let (a, b, c, d, e) = (
+            path_of("a"),
+            path_of("b"),
+            path_of("c"),
+            path_of("d"),
+            path_of("e"),
+        );
+        let a_data: Arc<[u8]> = vec![0; 4].into();
+        let d_data: Arc<[u8]> = vec![0; 4].into();
+        let mut all = vec![
+            resolved(&a, Some(a_data.clone())),
+            resolved(&b, None),
+            resolved(&c, Some(vec![0; 4].into())),
+            resolved(&d, Some(d_data.clone())),
+            resolved(&e, Some(vec![0; 8].into())),
+        ];
+        // A ZIP handle on an archive in memory shares its data
+        let cache = ArchiveCache::default();
+        for (path, data) in [(&a, &a_data), (&d, &d_data)] {
+            cache.get_or_open(path, |_| Ok(data.clone())).unwrap();
+        }
+
+        let evicted = evict(&mut all, 12);
+        let kept: Vec<&str> = all.iter().map(|r| r.path.as_str()).collect();
+        assert_eq!(kept, [&b, &d, &e]);
+        let evicted_paths: Vec<&str> = evicted.iter().map(|r| r.path.as_str()).collect();
+        assert_eq!(evicted_paths, [&a, &c]);
+
+        // Releasing them forgets their handles too, so nothing holds their data any more
+        crate::archive::release(&cache, evicted);
+        assert_eq!(Arc::strong_count(&a_data), 1);
+        assert_eq!(Arc::strong_count(&d_data), 3);
+
+        // The most recent archive stays even when it alone is over budget
+        crate::archive::release(&cache, evict(&mut all, 4));
+        let kept: Vec<&str> = all.iter().map(|r| r.path.as_str()).collect();
+        assert_eq!(kept, [&b, &e]);
+        assert_eq!(Arc::strong_count(&d_data), 1);
+    }
+
+    #[test]
+    fn test_paths_on_disk_are_not_split() {
+        let path = VirtualPath::parse("/books/Wow!/vol1.zip");
+        assert_eq!(path.root, "/books/Wow!/vol1.zip");
+        assert!(path.parent().is_none());
+        assert_eq!(
+            resolve(&ArchiveCache::default(), "/books/Wow!/vol1.zip").unwrap(),
+            "/books/Wow!/vol1.zip"
+        );
+    }
+}
@@ -0,0 +1,137 @@
+use std::path::{Path, PathBuf};
+
+/// File name of the volume a multi-volume set is opened from, when `name` is one of its
+/// later volumes:
+///
+/// - `book.part2.rar` → `book.part1.rar`, keeping the number of digits (`part01`)
+/// - `book.r00` → `book.rar` for old-style RAR names, where the `.rar` part comes first
+/// - `book.z01` → `book.zip` for split ZIPs, whose `.zip` part comes last but holds the
+///   central directory
+///
+/// Returns `None` for first volumes and for anything that is not a volume.
+pub fn head_name(name: &str) -> Option<String> {
+    let (stem, ext) = name.rsplit_once('.')?;
+    if ext.eq_ignore_ascii_case("rar") || ext.eq_ignore_ascii_case("cbr") {
+        let (base, part) = stem.rsplit_once('.')?;
+        let label = part
+            .get(..4)
+            .filter(|label| label.eq_ignore_ascii_case("part"))?;
+        let digits = &part[4..];
+        if !is_number(digits) || digits.parse::<u32>().ok()? <= 1 {
+            return None;
+        }
+        return Some(format!(
+            "{base}.{label}{:0width$}.{ext}",
+            1,
+            width = digits.len()
+        ));
+    }
+
+    let (kind, digits) = ext.split_at_checked(1)?;
+    if digits.len() < 2 || !is_number(digits) {
+        return None;
+    }
+    let head_ext = match kind {
+        "r" => "rar",
+        "R" => "RAR",
+        "z" => "zip",
+        "Z" => "ZIP",
+        _ => return None,
+    };
+    Some(format!("{stem}.{head_ext}"))
+}
+
+fn is_number(digits: &str) -> bool {
+    !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// Path of the volume to open instead of `path`, when `path` is a later volume whose set
+/// has its head volume next to it.
+pub fn first_volume(path: &Path) -> Option<PathBuf> {
+    let name = path.file_name()?.to_str()?;
+    let head = path.with_file_name(head_name(name)?);
+    head.is_file().then_some(head)
+}
+
+/// The parts preceding a split ZIP's `.zip` part (`.z01`, `.z02`, ...), in order.
+/// Empty for an ordinary ZIP.
+pub fn split_zip_parts(zip_path: &Path) -> Vec<PathBuf> {
+    let Some(ext) = zip_path.extension().and_then(|e| e.to_str()) else {
+        return Vec::new();
+    };
+    if !ext.eq_ignore_ascii_case("zip") {
+        return Vec::new();
+    }
+    let prefix = if ext == "ZIP" { "Z" } else { "z" };
+    (1..)
+        .map(|number| zip_path.with_extension(format!("{prefix}{number:02}")))
+        .take_while(|part| part.is_file())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_head_name() {
+        assert_eq!(
+            head_name("book.part2.rar").as_deref(),
+            Some("book.part1.rar")
+        );
+        assert_eq!(
+            head_name("book.part03.cbr").as_deref(),
+            Some("book.part01.cbr")
+        );
+        assert_eq!(
+            head_name("Book.Part10.RAR").as_deref(),
+            Some("Book.Part01.RAR")
+        );
+        assert_eq!(head_name("book.r00").as_deref(), Some("book.rar"));
+        assert_eq!(head_name("BOOK.R12").as_deref(), Some("BOOK.RAR"));
+        assert_eq!(head_name("book.z01").as_deref(), Some("book.zip"));
+        assert_eq!(head_name("book.v1.z100").as_deref(), Some("book.v1.zip"));
+
+        for name in [
+            "book.part1.rar",
+            "book.part01.rar",
+            "book.rar",
+            "book.zip",
+            "book.partx.rar",
+            "book.r0",
+            "book.7z",
+            "book",
+        ] {
+            assert_eq!(head_name(name), None, "{name}");
+        }
+    }
+
+    #[test]
+    fn test_first_volume_needs_head_on_disk() {
+        let dir = tempfile::tempdir().unwrap();
+        let part2 = dir.path().join("book.part2.rar");
+        std::fs::write(&part2, b"").unwrap();
+        assert_eq!(first_volume(&part2), None);
+
+        let part1 = dir.path().join("book.part1.rar");
+        std::fs::write(&part1, b"").unwrap();
+        assert_eq!(first_volume(&part2), Some(part1.clone()));
+        assert_eq!(first_volume(&part1), None);
+    }
+
+    #[test]
+    fn test_split_zip_parts() {
+        let dir = tempfile::tempdir().unwrap();
+        let zip = dir.path().join("book.zip");
+        for name in ["book.zip", "book.z01", "book.z02", "book.z04"] {
+            std::fs::write(dir.path().join(name), b"").unwrap();
+        }
+        // The chain stops at the first missing part
+        assert_eq!(
+            split_zip_parts(&zip),
+            vec![dir.path().join("book.z01"), dir.path().join("book.z02")]
+        );
+        assert!(split_zip_parts(&dir.path().join("other.zip")).is_empty());
+        assert!(split_zip_parts(&dir.path().join("book.z01")).is_empty());
+    }
+}
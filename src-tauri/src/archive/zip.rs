@@ -1,149 +1,353 @@
-use super::{is_archive_file, is_image_file, mime_type_from_name, store_temp_dir, ArchiveContents};
-use base64::Engine;
-use std::io::{Read, Write};
-use std::path::Path;
-
-/// List image file names inside a ZIP archive, sorted by natural order.
-pub fn list_images(archive_path: &str) -> Result<Vec<String>, String> {
-    let file =
-        std::fs::File::open(archive_path).map_err(|e| format!("Failed to open archive: {e}"))?;
-    let mut archive =
-        zip::ZipArchive::new(file).map_err(|e| format!("Failed to read ZIP archive: {e}"))?;
-
-    let mut names: Vec<String> = (0..archive.len())
-        .filter_map(|i| {
-            let entry = archive.by_index_raw(i).ok()?;
-            let name = entry.name().to_string();
-            if is_image_file(&name) && !name.contains("__MACOSX") {
-                // Use only the file name, not the full path inside archive
-                Some(name)
-            } else {
-                None
-            }
-        })
-        .collect();
-
-    names.sort_by(|a, b| natord::compare(a, b));
-    Ok(names)
+use super::comic_info::find_comic_info;
+use super::image_header::read_dimensions;
+use super::name_encoding::decode_names;
+use super::password;
+use super::sort::ListedEntry;
+use super::split_zip::{self, ZipSource};
+use super::{is_image_file, ArchiveCache, EntryId, EntryInfo, Listing};
+use crate::error::MekuriError;
+use std::collections::HashMap;
+use std::io::Read;
+use zip::read::ZipFile;
+use zip::result::{ZipError, ZipResult};
+use zip::{CompressionMethod, ZipArchive};
+
+/// Sort a ZIP error into a `MekuriError`, prefixing its message with `context`.
+fn zip_error(context: &str, err: ZipError) -> MekuriError {
+    let message = format!("{context}: {err}");
+    match err {
+        ZipError::Io(e) => MekuriError::io(context, e),
+        ZipError::FileNotFound => MekuriError::NotFound(message),
+        ZipError::InvalidPassword | ZipError::UnsupportedArchive(ZipError::PASSWORD_REQUIRED) => {
+            MekuriError::PasswordRequired(message)
+        }
+        ZipError::UnsupportedArchive(_) => MekuriError::UnsupportedFormat(message),
+        _ => MekuriError::Corrupt(message),
+    }
 }
 
-/// Analyze ZIP archive contents to determine if it contains images or nested archives.
-pub fn analyze_contents(archive_path: &str) -> Result<ArchiveContents, String> {
-    let file =
-        std::fs::File::open(archive_path).map_err(|e| format!("Failed to open archive: {e}"))?;
-    let mut archive =
-        zip::ZipArchive::new(file).map_err(|e| format!("Failed to read ZIP archive: {e}"))?;
-
-    let mut images: Vec<String> = Vec::new();
-    let mut nested_archives: Vec<String> = Vec::new();
+/// An open ZIP archive with its entry names decoded.
+///
+/// zip decodes names without the UTF-8 flag as CP437, which garbles the Shift_JIS names of
+/// archives made on Japanese Windows, so entries are named from `name_encoding` instead
+/// and looked up by index, never through zip's own names.
+struct ZipHandle {
+    archive: ZipArchive<ZipSource>,
+    /// Decoded name of each entry, by index
+    names: Vec<String>,
+    indices: HashMap<String, usize>,
+}
 
-    for i in 0..archive.len() {
-        let entry = match archive.by_index_raw(i) {
-            Ok(e) => e,
-            Err(_) => continue,
-        };
-        if entry.is_dir() {
-            continue;
-        }
-        let name = entry.name().to_string();
-        if name.contains("__MACOSX") {
-            continue;
-        }
+impl ZipHandle {
+    fn index_of(&self, entry_name: &str) -> Result<usize, MekuriError> {
+        self.indices
+            .get(entry_name)
+            .copied()
+            .ok_or_else(|| MekuriError::NotFound(format!("Entry not found: {entry_name}")))
+    }
 
-        if is_image_file(&name) {
-            images.push(name);
-        } else if is_archive_file(&name) {
-            nested_archives.push(name);
+    /// Index of the entry `id` names, if the entry at its index still has its name.
+    fn check_id(&self, id: &EntryId) -> Result<usize, MekuriError> {
+        match self.names.get(id.index) {
+            Some(name) if *name == id.name => Ok(id.index),
+            _ => Err(id.not_found()),
         }
     }
+}
 
-    // If we have images, return them
-    if !images.is_empty() {
-        images.sort_by(|a, b| natord::compare(a, b));
-        return Ok(ArchiveContents::Images { names: images });
+/// Open a ZIP archive, together with its other parts when it is split, parse its
+/// central directory and decode its entry names.
+fn open_archive(archive_path: &str) -> Result<ZipHandle, MekuriError> {
+    let source = split_zip::open(archive_path)?;
+    let mut archive =
+        ZipArchive::new(source).map_err(|e| zip_error("Failed to read ZIP archive", e))?;
+
+    let raw_names: Vec<Vec<u8>> = (0..archive.len())
+        .map(|i| {
+            archive
+                .by_index_raw(i)
+                .map(|entry| entry.name_raw().to_vec())
+                .unwrap_or_default()
+        })
+        .collect();
+    let names = decode_names(&raw_names);
+    let mut indices = HashMap::with_capacity(names.len());
+    for (index, name) in names.iter().enumerate() {
+        indices.entry(name.clone()).or_insert(index);
     }
+    Ok(ZipHandle {
+        archive,
+        names,
+        indices,
+    })
+}
 
-    // If we have nested archives, return them
-    if !nested_archives.is_empty() {
-        nested_archives.sort_by(|a, b| natord::compare(a, b));
-        return Ok(ArchiveContents::NestedArchives {
-            names: nested_archives,
-        });
+/// Fail with a message naming the method when an entry is compressed with a method this
+/// build cannot decode; zip itself only reports "Compression method not supported".
+fn check_method(entry_name: &str, method: CompressionMethod) -> Result<(), MekuriError> {
+    // zip reports every method it cannot decode through this variant
+    #[allow(deprecated)]
+    if let CompressionMethod::Unsupported(id) = method {
+        return Err(MekuriError::UnsupportedFormat(format!(
+            "Unsupported compression method {} in entry: {entry_name}",
+            method_name(id)
+        )));
     }
+    Ok(())
+}
 
-    Ok(ArchiveContents::Empty)
+/// Human-readable name of a ZIP compression method id (APPNOTE 4.4.5).
+fn method_name(id: u16) -> String {
+    let name = match id {
+        1 => "Shrink",
+        2..=5 => "Reduce",
+        6 => "Implode",
+        8 => "Deflate",
+        9 => "Deflate64",
+        10 => "PKWARE DCL Implode",
+        12 => "BZIP2",
+        14 => "LZMA",
+        16 => "IBM z/OS CMPSC",
+        18 => "IBM TERSE",
+        19 => "IBM LZ77",
+        20 | 93 => "Zstandard",
+        94 => "MP3",
+        95 => "XZ",
+        96 => "JPEG",
+        97 => "WavPack",
+        98 => "PPMd",
+        99 => "AES",
+        _ => return format!("#{id}"),
+    };
+    format!("{name} (#{id})")
 }
 
-/// Extract a nested archive from a ZIP file and return the path to the extracted file.
-pub fn extract_nested_archive(parent_path: &str, nested_name: &str) -> Result<String, String> {
-    let file =
-        std::fs::File::open(parent_path).map_err(|e| format!("Failed to open archive: {e}"))?;
-    let mut archive =
-        zip::ZipArchive::new(file).map_err(|e| format!("Failed to read ZIP archive: {e}"))?;
+/// Open an entry for reading, decrypting it (ZipCrypto or AES) when a password is given.
+/// Entries that are not encrypted ignore the password.
+fn open_entry<'a>(
+    archive: &'a mut ZipArchive<ZipSource>,
+    index: usize,
+    password: Option<&str>,
+) -> ZipResult<ZipFile<'a, ZipSource>> {
+    match password {
+        Some(password) => archive.by_index_decrypt(index, password.as_bytes()),
+        None => archive.by_index(index),
+    }
+}
+
+/// Read the raw bytes of a single entry from an open ZIP archive.
+fn read_from(
+    handle: &mut ZipHandle,
+    entry_name: &str,
+    password: Option<&str>,
+) -> Result<Vec<u8>, MekuriError> {
+    let index = handle.index_of(entry_name)?;
+    read_index(handle, index, password)
+}
 
-    let mut entry = archive
-        .by_name(nested_name)
-        .map_err(|e| format!("Entry not found: {e}"))?;
+/// Read the raw bytes of the entry at `index` from an open ZIP archive.
+fn read_index(
+    handle: &mut ZipHandle,
+    index: usize,
+    password: Option<&str>,
+) -> Result<Vec<u8>, MekuriError> {
+    let ZipHandle { archive, names, .. } = handle;
+    let method = archive
+        .by_index_raw(index)
+        .map_err(|e| zip_error("Failed to read entry", e))?
+        .compression();
+    check_method(&names[index], method)?;
+    let mut entry =
+        open_entry(archive, index, password).map_err(|e| zip_error("Failed to read entry", e))?;
 
     let mut buf = Vec::with_capacity(entry.size() as usize);
     entry
         .read_to_end(&mut buf)
-        .map_err(|e| format!("Failed to read entry: {e}"))?;
-
-    // Create temp directory and write file
-    let temp_dir =
-        tempfile::tempdir().map_err(|e| format!("Failed to create temp directory: {e}"))?;
-
-    let file_name = Path::new(nested_name)
-        .file_name()
-        .and_then(|n| n.to_str())
-        .unwrap_or("archive");
-    let temp_path = temp_dir.path().join(file_name);
+        .map_err(|e| MekuriError::io("Failed to read entry", e))?;
+    Ok(buf)
+}
 
-    let mut file = std::fs::File::create(&temp_path)
-        .map_err(|e| format!("Failed to create temp file: {e}"))?;
-    file.write_all(&buf)
-        .map_err(|e| format!("Failed to write temp file: {e}"))?;
+/// List the image entries of a ZIP archive with their central directory indices.
+pub fn list_entries(archive_path: &str) -> Result<Vec<ListedEntry>, MekuriError> {
+    let ZipHandle {
+        mut archive, names, ..
+    } = open_archive(archive_path)?;
+
+    let mut encrypted = false;
+    let entries: Vec<ListedEntry> = names
+        .into_iter()
+        .enumerate()
+        .filter_map(|(i, name)| {
+            if is_image_file(&name) && !name.contains("__MACOSX") {
+                let entry = archive.by_index_raw(i).ok()?;
+                encrypted |= entry.encrypted();
+                // The DOS date and time fields, in this order, sort chronologically
+                let modified = entry
+                    .last_modified()
+                    .map(|time| (u64::from(time.datepart()) << 16) | u64::from(time.timepart()));
+                Some(ListedEntry::new(i, name, modified))
+            } else {
+                None
+            }
+        })
+        .collect();
+    password::require(archive_path, encrypted)?;
+    Ok(entries)
+}
 
-    let result = temp_path.to_string_lossy().to_string();
+/// List the images and nested archives in a ZIP archive.
+pub fn analyze_contents(archive_path: &str) -> Result<Listing, MekuriError> {
+    let ZipHandle {
+        mut archive, names, ..
+    } = open_archive(archive_path)?;
+
+    let files = names.into_iter().enumerate().filter(|(i, name)| {
+        let is_dir = archive
+            .by_index_raw(*i)
+            .map_or(true, |entry| entry.is_dir());
+        !is_dir && !name.contains("__MACOSX")
+    });
+    Ok(Listing::new(files.map(|(_, name)| name)))
+}
 
-    // Store temp dir to keep it alive
-    store_temp_dir(temp_dir);
+/// Read a nested archive stored in a ZIP archive.
+pub fn read_nested_archive(parent_path: &str, nested_name: &str) -> Result<Vec<u8>, MekuriError> {
+    let mut handle = open_archive(parent_path)?;
+    let password = password::lookup(parent_path);
+    read_from(&mut handle, nested_name, password.as_deref())
+}
 
-    Ok(result)
+/// Read the raw bytes of a single entry from a ZIP archive.
+///
+/// The parsed central directory stays in the cache, so later pages skip reopening the file.
+pub fn read_entry(
+    cache: &ArchiveCache,
+    archive_path: &str,
+    entry_name: &str,
+) -> Result<Vec<u8>, MekuriError> {
+    let handle = cache.get_or_open(archive_path, open_archive)?;
+    let mut handle = handle
+        .lock()
+        .map_err(|e| MekuriError::lock("Failed to read ZIP archive", e))?;
+    let password = password::lookup(archive_path);
+    read_from(&mut handle, entry_name, password.as_deref())
 }
 
-/// Extract a single image from a ZIP archive and return it as a Base64 data URL.
-pub fn get_image_base64(archive_path: &str, entry_name: &str) -> Result<String, String> {
-    let file =
-        std::fs::File::open(archive_path).map_err(|e| format!("Failed to open archive: {e}"))?;
-    let mut archive =
-        zip::ZipArchive::new(file).map_err(|e| format!("Failed to read ZIP archive: {e}"))?;
+/// Read the raw bytes of the entry `id` names from a ZIP archive.
+pub fn read_entry_by_id(
+    cache: &ArchiveCache,
+    archive_path: &str,
+    id: &EntryId,
+) -> Result<Vec<u8>, MekuriError> {
+    let handle = cache.get_or_open(archive_path, open_archive)?;
+    let mut handle = handle
+        .lock()
+        .map_err(|e| MekuriError::lock("Failed to read ZIP archive", e))?;
+    let index = handle.check_id(id)?;
+    let password = password::lookup(archive_path);
+    read_index(&mut handle, index, password.as_deref())
+}
 
-    let mut entry = archive
-        .by_name(entry_name)
-        .map_err(|e| format!("Entry not found: {e}"))?;
+/// Check a password against the first encrypted entry of a ZIP archive.
+///
+/// ZipCrypto only stores one check byte, so about one wrong password in 256 gets through
+/// here and fails later when the entry is read.
+pub fn check_password(
+    cache: &ArchiveCache,
+    archive_path: &str,
+    password: &str,
+) -> Result<(), MekuriError> {
+    let handle = cache.get_or_open(archive_path, open_archive)?;
+    let mut handle = handle
+        .lock()
+        .map_err(|e| MekuriError::lock("Failed to read ZIP archive", e))?;
+    let archive = &mut handle.archive;
+    let Some(index) =
+        (0..archive.len()).find(|&i| archive.by_index_raw(i).is_ok_and(|e| e.encrypted()))
+    else {
+        return Ok(());
+    };
+    let result = match open_entry(archive, index, Some(password)) {
+        Ok(_) => Ok(()),
+        Err(ZipError::InvalidPassword) => Err(MekuriError::PasswordRequired(format!(
+            "Incorrect password for archive: {archive_path}"
+        ))),
+        Err(e) => Err(zip_error("Failed to read entry", e)),
+    };
+    result
+}
 
-    let mut buf = Vec::with_capacity(entry.size() as usize);
-    entry
-        .read_to_end(&mut buf)
-        .map_err(|e| format!("Failed to read entry: {e}"))?;
+/// Read the archive's ComicInfo.xml, if it has one.
+pub fn read_comic_info(
+    cache: &ArchiveCache,
+    archive_path: &str,
+) -> Result<Option<Vec<u8>>, MekuriError> {
+    let handle = cache.get_or_open(archive_path, open_archive)?;
+    let mut handle = handle
+        .lock()
+        .map_err(|e| MekuriError::lock("Failed to read ZIP archive", e))?;
+    let Some(name) = find_comic_info(handle.names.iter().map(String::as_str)).map(str::to_string)
+    else {
+        return Ok(None);
+    };
+    let password = password::lookup(archive_path);
+    read_from(&mut handle, &name, password.as_deref()).map(Some)
+}
 
-    let mime = mime_type_from_name(entry_name);
-    let b64 = base64::engine::general_purpose::STANDARD.encode(&buf);
-    Ok(format!("data:{mime};base64,{b64}"))
+/// Sizes and dimensions of every image, decompressing only the start of each entry.
+pub fn image_info(
+    cache: &ArchiveCache,
+    archive_path: &str,
+) -> Result<HashMap<String, EntryInfo>, MekuriError> {
+    let handle = cache.get_or_open(archive_path, open_archive)?;
+    let mut handle = handle
+        .lock()
+        .map_err(|e| MekuriError::lock("Failed to read ZIP archive", e))?;
+    let ZipHandle { archive, names, .. } = &mut *handle;
+
+    let password = password::lookup(archive_path);
+    let mut infos = HashMap::new();
+    for (i, name) in names.iter().enumerate() {
+        // Checked before opening, which derives the key again for every AES entry
+        if !is_image_file(name) || name.contains("__MACOSX") {
+            continue;
+        }
+        let Ok(mut entry) = open_entry(archive, i, password.as_deref()) else {
+            continue;
+        };
+        if entry.is_dir() {
+            continue;
+        }
+        let (compressed_size, size) = (entry.compressed_size(), entry.size());
+        let dimensions = read_dimensions(&mut entry);
+        infos.insert(
+            name.clone(),
+            EntryInfo::new(Some(compressed_size), size, dimensions),
+        );
+    }
+    Ok(infos)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::archive::{get_image_base64, list_images, ArchiveCache, SortMode};
+    use base64::Engine;
     use std::io::Write;
+    use zip::unstable::write::FileOptionsExt;
+    use zip::write::SimpleFileOptions;
 
     /// Create a temporary ZIP file with the given entries for testing.
     fn create_test_zip(entries: &[(&str, &[u8])]) -> tempfile::NamedTempFile {
+        create_test_zip_with(entries, SimpleFileOptions::default())
+    }
+
+    fn create_test_zip_with(
+        entries: &[(&str, &[u8])],
+        options: SimpleFileOptions,
+    ) -> tempfile::NamedTempFile {
         let file = tempfile::NamedTempFile::new().unwrap();
         let mut writer = zip::ZipWriter::new(std::io::BufWriter::new(file.as_file()));
-        let options = zip::write::SimpleFileOptions::default();
 
         for (name, data) in entries {
             writer.start_file(*name, options).unwrap();
@@ -163,7 +367,8 @@ mod tests {
             ("__MACOSX/._page01.jpg", b"macos metadata"),
         ]);
 
-        let result = list_images(zip_file.path().to_str().unwrap()).unwrap();
+        let result =
+            list_images(&ArchiveCache::default(), zip_file.path().to_str().unwrap()).unwrap();
         assert_eq!(result, vec!["page01.jpg", "page02.png", "page03.jpg"]);
     }
 
@@ -171,16 +376,36 @@ mod tests {
     fn test_list_images_empty_zip() {
         let zip_file = create_test_zip(&[("readme.txt", b"no images here")]);
 
-        let result = list_images(zip_file.path().to_str().unwrap()).unwrap();
+        let result =
+            list_images(&ArchiveCache::default(), zip_file.path().to_str().unwrap()).unwrap();
         assert!(result.is_empty());
     }
 
+    #[test]
+    fn test_analyze_contents_keeps_images_next_to_archives() {
+        let zip_file = create_test_zip(&[
+            ("vol10.cbz", b"volume"),
+            ("cover.jpg", b"fake-jpg"),
+            ("vol2.cbz", b"volume"),
+            ("readme.txt", b"not an image"),
+        ]);
+
+        let listing = analyze_contents(zip_file.path().to_str().unwrap()).unwrap();
+        assert_eq!(listing.images, ["cover.jpg"]);
+        assert_eq!(listing.archives, ["vol2.cbz", "vol10.cbz"]);
+    }
+
     #[test]
     fn test_get_image_base64_returns_data_url() {
         let png_data = b"fake-png-data";
         let zip_file = create_test_zip(&[("image.png", png_data)]);
 
-        let result = get_image_base64(zip_file.path().to_str().unwrap(), "image.png").unwrap();
+        let result = get_image_base64(
+            &ArchiveCache::default(),
+            zip_file.path().to_str().unwrap(),
+            "image.png",
+        )
+        .unwrap();
         assert!(result.starts_with("data:image/png;base64,"));
         // Verify the base64 content decodes back to original
         let b64_part = result.strip_prefix("data:image/png;base64,").unwrap();
@@ -194,8 +419,204 @@ mod tests {
     fn test_get_image_base64_entry_not_found() {
         let zip_file = create_test_zip(&[("image.png", b"data")]);
 
-        let result = get_image_base64(zip_file.path().to_str().unwrap(), "nonexistent.png");
-        assert!(result.is_err());
-        assert!(result.unwrap_err().contains("Entry not found"));
+        let result = get_image_base64(
+            &ArchiveCache::default(),
+            zip_file.path().to_str().unwrap(),
+            "nonexistent.png",
+        );
+        assert!(matches!(result, Err(MekuriError::NotFound(_))));
+    }
+
+    #[test]
+    fn test_shift_jis_names_round_trip() {
+        // zip writes ASCII names without the UTF-8 flag; swap in Shift_JIS bytes of the
+        // same length, as a Japanese Windows archiver would have written them
+        let zip_file = create_test_zip(&[("AAAA.jpg", b"cover"), ("BBBBBB.jpg", b"page")]);
+        let mut bytes = std::fs::read(zip_file.path()).unwrap();
+        // 表紙 ends in 0x5C, the byte of a backslash
+        for (placeholder, raw) in [
+            (&b"AAAA"[..], &b"\x95\x5c\x8e\x86"[..]),
+            (b"BBBBBB", b"\x96\x7b\x95\xb6\x82\x50"),
+        ] {
+            while let Some(at) = bytes
+                .windows(placeholder.len())
+                .position(|w| w == placeholder)
+            {
+                bytes[at..at + raw.len()].copy_from_slice(raw);
+            }
+        }
+        std::fs::write(zip_file.path(), &bytes).unwrap();
+        let path = zip_file.path().to_str().unwrap();
+
+        assert_eq!(
+            list_images(&ArchiveCache::default(), path).unwrap(),
+            vec!["本文１.jpg", "表紙.jpg"]
+        );
+        let cache = ArchiveCache::default();
+        assert_eq!(read_entry(&cache, path, "表紙.jpg").unwrap(), b"cover");
+        assert_eq!(read_entry(&cache, path, "本文１.jpg").unwrap(), b"page");
+    }
+
+    #[test]
+    fn test_read_entry_by_id() {
+        let zip_file = create_test_zip(&[("02.jpg", b"second"), ("01.jpg", b"first")]);
+        let path = zip_file.path().to_str().unwrap();
+        let cache = ArchiveCache::default();
+
+        let entries =
+            crate::archive::list_entries(&ArchiveCache::default(), path, SortMode::Natural)
+                .unwrap();
+        assert_eq!(
+            entries,
+            vec![EntryId::new(1, "01.jpg"), EntryId::new(0, "02.jpg")]
+        );
+        assert_eq!(
+            read_entry_by_id(&cache, path, &entries[0]).unwrap(),
+            b"first"
+        );
+        assert_eq!(
+            read_entry_by_id(&cache, path, &entries[1]).unwrap(),
+            b"second"
+        );
+
+        // An id whose index now holds another entry is not read
+        let stale = EntryId::new(0, "01.jpg");
+        assert!(matches!(
+            read_entry_by_id(&cache, path, &stale),
+            Err(MekuriError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_reads_other_compression_methods() {
+        for method in [
+            CompressionMethod::Bzip2,
+            CompressionMethod::Zstd,
+            CompressionMethod::Xz,
+            CompressionMethod::Ppmd,
+        ] {
+            let options = SimpleFileOptions::default().compression_method(method);
+            let zip_file = create_test_zip_with(&[("01.jpg", b"compressed page")], options);
+            let path = zip_file.path().to_str().unwrap();
+
+            let data = read_entry(&ArchiveCache::default(), path, "01.jpg");
+            assert_eq!(data.unwrap(), b"compressed page", "{method}");
+        }
+    }
+
+    #[test]
+    fn test_unsupported_method_is_named() {
+        let zip_file = create_test_zip(&[("01.jpg", b"page")]);
+        // Rewrite the method of the stored entry to Implode (6) in both headers
+        let mut bytes = std::fs::read(zip_file.path()).unwrap();
+        bytes[8] = 6;
+        let central = bytes.windows(4).position(|w| w == b"PK\x01\x02").unwrap();
+        bytes[central + 10] = 6;
+        std::fs::write(zip_file.path(), &bytes).unwrap();
+
+        let result = read_entry(
+            &ArchiveCache::default(),
+            zip_file.path().to_str().unwrap(),
+            "01.jpg",
+        );
+        let Err(MekuriError::UnsupportedFormat(message)) = result else {
+            panic!("expected UnsupportedFormat, got {result:?}");
+        };
+        assert!(message.contains("Implode (#6)"), "{message}");
+    }
+
+    #[test]
+    fn test_method_name() {
+        assert_eq!(method_name(9), "Deflate64 (#9)");
+        assert_eq!(method_name(97), "WavPack (#97)");
+        assert_eq!(method_name(42), "#42");
+    }
+
+    #[test]
+    fn test_read_entry_reuses_cached_archive() {
+        let zip_file = create_test_zip(&[("01.jpg", b"first"), ("02.jpg", b"second")]);
+        let path = zip_file.path().to_str().unwrap();
+        let cache = ArchiveCache::default();
+
+        assert_eq!(read_entry(&cache, path, "01.jpg").unwrap(), b"first");
+
+        let cached = cache
+            .get_or_open::<ZipHandle, _>(path, |_| panic!("should not reopen"))
+            .unwrap();
+        assert_eq!(
+            read_from(&mut cached.lock().unwrap(), "02.jpg", None).unwrap(),
+            b"second"
+        );
+    }
+
+    #[test]
+    fn test_read_metadata_from_comic_info() {
+        let xml = b"<ComicInfo><Series>Mekuri</Series><Manga>No</Manga></ComicInfo>";
+        let zip_file = create_test_zip(&[("01.jpg", b"page"), ("ComicInfo.xml", xml)]);
+        let cache = ArchiveCache::default();
+
+        let metadata = crate::archive::read_metadata(&cache, zip_file.path().to_str().unwrap())
+            .unwrap()
+            .unwrap();
+        assert_eq!(metadata.series.as_deref(), Some("Mekuri"));
+        assert_eq!(metadata.manga, crate::archive::comic_info::Manga::No);
+    }
+
+    #[test]
+    fn test_read_metadata_without_comic_info() {
+        let zip_file = create_test_zip(&[("01.jpg", b"page")]);
+        let metadata = crate::archive::read_metadata(
+            &ArchiveCache::default(),
+            zip_file.path().to_str().unwrap(),
+        );
+        assert_eq!(metadata.unwrap(), None);
+    }
+
+    #[test]
+    fn test_encrypted_zip_needs_password() {
+        let zipcrypto = SimpleFileOptions::default().with_deprecated_encryption(b"secret");
+        let aes = SimpleFileOptions::default().with_aes_encryption(zip::AesMode::Aes256, "secret");
+        for options in [zipcrypto, aes] {
+            let zip_file = create_test_zip_with(&[("01.jpg", b"page")], options);
+            let path = zip_file.path().to_str().unwrap();
+            let cache = ArchiveCache::default();
+
+            assert!(matches!(
+                list_images(&ArchiveCache::default(), path),
+                Err(MekuriError::PasswordRequired(_))
+            ));
+            crate::archive::set_password(&cache, path, "secret").unwrap();
+            assert_eq!(
+                list_images(&ArchiveCache::default(), path).unwrap(),
+                vec!["01.jpg"]
+            );
+            assert_eq!(read_entry(&cache, path, "01.jpg").unwrap(), b"page");
+        }
+    }
+
+    #[test]
+    fn test_wrong_password_is_not_kept() {
+        // ZipCrypto only has a one-byte check, so use AES to make the check reliable
+        let options =
+            SimpleFileOptions::default().with_aes_encryption(zip::AesMode::Aes128, "secret");
+        let zip_file = create_test_zip_with(&[("01.jpg", b"page")], options);
+        let path = zip_file.path().to_str().unwrap();
+
+        let result = crate::archive::set_password(&ArchiveCache::default(), path, "wrong");
+        assert!(matches!(result, Err(MekuriError::PasswordRequired(_))));
+        assert!(matches!(
+            list_images(&ArchiveCache::default(), path),
+            Err(MekuriError::PasswordRequired(_))
+        ));
+    }
+
+    #[test]
+    fn test_password_is_ignored_for_plain_zip() {
+        let zip_file = create_test_zip(&[("01.jpg", b"page")]);
+        let path = zip_file.path().to_str().unwrap();
+        let cache = ArchiveCache::default();
+
+        crate::archive::set_password(&cache, path, "anything").unwrap();
+        assert_eq!(read_entry(&cache, path, "01.jpg").unwrap(), b"page");
     }
 }
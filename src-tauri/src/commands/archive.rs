@@ -1,21 +1,173 @@
-use crate::archive::{self as archive_impl, ArchiveContents};
+use crate::archive::{
+    self as archive_impl, ArchiveCache, ArchiveMetadata, ContentTree, EntryId, PageEntry, PageInfo,
+    Prefetcher, SortMode, TocEntry,
+};
+use crate::error::MekuriError;
+use crate::protocol::FileScope;
+use crate::thumbnail;
+use crate::thumbnail_cache::ThumbnailCache;
+use tauri::{AppHandle, Emitter, State, Window};
+
+/// Event carrying `ExtractProgress` while `start_bulk_extraction` runs.
+const EXTRACT_PROGRESS_EVENT: &str = "archive-extract-progress";
+
+/// `password` is only needed for encrypted archives; once accepted it is remembered for
+/// the session, so later calls can leave it out. The listing commands sort in natural
+/// order unless given another `sort`.
+#[tauri::command]
+pub fn list_archive_images(
+    cache: State<'_, ArchiveCache>,
+    archive_path: String,
+    password: Option<String>,
+    sort: Option<SortMode>,
+) -> Result<Vec<String>, MekuriError> {
+    if let Some(password) = password {
+        archive_impl::set_password(&cache, &archive_path, &password)?;
+    }
+    let entries = archive_impl::list_entries(&cache, &archive_path, sort.unwrap_or_default())?;
+    Ok(entries.into_iter().map(|entry| entry.name).collect())
+}
+
+/// Like `list_archive_images`, with ids that `get_archive_image_by_id` reads back even
+/// when names repeat or do not survive the round trip.
+#[tauri::command]
+pub fn list_archive_entries(
+    cache: State<'_, ArchiveCache>,
+    archive_path: String,
+    sort: Option<SortMode>,
+) -> Result<Vec<EntryId>, MekuriError> {
+    archive_impl::list_entries(&cache, &archive_path, sort.unwrap_or_default())
+}
+
+/// Later volumes of a multi-volume archive are opened from the head of their set.
+#[tauri::command]
+pub fn first_volume(archive_path: String) -> String {
+    archive_impl::first_volume(&archive_path)
+}
+
+#[tauri::command]
+pub fn list_archive_pages(
+    cache: State<'_, ArchiveCache>,
+    archive_path: String,
+    sort: Option<SortMode>,
+) -> Result<Vec<PageEntry>, MekuriError> {
+    archive_impl::list_pages(&cache, &archive_path, sort.unwrap_or_default())
+}
+
+/// Folders of an archive with the index and count of their pages, for chapter jumps.
+#[tauri::command]
+pub fn get_table_of_contents(
+    cache: State<'_, ArchiveCache>,
+    archive_path: String,
+    sort: Option<SortMode>,
+) -> Result<Vec<TocEntry>, MekuriError> {
+    archive_impl::table_of_contents(&cache, &archive_path, sort.unwrap_or_default())
+}
+
+// Reading every image header (every whole image of a non-solid RAR) can take a while
+#[tauri::command]
+pub async fn list_pages_with_info(
+    cache: State<'_, ArchiveCache>,
+    archive_path: String,
+    sort: Option<SortMode>,
+) -> Result<Vec<PageInfo>, MekuriError> {
+    archive_impl::list_pages_with_info(&cache, &archive_path, sort.unwrap_or_default())
+}
+
+#[tauri::command]
+pub fn get_archive_image(
+    cache: State<'_, ArchiveCache>,
+    archive_path: String,
+    entry_name: String,
+    password: Option<String>,
+) -> Result<String, MekuriError> {
+    if let Some(password) = password {
+        archive_impl::set_password(&cache, &archive_path, &password)?;
+    }
+    archive_impl::get_image_base64(&cache, &archive_path, &entry_name)
+}
+
+#[tauri::command]
+pub fn get_archive_image_by_id(
+    cache: State<'_, ArchiveCache>,
+    archive_path: String,
+    entry: EntryId,
+) -> Result<String, MekuriError> {
+    archive_impl::get_image_base64_by_id(&cache, &archive_path, &entry)
+}
+
+#[tauri::command]
+pub fn start_bulk_extraction(
+    app: AppHandle,
+    cache: State<'_, ArchiveCache>,
+    archive_path: String,
+) -> Result<bool, MekuriError> {
+    archive_impl::start_bulk_extraction(&cache, &archive_path, move |progress| {
+        let _ = app.emit(EXTRACT_PROGRESS_EVENT, progress);
+    })
+}
+
+// Decoding and resizing are slow, so the thumbnail commands stay off the main thread
+#[tauri::command]
+pub async fn get_archive_thumbnail(
+    cache: State<'_, ArchiveCache>,
+    thumbnails: State<'_, ThumbnailCache>,
+    archive_path: String,
+    max_size: u32,
+) -> Result<String, MekuriError> {
+    thumbnail::archive_thumbnail(&cache, &thumbnails, &archive_path, max_size)
+}
+
+#[tauri::command]
+pub async fn get_page_thumbnail(
+    cache: State<'_, ArchiveCache>,
+    archive_path: String,
+    entry_name: String,
+    max_size: u32,
+) -> Result<String, MekuriError> {
+    thumbnail::page_thumbnail(&cache, &archive_path, &entry_name, max_size)
+}
+
+#[tauri::command]
+pub fn clear_thumbnail_cache(thumbnails: State<'_, ThumbnailCache>) -> Result<(), MekuriError> {
+    thumbnails.clear()
+}
 
 #[tauri::command]
-pub fn list_archive_images(archive_path: String) -> Result<Vec<String>, String> {
-    archive_impl::list_images(&archive_path)
+pub fn get_archive_metadata(
+    cache: State<'_, ArchiveCache>,
+    archive_path: String,
+) -> Result<Option<ArchiveMetadata>, MekuriError> {
+    archive_impl::read_metadata(&cache, &archive_path)
 }
 
+/// Every command taking an `archive_path` also accepts the virtual paths of the nested
+/// archives in the returned tree, including this one to analyze a nested archive in turn.
+/// Nested archives extracted for them are kept until the window opens another archive or
+/// closes. The pages of the archive are served over `mekuri://` from then on.
 #[tauri::command]
-pub fn get_archive_image(archive_path: String, entry_name: String) -> Result<String, String> {
-    archive_impl::get_image_base64(&archive_path, &entry_name)
+pub fn analyze_archive_contents(
+    cache: State<'_, ArchiveCache>,
+    scope: State<'_, FileScope>,
+    window: Window,
+    archive_path: String,
+) -> Result<ContentTree, MekuriError> {
+    scope.allow_archive(&archive_path);
+    archive_impl::open_nested(&cache, window.label(), &archive_path);
+    archive_impl::analyze_contents(&cache, &archive_path)
 }
 
 #[tauri::command]
-pub fn analyze_archive_contents(archive_path: String) -> Result<ArchiveContents, String> {
-    archive_impl::analyze_contents(&archive_path)
+pub fn prefetch_pages(
+    prefetcher: State<'_, Prefetcher>,
+    archive_path: String,
+    entries: Vec<String>,
+    priority: u8,
+) {
+    prefetcher.prefetch(&archive_path, entries, priority);
 }
 
 #[tauri::command]
-pub fn extract_nested_archive(parent_path: String, nested_name: String) -> Result<String, String> {
-    archive_impl::extract_nested_archive(&parent_path, &nested_name)
+pub fn cancel_prefetch(prefetcher: State<'_, Prefetcher>, archive_path: String) {
+    prefetcher.cancel(&archive_path);
 }
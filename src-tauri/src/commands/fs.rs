@@ -1,7 +1,15 @@
-use crate::archive;
-use base64::Engine;
+use crate::archive::{self, FileKind};
+use crate::error::MekuriError;
+use crate::protocol::FileScope;
+use crate::thumbnail_cache::ThumbnailCache;
 use serde::Serialize;
-use std::path::PathBuf;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager, State};
+
+/// Long edge of the covers shown in the file list. The frontend requests covers at the
+/// same size so that the ones it generates are found here on the next visit.
+const COVER_THUMBNAIL_SIZE: u32 = 128;
 
 #[derive(Debug, Serialize)]
 pub struct DirectoryEntry {
@@ -10,15 +18,55 @@ pub struct DirectoryEntry {
     pub is_dir: bool,
     pub is_archive: bool,
     pub is_pdf: bool,
+    /// Folder that directly contains images and can be opened in the viewer
+    pub is_image_folder: bool,
     pub has_subfolders: bool,
+    /// Cover already in the thumbnail cache, as a JPEG file path
+    pub thumbnail: Option<String>,
 }
 
+// Listing reads the start of every file that may be an archive, so it runs on the
+// blocking thread pool instead of holding up other commands
 #[tauri::command]
-pub fn read_directory(path: String) -> Result<Vec<DirectoryEntry>, String> {
-    let dir_path = PathBuf::from(&path);
+pub async fn read_directory(
+    app: AppHandle,
+    path: String,
+) -> Result<Vec<DirectoryEntry>, MekuriError> {
+    run_blocking("Failed to read directory", move || {
+        let mut entries = list_directory(&path)?;
+        attach_cached_covers(&app.state::<ThumbnailCache>(), &mut entries);
+        Ok(entries)
+    })
+    .await
+}
+
+/// Run `work` on the blocking thread pool and wait for it without blocking the caller.
+async fn run_blocking<T: Send + 'static>(
+    context: &str,
+    work: impl FnOnce() -> Result<T, MekuriError> + Send + 'static,
+) -> Result<T, MekuriError> {
+    tauri::async_runtime::spawn_blocking(work)
+        .await
+        .map_err(|e| MekuriError::Io(format!("{context}: {e}")))?
+}
+
+/// Fill in covers that are already cached. Missing covers are left for the frontend
+/// to request, so listing a folder never decodes images.
+fn attach_cached_covers(thumbnails: &ThumbnailCache, entries: &mut [DirectoryEntry]) {
+    for entry in entries.iter_mut() {
+        if entry.is_archive || entry.is_image_folder {
+            entry.thumbnail = thumbnails
+                .cached_path(&entry.path, COVER_THUMBNAIL_SIZE)
+                .map(|path| path.to_string_lossy().to_string());
+        }
+    }
+}
+
+fn list_directory(path: &str) -> Result<Vec<DirectoryEntry>, MekuriError> {
+    let dir_path = PathBuf::from(path);
 
     let entries =
-        std::fs::read_dir(&dir_path).map_err(|e| format!("Failed to read directory: {e}"))?;
+        std::fs::read_dir(&dir_path).map_err(|e| MekuriError::io("Failed to read directory", e))?;
 
     let mut result: Vec<DirectoryEntry> = entries
         .filter_map(|entry| {
@@ -34,8 +82,13 @@ pub fn read_directory(path: String) -> Result<Vec<DirectoryEntry>, String> {
             let entry_path = entry.path();
             let path = entry_path.to_string_lossy().to_string();
             let is_dir = metadata.is_dir();
-            let is_archive = !is_dir && archive::is_archive_file(&name);
-            let is_pdf = !is_dir && !is_archive && archive::is_pdf_file(&name);
+            let kind = if is_dir {
+                FileKind::Other
+            } else {
+                archive::detect_file_kind(&entry_path)
+            };
+            let is_archive = kind == FileKind::Archive;
+            let is_pdf = kind == FileKind::Pdf;
 
             // Only show directories, archives, and PDFs
             if !is_dir && !is_archive && !is_pdf {
@@ -48,6 +101,7 @@ pub fn read_directory(path: String) -> Result<Vec<DirectoryEntry>, String> {
             } else {
                 false
             };
+            let is_image_folder = is_dir && archive::is_image_folder(&entry_path);
 
             Some(DirectoryEntry {
                 name,
@@ -55,10 +109,13 @@ pub fn read_directory(path: String) -> Result<Vec<DirectoryEntry>, String> {
                 is_dir,
                 is_archive,
                 is_pdf,
+                is_image_folder,
                 has_subfolders,
+                thumbnail: None,
             })
         })
         .collect();
+    collapse_volumes(&mut result);
 
     result.sort_by(|a, b| {
         // Directories first, then archives
@@ -72,84 +129,130 @@ pub fn read_directory(path: String) -> Result<Vec<DirectoryEntry>, String> {
     Ok(result)
 }
 
+/// How the viewer should open a path, by the same rules as the directory listing.
+/// Folders of images are opened like archives. A PDF becomes readable through the
+/// `mekuri://` file route, which serves no other files outside the thumbnail cache.
 #[tauri::command]
-pub fn read_file_base64(path: String) -> Result<String, String> {
-    let file_path = PathBuf::from(&path);
-    let data = std::fs::read(&file_path).map_err(|e| format!("Failed to read file: {e}"))?;
-    Ok(base64::engine::general_purpose::STANDARD.encode(&data))
+pub fn get_file_kind(scope: State<'_, FileScope>, path: String) -> FileKind {
+    let path = Path::new(&path);
+    let kind = file_kind(path);
+    if kind == FileKind::Pdf {
+        scope.allow_file(path);
+    }
+    kind
+}
+
+/// Kind of a path as the listing shows it; folders count as archives only when they hold
+/// images directly.
+fn file_kind(path: &Path) -> FileKind {
+    if !path.is_dir() {
+        archive::detect_file_kind(path)
+    } else if archive::is_image_folder(path) {
+        FileKind::Archive
+    } else {
+        FileKind::Other
+    }
 }
 
 #[tauri::command]
-pub fn trash_file(path: String) -> Result<(), String> {
+pub fn trash_file(path: String) -> Result<(), MekuriError> {
     let file_path = PathBuf::from(&path);
     if !file_path.exists() {
-        return Err(format!("File does not exist: {path}"));
+        return Err(MekuriError::NotFound(format!(
+            "File does not exist: {path}"
+        )));
     }
     if !file_path.is_file() {
-        return Err(format!("Path is not a file: {path}"));
+        return Err(MekuriError::InvalidInput(format!(
+            "Path is not a file: {path}"
+        )));
     }
-    trash::delete(&file_path).map_err(|e| format!("Failed to move file to trash: {e}"))
+    trash::delete(&file_path)
+        .map_err(|e| MekuriError::Io(format!("Failed to move file to trash: {e}")))
 }
 
 #[tauri::command]
-pub fn move_file(src: String, dest_dir: String) -> Result<String, String> {
+pub fn move_file(src: String, dest_dir: String) -> Result<String, MekuriError> {
     let src_path = PathBuf::from(&src);
     if !src_path.exists() {
-        return Err(format!("File does not exist: {src}"));
+        return Err(MekuriError::NotFound(format!("File does not exist: {src}")));
     }
     if !src_path.is_file() {
-        return Err(format!("Path is not a file: {src}"));
+        return Err(MekuriError::InvalidInput(format!(
+            "Path is not a file: {src}"
+        )));
     }
     let dest_dir_path = PathBuf::from(&dest_dir);
     if !dest_dir_path.is_dir() {
-        return Err(format!("Destination is not a directory: {dest_dir}"));
+        return Err(MekuriError::InvalidInput(format!(
+            "Destination is not a directory: {dest_dir}"
+        )));
     }
 
     // 同一フォルダへの移動は no-op ではなくエラーとして返す
     let src_parent = src_path
         .parent()
-        .ok_or_else(|| format!("Invalid file path: {src}"))?;
+        .ok_or_else(|| MekuriError::InvalidInput(format!("Invalid file path: {src}")))?;
     let same_dir = match (src_parent.canonicalize(), dest_dir_path.canonicalize()) {
         (Ok(a), Ok(b)) => a == b,
         _ => false,
     };
     if same_dir {
-        return Err("Source and destination are the same folder".to_string());
+        return Err(MekuriError::InvalidInput(
+            "Source and destination are the same folder".to_string(),
+        ));
     }
 
     let file_name = src_path
         .file_name()
-        .ok_or_else(|| format!("Invalid file path: {src}"))?;
+        .ok_or_else(|| MekuriError::InvalidInput(format!("Invalid file path: {src}")))?;
     let dest_path = dest_dir_path.join(file_name);
     if dest_path.exists() {
-        return Err(format!(
+        return Err(MekuriError::AlreadyExists(format!(
             "A file with the same name already exists: {}",
             dest_path.display()
-        ));
+        )));
     }
 
     // rename を試し、失敗（クロスデバイス等）なら copy + remove にフォールバック。
     // コピー後の削除失敗は二重存在を許容し、データ喪失は起こさない。
     if std::fs::rename(&src_path, &dest_path).is_err() {
-        std::fs::copy(&src_path, &dest_path).map_err(|e| format!("Failed to copy file: {e}"))?;
-        std::fs::remove_file(&src_path)
-            .map_err(|e| format!("Copied to destination but failed to remove the original: {e}"))?;
+        std::fs::copy(&src_path, &dest_path)
+            .map_err(|e| MekuriError::io("Failed to copy file", e))?;
+        std::fs::remove_file(&src_path).map_err(|e| {
+            MekuriError::io("Copied to destination but failed to remove the original", e)
+        })?;
     }
 
     dest_path
         .to_str()
         .map(|s| s.to_string())
-        .ok_or_else(|| "Destination path is not valid UTF-8".to_string())
+        .ok_or_else(|| MekuriError::InvalidInput("Destination path is not valid UTF-8".to_string()))
 }
 
+// Walks the whole tree below `path`, so it runs on the blocking thread pool too
 #[tauri::command]
-pub fn search_directory(path: String, query: String) -> Result<Vec<DirectoryEntry>, String> {
-    let dir_path = PathBuf::from(&path);
+pub async fn search_directory(
+    app: AppHandle,
+    path: String,
+    query: String,
+) -> Result<Vec<DirectoryEntry>, MekuriError> {
+    run_blocking("Failed to search directory", move || {
+        let mut entries = find_entries(&path, &query)?;
+        attach_cached_covers(&app.state::<ThumbnailCache>(), &mut entries);
+        Ok(entries)
+    })
+    .await
+}
+
+fn find_entries(path: &str, query: &str) -> Result<Vec<DirectoryEntry>, MekuriError> {
+    let dir_path = PathBuf::from(path);
     let query_lower = query.to_lowercase();
     let mut result: Vec<DirectoryEntry> = Vec::new();
 
     search_recursive(&dir_path, &query_lower, &mut result)
-        .map_err(|e| format!("Failed to search directory: {e}"))?;
+        .map_err(|e| MekuriError::io("Failed to search directory", e))?;
+    collapse_volumes(&mut result);
 
     result.sort_by(|a, b| match (a.is_dir, b.is_dir) {
         (true, false) => std::cmp::Ordering::Less,
@@ -186,22 +289,27 @@ fn search_recursive(
             if name.to_lowercase().contains(query) {
                 let path_str = entry_path.to_string_lossy().to_string();
                 let has_subfolders = has_subdirectories(&entry_path);
+                let is_image_folder = archive::is_image_folder(&entry_path);
                 result.push(DirectoryEntry {
                     name,
                     path: path_str,
                     is_dir: true,
                     is_archive: false,
                     is_pdf: false,
+                    is_image_folder,
                     has_subfolders,
+                    thumbnail: None,
                 });
             }
             // 再帰的にサブディレクトリを検索
             let _ = search_recursive(&entry_path, query, result);
-        } else {
-            let is_archive = archive::is_archive_file(&name);
-            let is_pdf = !is_archive && archive::is_pdf_file(&name);
+        } else if name.to_lowercase().contains(query) {
+            // Only files whose name matches are worth reading a header from
+            let kind = archive::detect_file_kind(&entry_path);
+            let is_archive = kind == FileKind::Archive;
+            let is_pdf = kind == FileKind::Pdf;
 
-            if (is_archive || is_pdf) && name.to_lowercase().contains(query) {
+            if is_archive || is_pdf {
                 let path_str = entry_path.to_string_lossy().to_string();
                 result.push(DirectoryEntry {
                     name,
@@ -209,7 +317,9 @@ fn search_recursive(
                     is_dir: false,
                     is_archive,
                     is_pdf,
+                    is_image_folder: false,
                     has_subfolders: false,
+                    thumbnail: None,
                 });
             }
         }
@@ -218,6 +328,17 @@ fn search_recursive(
     Ok(())
 }
 
+/// Drop the later volumes of multi-volume archives whose head volume is listed too, so
+/// each set shows up once. Opening the head reads the whole set.
+fn collapse_volumes(entries: &mut Vec<DirectoryEntry>) {
+    let paths: HashSet<PathBuf> = entries.iter().map(|e| PathBuf::from(&e.path)).collect();
+    entries.retain(|entry| {
+        entry.is_dir
+            || archive::volume_head_name(&entry.name)
+                .is_none_or(|head| !paths.contains(&Path::new(&entry.path).with_file_name(head)))
+    });
+}
+
 fn has_subdirectories(path: &PathBuf) -> bool {
     if let Ok(entries) = std::fs::read_dir(path) {
         for entry in entries.flatten() {
@@ -243,8 +364,7 @@ mod tests {
     #[test]
     fn trash_file_nonexistent_path() {
         let result = trash_file("/tmp/nonexistent_file_mekuri_test_12345.zip".to_string());
-        assert!(result.is_err());
-        assert!(result.unwrap_err().contains("File does not exist"));
+        assert!(matches!(result, Err(MekuriError::NotFound(_))));
     }
 
     #[test]
@@ -252,8 +372,21 @@ mod tests {
         let dir = tempfile::tempdir().unwrap();
         let dir_path = dir.path().to_string_lossy().to_string();
         let result = trash_file(dir_path);
-        assert!(result.is_err());
-        assert!(result.unwrap_err().contains("Path is not a file"));
+        assert!(matches!(result, Err(MekuriError::InvalidInput(_))));
+    }
+
+    #[test]
+    fn file_kind_of_folder_follows_listing() {
+        let dir = tempfile::tempdir().unwrap();
+        let images = dir.path().join("book");
+        fs::create_dir(&images).unwrap();
+        fs::write(images.join("001.jpg"), "").unwrap();
+        let empty = dir.path().join("empty");
+        fs::create_dir(&empty).unwrap();
+
+        assert_eq!(file_kind(&images), FileKind::Archive);
+        assert_eq!(file_kind(&empty), FileKind::Other);
+        assert_eq!(file_kind(dir.path()), FileKind::Other);
     }
 
     #[test]
@@ -265,8 +398,7 @@ mod tests {
         fs::write(sub.join("nested.zip"), "").unwrap();
         fs::write(sub.join("other.txt"), "").unwrap();
 
-        let result =
-            search_directory(dir.path().to_string_lossy().to_string(), "zip".to_string()).unwrap();
+        let result = find_entries(dir.path().to_str().unwrap(), "zip").unwrap();
         let names: Vec<&str> = result.iter().map(|e| e.name.as_str()).collect();
         assert!(names.contains(&"test.zip"));
         assert!(names.contains(&"nested.zip"));
@@ -281,11 +413,7 @@ mod tests {
         let other = dir.path().join("photos");
         fs::create_dir(&other).unwrap();
 
-        let result = search_directory(
-            dir.path().to_string_lossy().to_string(),
-            "manga".to_string(),
-        )
-        .unwrap();
+        let result = find_entries(dir.path().to_str().unwrap(), "manga").unwrap();
         assert_eq!(result.len(), 1);
         assert_eq!(result[0].name, "manga_vol1");
         assert!(result[0].is_dir);
@@ -296,11 +424,7 @@ mod tests {
         let dir = tempfile::tempdir().unwrap();
         fs::write(dir.path().join("MyArchive.ZIP"), "").unwrap();
 
-        let result = search_directory(
-            dir.path().to_string_lossy().to_string(),
-            "myarchive".to_string(),
-        )
-        .unwrap();
+        let result = find_entries(dir.path().to_str().unwrap(), "myarchive").unwrap();
         assert_eq!(result.len(), 1);
         assert_eq!(result[0].name, "MyArchive.ZIP");
     }
@@ -310,14 +434,89 @@ mod tests {
         let dir = tempfile::tempdir().unwrap();
         fs::write(dir.path().join("test.zip"), "").unwrap();
 
-        let result = search_directory(
-            dir.path().to_string_lossy().to_string(),
-            "nonexistent".to_string(),
-        )
-        .unwrap();
+        let result = find_entries(dir.path().to_str().unwrap(), "nonexistent").unwrap();
         assert!(result.is_empty());
     }
 
+    #[test]
+    fn read_directory_classifies_files_by_content() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("renamed.cbz"), b"Rar!\x1a\x07\x00").unwrap();
+        fs::write(dir.path().join("book"), b"PK\x03\x04").unwrap();
+        fs::write(dir.path().join("scan.zip"), b"%PDF-1.7").unwrap();
+        fs::write(dir.path().join("notes.txt"), b"text").unwrap();
+
+        let result = list_directory(dir.path().to_str().unwrap()).unwrap();
+        let find = |name: &str| result.iter().find(|e| e.name == name);
+
+        assert!(find("renamed.cbz").unwrap().is_archive);
+        assert!(find("book").unwrap().is_archive);
+        let scan = find("scan.zip").unwrap();
+        assert!(scan.is_pdf && !scan.is_archive);
+        assert!(find("notes.txt").is_none());
+    }
+
+    #[test]
+    fn read_directory_collapses_volume_sets() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in [
+            "book.part1.rar",
+            "book.part2.rar",
+            "old.rar",
+            "old.r00",
+            "orphan.part2.rar",
+        ] {
+            fs::write(dir.path().join(name), b"Rar!\x1a\x07\x00").unwrap();
+        }
+        fs::write(dir.path().join("split.zip"), b"PK\x05\x06").unwrap();
+        fs::write(dir.path().join("split.z01"), b"PK\x07\x08").unwrap();
+
+        let result = list_directory(dir.path().to_str().unwrap()).unwrap();
+        let names: Vec<&str> = result.iter().map(|e| e.name.as_str()).collect();
+        // A later volume without its head stays visible
+        assert_eq!(
+            names,
+            vec!["book.part1.rar", "old.rar", "orphan.part2.rar", "split.zip"]
+        );
+    }
+
+    #[test]
+    fn read_directory_flags_image_folders() {
+        let dir = tempfile::tempdir().unwrap();
+        let book = dir.path().join("book");
+        fs::create_dir(&book).unwrap();
+        fs::write(book.join("001.jpg"), b"").unwrap();
+        let empty = dir.path().join("empty");
+        fs::create_dir(&empty).unwrap();
+        fs::write(dir.path().join("cover.jpg"), b"").unwrap();
+
+        let result = list_directory(dir.path().to_str().unwrap()).unwrap();
+        let names: Vec<&str> = result.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, vec!["book", "empty"]);
+        assert!(result[0].is_image_folder);
+        assert!(!result[1].is_image_folder);
+    }
+
+    #[test]
+    fn read_directory_attaches_only_cached_covers() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.zip"), b"PK\x03\x04").unwrap();
+        fs::write(dir.path().join("b.zip"), b"PK\x03\x04").unwrap();
+        let cache_dir = tempfile::tempdir().unwrap();
+        let thumbnails = ThumbnailCache::new(cache_dir.path().to_path_buf());
+        let a = dir.path().join("a.zip").to_string_lossy().to_string();
+        thumbnails
+            .get_or_create(&a, COVER_THUMBNAIL_SIZE, || Ok(b"jpeg".to_vec()))
+            .unwrap();
+
+        let mut result = list_directory(dir.path().to_str().unwrap()).unwrap();
+        attach_cached_covers(&thumbnails, &mut result);
+
+        let cover = result[0].thumbnail.as_ref().unwrap();
+        assert_eq!(fs::read(cover).unwrap(), b"jpeg");
+        assert!(result[1].thumbnail.is_none());
+    }
+
     #[test]
     #[ignore] // Requires Finder interaction on macOS; run manually with `cargo test -- --ignored`
     fn trash_file_success() {
@@ -365,7 +564,7 @@ mod tests {
             dest_dir.to_string_lossy().to_string(),
         );
 
-        assert!(result.unwrap_err().contains("already exists"));
+        assert!(matches!(result, Err(MekuriError::AlreadyExists(_))));
         assert!(src.exists());
         assert_eq!(std::fs::read(dest_dir.join("a.zip")).unwrap(), b"other");
     }
@@ -377,7 +576,7 @@ mod tests {
             "/tmp/nonexistent_mekuri_move_12345.zip".to_string(),
             dir.path().to_string_lossy().to_string(),
         );
-        assert!(result.unwrap_err().contains("does not exist"));
+        assert!(matches!(result, Err(MekuriError::NotFound(_))));
     }
 
     #[test]
@@ -389,7 +588,7 @@ mod tests {
             sub.to_string_lossy().to_string(),
             dir.path().to_string_lossy().to_string(),
         );
-        assert!(result.unwrap_err().contains("not a file"));
+        assert!(matches!(result, Err(MekuriError::InvalidInput(_))));
     }
 
     #[test]
@@ -401,7 +600,7 @@ mod tests {
             src.to_string_lossy().to_string(),
             dir.path().join("no_such_dir").to_string_lossy().to_string(),
         );
-        assert!(result.unwrap_err().contains("not a directory"));
+        assert!(result.unwrap_err().message().contains("not a directory"));
         assert!(src.exists());
     }
 
@@ -414,7 +613,7 @@ mod tests {
             src.to_string_lossy().to_string(),
             dir.path().to_string_lossy().to_string(),
         );
-        assert!(result.unwrap_err().contains("same"));
+        assert!(result.unwrap_err().message().contains("same"));
         assert!(src.exists());
     }
 }
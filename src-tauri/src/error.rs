@@ -0,0 +1,109 @@
+//! バックエンド共通のエラー型。
+//! フロントエンドには `{ code, message }` として渡り、`code` で処理を分けられる。
+
+use serde::Serialize;
+use std::fmt;
+use std::io;
+
+/// コマンドとアーカイブ処理が返すエラー。
+///
+/// バリアント名がそのままフロントエンド向けの安定したコードになる（名前を変えないこと）。
+/// 中身は人が読むためのメッセージで、文言は変わりうるので分岐には使わない。
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "code", content = "message")]
+pub enum MekuriError {
+    /// ファイル・フォルダ・アーカイブ内のエントリが存在しない
+    NotFound(String),
+    /// 対応していない形式（アーカイブ形式、圧縮方式、画像形式など）
+    UnsupportedFormat(String),
+    /// アーカイブや画像が壊れていて読めない
+    Corrupt(String),
+    /// 暗号化されていて読むにはパスワードが要る
+    PasswordRequired(String),
+    /// OS に読み書きを拒否された
+    PermissionDenied(String),
+    /// 移動先などに同じ名前のものがすでにある
+    AlreadyExists(String),
+    /// 引数がおかしい（不正なパスなど）
+    InvalidInput(String),
+    /// 上のどれにも当てはまらない入出力エラー
+    Io(String),
+}
+
+impl MekuriError {
+    pub fn message(&self) -> &str {
+        match self {
+            Self::NotFound(message)
+            | Self::UnsupportedFormat(message)
+            | Self::Corrupt(message)
+            | Self::PasswordRequired(message)
+            | Self::PermissionDenied(message)
+            | Self::AlreadyExists(message)
+            | Self::InvalidInput(message)
+            | Self::Io(message) => message,
+        }
+    }
+
+    /// `io::Error` を種類に応じたバリアントに振り分ける。メッセージは `"{context}: {err}"`。
+    pub fn io(context: &str, err: io::Error) -> Self {
+        let message = format!("{context}: {err}");
+        match err.kind() {
+            io::ErrorKind::NotFound => Self::NotFound(message),
+            io::ErrorKind::PermissionDenied => Self::PermissionDenied(message),
+            io::ErrorKind::AlreadyExists => Self::AlreadyExists(message),
+            io::ErrorKind::InvalidData | io::ErrorKind::UnexpectedEof => Self::Corrupt(message),
+            io::ErrorKind::InvalidInput => Self::InvalidInput(message),
+            io::ErrorKind::Unsupported => Self::UnsupportedFormat(message),
+            _ => Self::Io(message),
+        }
+    }
+
+    /// ロックの取得に失敗したとき（別スレッドのパニックで poison された場合）のエラー。
+    pub fn lock<T>(context: &str, err: std::sync::PoisonError<T>) -> Self {
+        Self::Io(format!("{context}: {err}"))
+    }
+}
+
+impl fmt::Display for MekuriError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+impl std::error::Error for MekuriError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serializes_code_and_message() {
+        let err = MekuriError::NotFound("Entry not found: 01.jpg".to_string());
+        assert_eq!(
+            serde_json::to_value(&err).unwrap(),
+            serde_json::json!({ "code": "NotFound", "message": "Entry not found: 01.jpg" })
+        );
+        assert_eq!(err.to_string(), "Entry not found: 01.jpg");
+    }
+
+    #[test]
+    fn test_io_maps_error_kinds() {
+        let io = |kind| MekuriError::io("Failed to open archive", io::Error::from(kind));
+        assert!(matches!(
+            io(io::ErrorKind::NotFound),
+            MekuriError::NotFound(_)
+        ));
+        assert!(matches!(
+            io(io::ErrorKind::PermissionDenied),
+            MekuriError::PermissionDenied(_)
+        ));
+        assert!(matches!(
+            io(io::ErrorKind::UnexpectedEof),
+            MekuriError::Corrupt(_)
+        ));
+        assert!(matches!(io(io::ErrorKind::Interrupted), MekuriError::Io(_)));
+        assert!(io(io::ErrorKind::NotFound)
+            .message()
+            .starts_with("Failed to open archive: "));
+    }
+}
@@ -15,7 +15,11 @@ pub struct LaunchState {
 }
 
 /// mekuri が開ける拡張子（ASCII 大文字小文字無視）。
-const SUPPORTED_EXTENSIONS: [&str; 5] = ["zip", "cbz", "rar", "cbr", "pdf"];
+/// `tar.gz` などの複合拡張子を含むため、ファイル名の末尾で判定する。
+const SUPPORTED_EXTENSIONS: [&str; 14] = [
+    "zip", "cbz", "rar", "cbr", "7z", "cb7", "tar", "cbt", "tgz", "tar.gz", "tar.bz2", "tar.xz",
+    "tar.zst", "pdf",
+];
 
 /// JS の encodeURIComponent と同じ非エスケープ集合
 /// （英数字と - _ . ! ~ * ' ( ) 以外をパーセントエンコード）。
@@ -31,12 +35,13 @@ const URI_COMPONENT: &AsciiSet = &NON_ALPHANUMERIC
     .remove(b')');
 
 pub fn is_supported_file(path: &Path) -> bool {
-    path.extension()
-        .and_then(|e| e.to_str())
-        .map(|e| {
+    path.file_name()
+        .and_then(|n| n.to_str())
+        .map(|n| {
+            let lower = n.to_ascii_lowercase();
             SUPPORTED_EXTENSIONS
                 .iter()
-                .any(|s| e.eq_ignore_ascii_case(s))
+                .any(|s| lower.ends_with(&format!(".{s}")))
         })
         .unwrap_or(false)
 }
@@ -175,7 +180,23 @@ mod tests {
     #[test]
     fn supported_extensions_are_accepted_case_insensitively() {
         for name in [
-            "a.zip", "a.cbz", "a.rar", "a.cbr", "a.pdf", "a.ZIP", "a.CbZ", "a.PDF",
+            "a.zip",
+            "a.cbz",
+            "a.rar",
+            "a.cbr",
+            "a.7z",
+            "a.cb7",
+            "a.tar",
+            "a.cbt",
+            "a.tgz",
+            "a.tar.gz",
+            "a.tar.bz2",
+            "a.tar.xz",
+            "a.TAR.ZST",
+            "a.pdf",
+            "a.ZIP",
+            "a.CbZ",
+            "a.PDF",
         ] {
             assert!(is_supported_file(Path::new(name)), "{name}");
         }
@@ -183,7 +204,15 @@ mod tests {
 
     #[test]
     fn unsupported_files_are_rejected() {
-        for name in ["a.txt", "a.png", "a.avif", "archive", "a.", "a.zip.bak"] {
+        for name in [
+            "a.txt",
+            "a.png",
+            "a.avif",
+            "archive",
+            "a.",
+            "a.zip.bak",
+            "a.gz",
+        ] {
             assert!(!is_supported_file(Path::new(name)), "{name}");
         }
     }
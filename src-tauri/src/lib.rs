@@ -1,31 +1,81 @@
 mod archive;
 mod commands;
+mod error;
 pub mod launch;
+mod protocol;
+mod thumbnail;
+mod thumbnail_cache;
 pub mod window_label;
 
+use tauri::Manager;
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
+    let archive_cache = archive::ArchiveCache::default();
+
     let app = tauri::Builder::default()
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_store::Builder::default().build())
         .manage(launch::LaunchState::default())
+        .manage(archive::Prefetcher::new(archive_cache.clone()))
+        .manage(archive_cache)
+        .setup(|app| {
+            // 前回の異常終了などで残った展開先を消す（時間がかかりうるので別スレッドで）
+            std::thread::spawn(archive::sweep_stale_temp_dirs);
+            let dir = app.path().app_cache_dir()?.join("thumbnails");
+            // mekuri:// の /file/ はサムネイルキャッシュ内と、ビューワーで開いた PDF だけを返す
+            app.manage(protocol::FileScope::new(dir.clone()));
+            app.manage(thumbnail_cache::ThumbnailCache::new(dir));
+            Ok(())
+        })
+        .on_window_event(|window, event| {
+            // ウィンドウが閉じたら、そのウィンドウのために展開したネストアーカイブを解放する
+            if let tauri::WindowEvent::Destroyed = event {
+                let cache = window.state::<archive::ArchiveCache>();
+                archive::close_nested(&cache, window.label());
+            }
+        })
+        .register_asynchronous_uri_scheme_protocol(protocol::SCHEME, |ctx, request, responder| {
+            let app = ctx.app_handle().clone();
+            // アーカイブの読み出しはブロッキングなので webview のスレッドから外す
+            std::thread::spawn(move || {
+                let cache = app.state::<archive::ArchiveCache>();
+                let scope = app.state::<protocol::FileScope>();
+                responder.respond(protocol::handle(&cache, &scope, &request));
+            });
+        })
         .invoke_handler(tauri::generate_handler![
             commands::fs::read_directory,
-            commands::fs::read_file_base64,
+            commands::fs::get_file_kind,
             commands::fs::trash_file,
             commands::fs::move_file,
             commands::fs::search_directory,
             commands::archive::list_archive_images,
+            commands::archive::list_archive_entries,
+            commands::archive::first_volume,
+            commands::archive::list_archive_pages,
+            commands::archive::list_pages_with_info,
+            commands::archive::get_table_of_contents,
             commands::archive::get_archive_image,
+            commands::archive::get_archive_image_by_id,
+            commands::archive::get_archive_thumbnail,
+            commands::archive::get_page_thumbnail,
+            commands::archive::clear_thumbnail_cache,
+            commands::archive::get_archive_metadata,
             commands::archive::analyze_archive_contents,
-            commands::archive::extract_nested_archive,
+            commands::archive::prefetch_pages,
+            commands::archive::cancel_prefetch,
+            commands::archive::start_bulk_extraction,
             commands::launch::was_opened_via_file,
         ])
         .build(tauri::generate_context!())
         .expect("error while building tauri application");
 
-    app.run(|_app_handle, _event| {
+    app.run(|_app_handle, event| {
+        if let tauri::RunEvent::Exit = event {
+            archive::remove_temp_dirs();
+        }
         #[cfg(target_os = "macos")]
-        launch::handle_run_event(_app_handle, _event);
+        launch::handle_run_event(_app_handle, event);
     });
 }
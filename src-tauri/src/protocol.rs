@@ -0,0 +1,554 @@
+//! `mekuri://` URI スキームプロトコル。
+//! アーカイブ内の画像やローカルファイルを Base64 / JSON を経由せず生バイトで返す。
+//!
+//! ルート（各セグメントは encodeURIComponent 済み。JS 側は src/api/protocol.ts で生成）:
+//! - `/page/<archive_path>/<entry_name>`: アーカイブ内の画像。ビューワーで開いたアーカイブ
+//!   （とその中のネストアーカイブ）に限る（`FileScope`）
+//! - `/file/<path>`: ディスク上のファイル。ビューワーで開いた PDF とサムネイルキャッシュ内の
+//!   ファイルに限る（`FileScope`）
+//!
+//! どちらも単一区間の HTTP Range に対応する（pdf.js の分割読み込み用）。
+//! CORS はアプリ自身のオリジンにだけ許可する。
+
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use percent_encoding::percent_decode_str;
+use tauri::http::{header, Method, Request, Response, StatusCode};
+
+use crate::archive::{self, ArchiveCache};
+use crate::error::MekuriError;
+
+pub const SCHEME: &str = "mekuri";
+
+/// webview のオリジン（macOS / Linux、Windows の http / https）。
+const APP_ORIGINS: [&str; 3] = [
+    "tauri://localhost",
+    "http://tauri.localhost",
+    "https://tauri.localhost",
+];
+/// `pnpm tauri dev` の Vite 開発サーバー（tauri.conf.json の devUrl と同値）。
+const DEV_ORIGIN: &str = "http://localhost:1420";
+
+/// `/file/` と `/page/` で配信してよいファイル。Tauri の managed state。
+pub struct FileScope {
+    /// 配下のファイルをすべて配信するディレクトリ（サムネイルキャッシュ）
+    dir: PathBuf,
+    /// ビューワーで開いた PDF（正規化済みのパス）
+    files: Mutex<HashSet<PathBuf>>,
+    /// ビューワーで開いたアーカイブ・画像フォルダ（仮想パスの起点を正規化したもの）
+    archives: Mutex<HashSet<PathBuf>>,
+}
+
+impl FileScope {
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            files: Mutex::new(HashSet::new()),
+            archives: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// ビューワーで開く PDF を配信できるようにする。
+    pub fn allow_file(&self, path: &Path) {
+        if let (Ok(path), Ok(mut files)) = (path.canonicalize(), self.files.lock()) {
+            files.insert(path);
+        }
+    }
+
+    /// ビューワーで開くアーカイブのページを配信できるようにする。
+    /// 仮想パスならディスク上の起点を記録し、その中のネストアーカイブもすべて配信する。
+    pub fn allow_archive(&self, archive_path: &str) {
+        let root = archive::root_on_disk(archive_path);
+        if let (Ok(root), Ok(mut archives)) =
+            (Path::new(&root).canonicalize(), self.archives.lock())
+        {
+            archives.insert(root);
+        }
+    }
+
+    fn allows_archive(&self, archive_path: &str) -> bool {
+        let root = archive::root_on_disk(archive_path);
+        let Ok(root) = Path::new(&root).canonicalize() else {
+            return false;
+        };
+        self.archives
+            .lock()
+            .is_ok_and(|archives| archives.contains(&root))
+    }
+
+    /// `..` やシンボリックリンクで範囲外に出ないよう、正規化したパスで判定する。
+    fn allows(&self, path: &Path) -> bool {
+        let Ok(path) = path.canonicalize() else {
+            return false;
+        };
+        if self
+            .dir
+            .canonicalize()
+            .is_ok_and(|dir| path.starts_with(dir))
+        {
+            return true;
+        }
+        self.files.lock().is_ok_and(|files| files.contains(&path))
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Route {
+    Page {
+        archive_path: String,
+        entry_name: String,
+    },
+    File {
+        path: String,
+    },
+}
+
+/// Range ヘッダーの解釈結果（区間は両端を含む）。
+#[derive(Debug, PartialEq, Eq)]
+enum ByteRange {
+    Full,
+    Partial { start: u64, end: u64 },
+    Unsatisfiable,
+}
+
+/// URI のパス部分をルートに変換する。
+fn parse_route(path: &str) -> Option<Route> {
+    let segments: Vec<String> = path
+        .trim_start_matches('/')
+        .split('/')
+        .map(|s| percent_decode_str(s).decode_utf8().map(|d| d.into_owned()))
+        .collect::<Result<_, _>>()
+        .ok()?;
+
+    match segments.as_slice() {
+        [route, archive_path, entry_name] if route == "page" => Some(Route::Page {
+            archive_path: archive_path.clone(),
+            entry_name: entry_name.clone(),
+        }),
+        [route, path] if route == "file" => Some(Route::File { path: path.clone() }),
+        _ => None,
+    }
+}
+
+/// `bytes=` 形式の Range ヘッダーを解釈する。
+/// 複数区間や解釈できない値は RFC 9110 に従い無視して全体を返す。
+fn parse_range(value: Option<&str>, len: u64) -> ByteRange {
+    let Some(spec) = value.and_then(|v| v.trim().strip_prefix("bytes=")) else {
+        return ByteRange::Full;
+    };
+    if spec.contains(',') {
+        return ByteRange::Full;
+    }
+    let Some((start, end)) = spec.split_once('-') else {
+        return ByteRange::Full;
+    };
+
+    let (start, end) = match (start.trim(), end.trim()) {
+        // bytes=-N: 末尾 N バイト
+        ("", suffix) => match suffix.parse::<u64>() {
+            Ok(0) => return ByteRange::Unsatisfiable,
+            Ok(n) => (len.saturating_sub(n), len.saturating_sub(1)),
+            Err(_) => return ByteRange::Full,
+        },
+        // bytes=N- / bytes=N-M
+        (start, end) => {
+            let Ok(start) = start.parse::<u64>() else {
+                return ByteRange::Full;
+            };
+            let end = if end.is_empty() {
+                len.saturating_sub(1)
+            } else {
+                match end.parse::<u64>() {
+                    Ok(end) if end >= start => end.min(len.saturating_sub(1)),
+                    _ => return ByteRange::Full,
+                }
+            };
+            (start, end)
+        }
+    };
+
+    if len == 0 || start >= len {
+        ByteRange::Unsatisfiable
+    } else {
+        ByteRange::Partial { start, end }
+    }
+}
+
+/// プロトコルリクエストを処理する。lib.rs でワーカースレッドから呼ばれる。
+pub fn handle(
+    cache: &ArchiveCache,
+    scope: &FileScope,
+    request: &Request<Vec<u8>>,
+) -> Response<Vec<u8>> {
+    let origin = request
+        .headers()
+        .get(header::ORIGIN)
+        .and_then(|v| v.to_str().ok());
+    with_cors(respond(cache, scope, request), origin)
+}
+
+fn respond(
+    cache: &ArchiveCache,
+    scope: &FileScope,
+    request: &Request<Vec<u8>>,
+) -> Response<Vec<u8>> {
+    // pdf.js は fetch を使うため、Range 付きのクロスオリジン要求のプリフライトに応答する
+    if request.method() == Method::OPTIONS {
+        return Response::builder()
+            .status(StatusCode::NO_CONTENT)
+            .header(header::ACCESS_CONTROL_ALLOW_METHODS, "GET, OPTIONS")
+            .header(header::ACCESS_CONTROL_ALLOW_HEADERS, "Range")
+            .body(Vec::new())
+            .unwrap_or_default();
+    }
+
+    let range = request
+        .headers()
+        .get(header::RANGE)
+        .and_then(|v| v.to_str().ok());
+
+    let result = match parse_route(request.uri().path()) {
+        Some(Route::Page {
+            archive_path,
+            entry_name,
+        }) if scope.allows_archive(&archive_path) => {
+            archive::read_entry(cache, &archive_path, &entry_name).map(|data| {
+                let mime = archive::mime_type_from_name(&entry_name);
+                bytes_response(data, mime, range)
+            })
+        }
+        Some(Route::Page { archive_path, .. }) => Err(MekuriError::PermissionDenied(format!(
+            "Archive is not served: {archive_path}"
+        ))),
+        Some(Route::File { path }) if scope.allows(Path::new(&path)) => file_response(&path, range),
+        Some(Route::File { path }) => Err(MekuriError::PermissionDenied(format!(
+            "File is not served: {path}"
+        ))),
+        None => return error_response(StatusCode::NOT_FOUND, "Unknown mekuri:// route"),
+    };
+
+    result.unwrap_or_else(|e| error_response(error_status(&e), e.message()))
+}
+
+/// エラーの種類に対応する HTTP ステータス。
+fn error_status(err: &MekuriError) -> StatusCode {
+    match err {
+        MekuriError::NotFound(_) => StatusCode::NOT_FOUND,
+        MekuriError::PermissionDenied(_) | MekuriError::PasswordRequired(_) => {
+            StatusCode::FORBIDDEN
+        }
+        MekuriError::UnsupportedFormat(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+/// メモリ上のバイト列を（Range があれば切り出して）返す。
+fn bytes_response(data: Vec<u8>, mime: &str, range: Option<&str>) -> Response<Vec<u8>> {
+    let len = data.len() as u64;
+    match parse_range(range, len) {
+        ByteRange::Full => ok_response(data, mime, len),
+        ByteRange::Partial { start, end } => partial_response(
+            data[start as usize..=end as usize].to_vec(),
+            mime,
+            start,
+            end,
+            len,
+        ),
+        ByteRange::Unsatisfiable => unsatisfiable_response(len),
+    }
+}
+
+/// ディスク上のファイルを返す。Range 指定時は該当区間のみ読み込む。
+fn file_response(path: &str, range: Option<&str>) -> Result<Response<Vec<u8>>, MekuriError> {
+    let mime = if archive::is_pdf_file(path) {
+        "application/pdf"
+    } else {
+        archive::mime_type_from_name(path)
+    };
+
+    let mut file = File::open(path).map_err(|e| MekuriError::io("Failed to read file", e))?;
+    let len = file
+        .metadata()
+        .map_err(|e| MekuriError::io("Failed to read file", e))?
+        .len();
+
+    match parse_range(range, len) {
+        ByteRange::Full => {
+            let mut data = Vec::with_capacity(len as usize);
+            file.read_to_end(&mut data)
+                .map_err(|e| MekuriError::io("Failed to read file", e))?;
+            Ok(ok_response(data, mime, len))
+        }
+        ByteRange::Partial { start, end } => {
+            let mut data = vec![0; (end - start + 1) as usize];
+            file.seek(SeekFrom::Start(start))
+                .and_then(|_| file.read_exact(&mut data))
+                .map_err(|e| MekuriError::io("Failed to read file", e))?;
+            Ok(partial_response(data, mime, start, end, len))
+        }
+        ByteRange::Unsatisfiable => Ok(unsatisfiable_response(len)),
+    }
+}
+
+/// アプリ自身のオリジンからの要求にだけ CORS ヘッダーを付ける。
+fn with_cors(mut response: Response<Vec<u8>>, origin: Option<&str>) -> Response<Vec<u8>> {
+    let headers = response.headers_mut();
+    headers.insert(header::VARY, header::HeaderValue::from_static("Origin"));
+    let allowed = origin.filter(|origin| {
+        APP_ORIGINS.contains(origin) || (cfg!(debug_assertions) && *origin == DEV_ORIGIN)
+    });
+    if let Some(origin) = allowed.and_then(|o| header::HeaderValue::from_str(o).ok()) {
+        headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, origin);
+        headers.insert(
+            header::ACCESS_CONTROL_EXPOSE_HEADERS,
+            header::HeaderValue::from_static("Accept-Ranges, Content-Length, Content-Range"),
+        );
+    }
+    response
+}
+
+fn ok_response(data: Vec<u8>, mime: &str, len: u64) -> Response<Vec<u8>> {
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, mime)
+        .header(header::CONTENT_LENGTH, len)
+        .header(header::ACCEPT_RANGES, "bytes")
+        .body(data)
+        .unwrap_or_default()
+}
+
+fn partial_response(
+    data: Vec<u8>,
+    mime: &str,
+    start: u64,
+    end: u64,
+    len: u64,
+) -> Response<Vec<u8>> {
+    Response::builder()
+        .status(StatusCode::PARTIAL_CONTENT)
+        .header(header::CONTENT_TYPE, mime)
+        .header(header::CONTENT_LENGTH, data.len())
+        .header(header::CONTENT_RANGE, format!("bytes {start}-{end}/{len}"))
+        .header(header::ACCEPT_RANGES, "bytes")
+        .body(data)
+        .unwrap_or_default()
+}
+
+fn unsatisfiable_response(len: u64) -> Response<Vec<u8>> {
+    Response::builder()
+        .status(StatusCode::RANGE_NOT_SATISFIABLE)
+        .header(header::CONTENT_RANGE, format!("bytes */{len}"))
+        .body(Vec::new())
+        .unwrap_or_default()
+}
+
+fn error_response(status: StatusCode, message: &str) -> Response<Vec<u8>> {
+    Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, "text/plain; charset=utf-8")
+        .body(message.as_bytes().to_vec())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::launch::encode_uri_component;
+
+    fn get(path: &str, range: Option<&str>) -> Response<Vec<u8>> {
+        get_in(&FileScope::new(PathBuf::from("/nonexistent")), path, range)
+    }
+
+    fn get_in(scope: &FileScope, path: &str, range: Option<&str>) -> Response<Vec<u8>> {
+        let mut builder = Request::builder().uri(format!("mekuri://localhost{path}"));
+        if let Some(range) = range {
+            builder = builder.header(header::RANGE, range);
+        }
+        handle(
+            &ArchiveCache::default(),
+            scope,
+            &builder.body(Vec::new()).unwrap(),
+        )
+    }
+
+    fn file_url(path: &Path) -> String {
+        format!("/file/{}", encode_uri_component(path.to_str().unwrap()))
+    }
+
+    fn header_str(res: &Response<Vec<u8>>, name: header::HeaderName) -> &str {
+        res.headers().get(name).unwrap().to_str().unwrap()
+    }
+
+    #[test]
+    fn parse_route_decodes_segments() {
+        assert_eq!(
+            parse_route("/page/%2Fbooks%2F%E6%BC%AB%E7%94%BB.cbz/ch1%2F001.jpg"),
+            Some(Route::Page {
+                archive_path: "/books/漫画.cbz".to_string(),
+                entry_name: "ch1/001.jpg".to_string(),
+            })
+        );
+        assert_eq!(
+            parse_route("/file/C%3A%5Cdocs%5Ca.pdf"),
+            Some(Route::File {
+                path: "C:\\docs\\a.pdf".to_string(),
+            })
+        );
+        assert_eq!(parse_route("/page/only-archive"), None);
+        assert_eq!(parse_route("/other/x"), None);
+    }
+
+    #[test]
+    fn parse_range_variants() {
+        assert_eq!(parse_range(None, 100), ByteRange::Full);
+        assert_eq!(
+            parse_range(Some("bytes=0-9"), 100),
+            ByteRange::Partial { start: 0, end: 9 }
+        );
+        assert_eq!(
+            parse_range(Some("bytes=90-"), 100),
+            ByteRange::Partial { start: 90, end: 99 }
+        );
+        assert_eq!(
+            parse_range(Some("bytes=-10"), 100),
+            ByteRange::Partial { start: 90, end: 99 }
+        );
+        assert_eq!(
+            parse_range(Some("bytes=50-500"), 100),
+            ByteRange::Partial { start: 50, end: 99 }
+        );
+        assert_eq!(
+            parse_range(Some("bytes=100-"), 100),
+            ByteRange::Unsatisfiable
+        );
+        assert_eq!(parse_range(Some("bytes=0-1,5-6"), 100), ByteRange::Full);
+        assert_eq!(parse_range(Some("items=0-1"), 100), ByteRange::Full);
+        assert_eq!(parse_range(Some("bytes=9-1"), 100), ByteRange::Full);
+    }
+
+    #[test]
+    fn file_route_serves_ranges() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("doc.pdf");
+        std::fs::write(&path, b"%PDF-0123456789").unwrap();
+        let url = file_url(&path);
+        let scope = FileScope::new(PathBuf::from("/nonexistent"));
+        scope.allow_file(&path);
+        let get = |url: &str, range| get_in(&scope, url, range);
+
+        let full = get(&url, None);
+        assert_eq!(full.status(), StatusCode::OK);
+        assert_eq!(header_str(&full, header::CONTENT_TYPE), "application/pdf");
+        assert_eq!(header_str(&full, header::ACCEPT_RANGES), "bytes");
+        assert_eq!(full.body(), b"%PDF-0123456789");
+
+        let part = get(&url, Some("bytes=5-7"));
+        assert_eq!(part.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(header_str(&part, header::CONTENT_RANGE), "bytes 5-7/15");
+        assert_eq!(part.body(), b"012");
+
+        let bad = get(&url, Some("bytes=99-"));
+        assert_eq!(bad.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+    }
+
+    #[test]
+    fn file_route_is_limited_to_scope() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache_dir = dir.path().join("thumbnails");
+        std::fs::create_dir(&cache_dir).unwrap();
+        let thumbnail = cache_dir.join("0123.jpg");
+        std::fs::write(&thumbnail, b"jpeg").unwrap();
+        let secret = dir.path().join("secret.txt");
+        std::fs::write(&secret, b"secret").unwrap();
+        let scope = FileScope::new(cache_dir.clone());
+
+        assert_eq!(get_in(&scope, &file_url(&thumbnail), None).body(), b"jpeg");
+        assert_eq!(
+            get_in(&scope, &file_url(&secret), None).status(),
+            StatusCode::FORBIDDEN
+        );
+        // Leaving the cache folder through `..` is not allowed either
+        let escaped = cache_dir.join("..").join("secret.txt");
+        assert_eq!(
+            get_in(&scope, &file_url(&escaped), None).status(),
+            StatusCode::FORBIDDEN
+        );
+    }
+
+    #[test]
+    fn cors_allows_only_app_origins() {
+        let request = |origin: &str| {
+            let request = Request::builder()
+                .uri("mekuri://localhost/nope")
+                .header(header::ORIGIN, origin)
+                .body(Vec::new())
+                .unwrap();
+            handle(
+                &ArchiveCache::default(),
+                &FileScope::new(PathBuf::from("/nonexistent")),
+                &request,
+            )
+        };
+
+        let app = request("tauri://localhost");
+        assert_eq!(
+            header_str(&app, header::ACCESS_CONTROL_ALLOW_ORIGIN),
+            "tauri://localhost"
+        );
+        let other = request("https://example.com");
+        assert!(other
+            .headers()
+            .get(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+            .is_none());
+    }
+
+    #[test]
+    fn page_route_serves_folder_image() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("001.png"), b"fake-png").unwrap();
+        let folder = dir.path().to_str().unwrap();
+        let url = format!("/page/{}/001.png", encode_uri_component(folder));
+        let scope = FileScope::new(PathBuf::from("/nonexistent"));
+        scope.allow_archive(folder);
+
+        let res = get_in(&scope, &url, None);
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(header_str(&res, header::CONTENT_TYPE), "image/png");
+        assert_eq!(res.body(), b"fake-png");
+
+        let missing = get_in(&scope, &format!("{url}.missing"), None);
+        assert_eq!(missing.status(), StatusCode::NOT_FOUND);
+        assert_eq!(get("/nope", None).status(), StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn page_route_is_limited_to_opened_archives() {
+        let dir = tempfile::tempdir().unwrap();
+        let opened = dir.path().join("opened");
+        let other = dir.path().join("other");
+        for folder in [&opened, &other] {
+            std::fs::create_dir(folder).unwrap();
+            std::fs::write(folder.join("001.png"), b"fake-png").unwrap();
+        }
+        let scope = FileScope::new(PathBuf::from("/nonexistent"));
+        scope.allow_archive(opened.to_str().unwrap());
+
+        let url = |folder: &Path| {
+            format!(
+                "/page/{}/001.png",
+                encode_uri_component(folder.to_str().unwrap())
+            )
+        };
+        assert_eq!(get_in(&scope, &url(&opened), None).status(), StatusCode::OK);
+        assert_eq!(
+            get_in(&scope, &url(&other), None).status(),
+            StatusCode::FORBIDDEN
+        );
+        // Nothing is served before an archive is opened
+        assert_eq!(get(&url(&opened), None).status(), StatusCode::FORBIDDEN);
+    }
+}
@@ -0,0 +1,170 @@
+//! アーカイブの表紙・ページのサムネイル生成。
+//! 画像をデコードして縮小し、小さな JPEG の data URL として返す。
+
+use std::io::Cursor;
+
+use base64::Engine;
+use image::{DynamicImage, ImageFormat};
+
+use crate::archive::{self, ArchiveCache};
+use crate::error::MekuriError;
+use crate::thumbnail_cache::ThumbnailCache;
+
+/// 縮小後の長辺の上限（極端な値で巨大な画像を返さないため）
+const MAX_THUMBNAIL_SIZE: u32 = 1024;
+
+/// アーカイブの表紙（自然順で先頭の画像）のサムネイルを返す。
+/// 一度生成したものは `thumbnails` に保存し、次からはアーカイブを読まずに返す。
+/// 表紙はファイル一覧で大量に読むため、閲覧中のページを追い出さないようページキャッシュには入れない。
+pub fn archive_thumbnail(
+    cache: &ArchiveCache,
+    thumbnails: &ThumbnailCache,
+    archive_path: &str,
+    max_size: u32,
+) -> Result<String, MekuriError> {
+    let jpeg = thumbnails.get_or_create(archive_path, max_size, || {
+        let names = archive::list_images(cache, archive_path)?;
+        let cover = names.first().ok_or_else(|| {
+            MekuriError::NotFound(format!("No images found in archive: {archive_path}"))
+        })?;
+        let data = archive::read_entry_uncached(cache, archive_path, cover)?;
+        encode_thumbnail(&data, max_size)
+    })?;
+    Ok(to_data_url(&jpeg))
+}
+
+/// アーカイブ内の任意のエントリのサムネイルを生成する。
+pub fn page_thumbnail(
+    cache: &ArchiveCache,
+    archive_path: &str,
+    entry_name: &str,
+    max_size: u32,
+) -> Result<String, MekuriError> {
+    let data = archive::read_entry(cache, archive_path, entry_name)?;
+    let jpeg = encode_thumbnail(&data, max_size)?;
+    Ok(to_data_url(&jpeg))
+}
+
+fn to_data_url(jpeg: &[u8]) -> String {
+    let b64 = base64::engine::general_purpose::STANDARD.encode(jpeg);
+    format!("data:image/jpeg;base64,{b64}")
+}
+
+/// 画像をデコードし、縦横比を保って長辺 `max_size` 以内に縮小した JPEG を返す。
+/// 元画像がそれより小さい場合は拡大しない。
+fn encode_thumbnail(data: &[u8], max_size: u32) -> Result<Vec<u8>, MekuriError> {
+    let max_size = max_size.clamp(1, MAX_THUMBNAIL_SIZE);
+    let image =
+        image::load_from_memory(data).map_err(|e| image_error("Failed to decode image", e))?;
+
+    let image = if image.width() > max_size || image.height() > max_size {
+        image.thumbnail(max_size, max_size)
+    } else {
+        image
+    };
+
+    // JPEG はアルファを持てないので RGB に落とす
+    let rgb = DynamicImage::ImageRgb8(image.to_rgb8());
+    let mut buf = Cursor::new(Vec::new());
+    rgb.write_to(&mut buf, ImageFormat::Jpeg)
+        .map_err(|e| image_error("Failed to encode thumbnail", e))?;
+    Ok(buf.into_inner())
+}
+
+/// 画像処理のエラーを種類に応じて振り分ける。
+fn image_error(context: &str, err: image::ImageError) -> MekuriError {
+    let message = format!("{context}: {err}");
+    match err {
+        image::ImageError::IoError(e) => MekuriError::io(context, e),
+        image::ImageError::Unsupported(_) => MekuriError::UnsupportedFormat(message),
+        image::ImageError::Decoding(_) => MekuriError::Corrupt(message),
+        _ => MekuriError::Io(message),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{GenericImageView, RgbaImage};
+    use std::io::Write;
+
+    fn png_bytes(width: u32, height: u32) -> Vec<u8> {
+        let image = RgbaImage::from_pixel(width, height, image::Rgba([200, 30, 30, 128]));
+        let mut buf = Cursor::new(Vec::new());
+        image.write_to(&mut buf, ImageFormat::Png).unwrap();
+        buf.into_inner()
+    }
+
+    fn decode_data_url(url: &str) -> DynamicImage {
+        let b64 = url.strip_prefix("data:image/jpeg;base64,").unwrap();
+        let jpeg = base64::engine::general_purpose::STANDARD
+            .decode(b64)
+            .unwrap();
+        image::load_from_memory_with_format(&jpeg, ImageFormat::Jpeg).unwrap()
+    }
+
+    fn create_test_zip(entries: &[(&str, &[u8])]) -> tempfile::NamedTempFile {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let mut writer = zip::ZipWriter::new(std::io::BufWriter::new(file.as_file()));
+        let options = zip::write::SimpleFileOptions::default();
+        for (name, data) in entries {
+            writer.start_file(*name, options).unwrap();
+            writer.write_all(data).unwrap();
+        }
+        writer.finish().unwrap();
+        file
+    }
+
+    #[test]
+    fn encode_thumbnail_keeps_aspect_ratio() {
+        let jpeg = encode_thumbnail(&png_bytes(400, 200), 100).unwrap();
+        let image = image::load_from_memory_with_format(&jpeg, ImageFormat::Jpeg).unwrap();
+        assert_eq!(image.dimensions(), (100, 50));
+    }
+
+    #[test]
+    fn encode_thumbnail_does_not_upscale() {
+        let jpeg = encode_thumbnail(&png_bytes(40, 20), 100).unwrap();
+        let image = image::load_from_memory_with_format(&jpeg, ImageFormat::Jpeg).unwrap();
+        assert_eq!(image.dimensions(), (40, 20));
+    }
+
+    #[test]
+    fn encode_thumbnail_rejects_non_image() {
+        let err = encode_thumbnail(b"not an image", 100).unwrap_err();
+        // 形式を判別できないバイト列
+        assert!(matches!(err, MekuriError::UnsupportedFormat(_)));
+        assert!(err.message().contains("Failed to decode image"));
+    }
+
+    #[test]
+    fn archive_thumbnail_uses_first_image() {
+        let cover = png_bytes(300, 600);
+        let page = png_bytes(600, 300);
+        let zip_file = create_test_zip(&[("02.png", &page), ("01.png", &cover)]);
+        let path = zip_file.path().to_str().unwrap();
+        let cache = ArchiveCache::default();
+        let cache_dir = tempfile::tempdir().unwrap();
+        let thumbnails = ThumbnailCache::new(cache_dir.path().to_path_buf());
+
+        let thumb = decode_data_url(&archive_thumbnail(&cache, &thumbnails, path, 60).unwrap());
+        assert_eq!(thumb.dimensions(), (30, 60));
+        assert!(thumbnails.cached_path(path, 60).is_some());
+
+        let thumb = decode_data_url(&page_thumbnail(&cache, path, "02.png", 60).unwrap());
+        assert_eq!(thumb.dimensions(), (60, 30));
+    }
+
+    #[test]
+    fn archive_thumbnail_without_images() {
+        let zip_file = create_test_zip(&[("readme.txt", b"text")]);
+        let cache_dir = tempfile::tempdir().unwrap();
+        let result = archive_thumbnail(
+            &ArchiveCache::default(),
+            &ThumbnailCache::new(cache_dir.path().to_path_buf()),
+            zip_file.path().to_str().unwrap(),
+            60,
+        );
+        assert!(matches!(result, Err(MekuriError::NotFound(_))));
+    }
+}
@@ -0,0 +1,282 @@
+//! サムネイルのディスクキャッシュ。
+//! 生成した表紙を app cache dir 配下に JPEG として保存し、同じフォルダを開き直したときに
+//! アーカイブを読まずに表示できるようにする。
+
+use crate::error::MekuriError;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// キャッシュ全体のサイズ上限
+const DEFAULT_MAX_BYTES: u64 = 256 * 1024 * 1024;
+
+/// ファイルの正規化パス・サイズ・更新日時とサムネイルの大きさをキーにしたキャッシュ。
+/// 元ファイルが書き換わるとキーが変わるので、古いサムネイルは使われずにいずれ追い出される。
+pub struct ThumbnailCache {
+    dir: PathBuf,
+    max_bytes: u64,
+    /// 保存済みファイルの合計サイズ。最初の書き込み時にディレクトリを走査して求める
+    total_bytes: Mutex<Option<u64>>,
+}
+
+impl ThumbnailCache {
+    pub fn new(dir: PathBuf) -> Self {
+        Self::with_limit(dir, DEFAULT_MAX_BYTES)
+    }
+
+    /// 合計 `max_bytes` を超えたら、最後に使われたのが古いものから削除するキャッシュを作る。
+    pub fn with_limit(dir: PathBuf, max_bytes: u64) -> Self {
+        Self {
+            dir,
+            max_bytes,
+            total_bytes: Mutex::new(None),
+        }
+    }
+
+    /// 生成済みのサムネイルがあればそのファイルのパスを返す。生成はしない。
+    pub fn cached_path(&self, file_path: &str, max_size: u32) -> Option<PathBuf> {
+        let path = self.entry_path(file_path, max_size)?;
+        path.is_file().then_some(path)
+    }
+
+    /// キャッシュにあればそれを返し、なければ `generate` で生成して保存する。
+    pub fn get_or_create<F>(
+        &self,
+        file_path: &str,
+        max_size: u32,
+        generate: F,
+    ) -> Result<Vec<u8>, MekuriError>
+    where
+        F: FnOnce() -> Result<Vec<u8>, MekuriError>,
+    {
+        let Some(path) = self.entry_path(file_path, max_size) else {
+            // 元ファイルを stat できない。エラーは生成側に任せる
+            return generate();
+        };
+
+        if let Ok(data) = fs::read(&path) {
+            // 更新日時を最終使用日時として扱い、よく見るものを追い出さないようにする
+            let _ = fs::File::options()
+                .write(true)
+                .open(&path)
+                .and_then(|file| file.set_modified(SystemTime::now()));
+            return Ok(data);
+        }
+
+        let data = generate()?;
+        // 保存できなくてもサムネイル自体は返す
+        let _ = self.store(&path, &data);
+        Ok(data)
+    }
+
+    /// 保存済みのサムネイルをすべて削除する。
+    pub fn clear(&self) -> Result<(), MekuriError> {
+        let mut total = self
+            .total_bytes
+            .lock()
+            .map_err(|e| MekuriError::lock("Thumbnail cache is unavailable", e))?;
+        match fs::remove_dir_all(&self.dir) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(MekuriError::io("Failed to clear thumbnail cache", e)),
+        }
+        *total = Some(0);
+        Ok(())
+    }
+
+    fn entry_path(&self, file_path: &str, max_size: u32) -> Option<PathBuf> {
+        let canonical = fs::canonicalize(file_path).ok()?;
+        let metadata = fs::metadata(&canonical).ok()?;
+        let modified = metadata
+            .modified()
+            .ok()?
+            .duration_since(UNIX_EPOCH)
+            .ok()?
+            .as_nanos();
+        let key = format!(
+            "{}\0{}\0{modified}\0{max_size}",
+            canonical.to_string_lossy(),
+            metadata.len()
+        );
+        Some(self.dir.join(format!("{:016x}.jpg", fnv1a(key.as_bytes()))))
+    }
+
+    fn store(&self, path: &Path, data: &[u8]) -> std::io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        // 書きかけのファイルを読まれないよう、一時ファイルに書いてから置き換える
+        let mut file = tempfile::NamedTempFile::new_in(&self.dir)?;
+        file.write_all(data)?;
+        file.persist(path).map_err(|e| e.error)?;
+
+        let Ok(mut total) = self.total_bytes.lock() else {
+            return Ok(());
+        };
+        let current = match *total {
+            Some(bytes) => bytes + data.len() as u64,
+            None => cache_entries(&self.dir).iter().map(|entry| entry.len).sum(),
+        };
+        *total = Some(if current > self.max_bytes {
+            // 書き込みのたびに走査しないよう、上限の 3/4 まで減らす
+            evict(&self.dir, self.max_bytes / 4 * 3)
+        } else {
+            current
+        });
+        Ok(())
+    }
+}
+
+struct CacheEntry {
+    path: PathBuf,
+    len: u64,
+    modified: SystemTime,
+}
+
+fn cache_entries(dir: &Path) -> Vec<CacheEntry> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .filter_map(|entry| {
+            let metadata = entry.metadata().ok()?;
+            metadata.is_file().then(|| CacheEntry {
+                path: entry.path(),
+                len: metadata.len(),
+                modified: metadata.modified().unwrap_or(UNIX_EPOCH),
+            })
+        })
+        .collect()
+}
+
+/// 最終使用日時が古いものから削除して合計 `target` バイト以下にし、残った合計を返す。
+fn evict(dir: &Path, target: u64) -> u64 {
+    let mut entries = cache_entries(dir);
+    entries.sort_by_key(|entry| entry.modified);
+    let mut total: u64 = entries.iter().map(|entry| entry.len).sum();
+    for entry in entries {
+        if total <= target {
+            break;
+        }
+        if fs::remove_file(&entry.path).is_ok() {
+            total -= entry.len;
+        }
+    }
+    total
+}
+
+/// FNV-1a。Rust のバージョンが変わってもファイル名が変わらないよう、
+/// `DefaultHasher` ではなく固定のアルゴリズムを使う。
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::time::Duration;
+
+    fn source_file(dir: &tempfile::TempDir, name: &str) -> String {
+        let path = dir.path().join(name);
+        fs::write(&path, b"archive").unwrap();
+        path.to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn test_generates_once_and_reuses() {
+        let source_dir = tempfile::tempdir().unwrap();
+        let cache_dir = tempfile::tempdir().unwrap();
+        let path = source_file(&source_dir, "a.zip");
+        let cache = ThumbnailCache::new(cache_dir.path().join("thumbnails"));
+        let calls = Cell::new(0);
+        let generate = || {
+            calls.set(calls.get() + 1);
+            Ok(b"jpeg".to_vec())
+        };
+
+        assert!(cache.cached_path(&path, 128).is_none());
+        assert_eq!(cache.get_or_create(&path, 128, generate).unwrap(), b"jpeg");
+        assert_eq!(cache.get_or_create(&path, 128, generate).unwrap(), b"jpeg");
+        assert_eq!(calls.get(), 1);
+
+        let cached = cache.cached_path(&path, 128).unwrap();
+        assert_eq!(fs::read(cached).unwrap(), b"jpeg");
+        // 大きさが違えば別のサムネイル
+        assert!(cache.cached_path(&path, 256).is_none());
+    }
+
+    #[test]
+    fn test_key_changes_when_file_changes() {
+        let source_dir = tempfile::tempdir().unwrap();
+        let cache_dir = tempfile::tempdir().unwrap();
+        let path = source_file(&source_dir, "a.zip");
+        let cache = ThumbnailCache::new(cache_dir.path().to_path_buf());
+
+        cache
+            .get_or_create(&path, 128, || Ok(b"old".to_vec()))
+            .unwrap();
+        let file = fs::File::options().write(true).open(&path).unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(60))
+            .unwrap();
+
+        assert!(cache.cached_path(&path, 128).is_none());
+        let data = cache.get_or_create(&path, 128, || Ok(b"new".to_vec()));
+        assert_eq!(data.unwrap(), b"new");
+    }
+
+    #[test]
+    fn test_generate_error_is_not_cached() {
+        let source_dir = tempfile::tempdir().unwrap();
+        let cache_dir = tempfile::tempdir().unwrap();
+        let path = source_file(&source_dir, "a.zip");
+        let cache = ThumbnailCache::new(cache_dir.path().to_path_buf());
+
+        let error = MekuriError::NotFound("no images".to_string());
+        let result = cache.get_or_create(&path, 128, || Err(error.clone()));
+        assert_eq!(result.unwrap_err(), error);
+        assert!(cache.cached_path(&path, 128).is_none());
+    }
+
+    #[test]
+    fn test_evicts_least_recently_used() {
+        let source_dir = tempfile::tempdir().unwrap();
+        let cache_dir = tempfile::tempdir().unwrap();
+        let [a, b, c] = ["a.zip", "b.zip", "c.zip"].map(|name| source_file(&source_dir, name));
+        let cache = ThumbnailCache::with_limit(cache_dir.path().to_path_buf(), 28);
+        let thumbnail = || Ok(vec![0; 10]);
+
+        cache.get_or_create(&a, 128, thumbnail).unwrap();
+        cache.get_or_create(&b, 128, thumbnail).unwrap();
+        // `b` を最も古く使われたものにする
+        let a_path = cache.cached_path(&a, 128).unwrap();
+        let b_path = cache.cached_path(&b, 128).unwrap();
+        let past = SystemTime::now() - Duration::from_secs(60);
+        let file = fs::File::options().write(true).open(&b_path).unwrap();
+        file.set_modified(past).unwrap();
+        cache.get_or_create(&c, 128, thumbnail).unwrap();
+
+        assert!(cache.cached_path(&b, 128).is_none());
+        assert!(a_path.is_file());
+        assert!(cache.cached_path(&c, 128).is_some());
+    }
+
+    #[test]
+    fn test_clear_removes_everything() {
+        let source_dir = tempfile::tempdir().unwrap();
+        let cache_dir = tempfile::tempdir().unwrap();
+        let path = source_file(&source_dir, "a.zip");
+        let cache = ThumbnailCache::new(cache_dir.path().join("thumbnails"));
+
+        // 一度も保存していなくても成功する
+        cache.clear().unwrap();
+        cache
+            .get_or_create(&path, 128, || Ok(b"jpeg".to_vec()))
+            .unwrap();
+        cache.clear().unwrap();
+        assert!(cache.cached_path(&path, 128).is_none());
+    }
+}
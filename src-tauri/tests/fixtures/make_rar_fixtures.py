@@ -0,0 +1,136 @@
+"""Write the RAR archives the tests read, as RAR5 archives of stored (uncompressed) files:
+
+- encrypted-headers.rar: one page, 001.jpg, with headers encrypted with "secret"
+- mixed-content.rar: a cover, cover.jpg, next to a nested archive, vol1.cbz
+- pages.rar and solid-pages.rar: 001.jpg of 300x200 pixels and 002.jpg of 200x300, the
+  second archive flagged solid
+
+No tool that writes RAR archives is assumed to be installed, so the archives are put
+together by hand following the RAR5 format. Needs the `cryptography` package.
+"""
+
+import hashlib
+import os
+import struct
+import zlib
+
+from cryptography.hazmat.primitives.ciphers import Cipher, algorithms, modes
+
+LG2_COUNT = 15
+SALT = bytes(range(16))
+JPEG = b"\xff\xd8\xff\xe0fake-jpg"
+
+
+def jpeg(width, height):
+    """The start of a baseline JPEG, up to its frame header."""
+    return (
+        b"\xff\xd8\xff\xc0\x00\x11\x08"
+        + struct.pack(">HH", height, width)
+        + b"\x03\x01\x22\x00\x02\x11\x01\x03\x11\x01\xff\xd9"
+    )
+
+
+def vint(value):
+    out = bytearray()
+    while True:
+        byte = value & 0x7F
+        value >>= 7
+        out.append(byte | (0x80 if value else 0))
+        if not value:
+            return bytes(out)
+
+
+def block(header_type, fields, flags=0, data_size=None):
+    body = vint(header_type) + vint(flags)
+    if data_size is not None:
+        body += vint(data_size)
+    body += fields
+    sized = vint(len(body)) + body
+    return struct.pack("<I", zlib.crc32(sized)) + sized
+
+
+def file_header(name, data):
+    name = name.encode()
+    return block(
+        2,
+        vint(0x0004)  # data CRC32 present
+        + vint(len(data))
+        + vint(0x20)  # archive attribute
+        + struct.pack("<I", zlib.crc32(data))
+        + vint(0)  # stored
+        + vint(0)  # Windows
+        + vint(len(name))
+        + name,
+        flags=0x0002,
+        data_size=len(data),
+    )
+
+
+def pbkdf2(password, iterations):
+    return hashlib.pbkdf2_hmac("sha256", password.encode(), SALT, iterations, 32)
+
+
+def header_encryption(password):
+    """The archive encryption header and a function encrypting each header after it."""
+    count = 1 << LG2_COUNT
+    key = pbkdf2(password, count)
+    check_value = pbkdf2(password, count + 32)
+    check = bytearray(8)
+    for i, byte in enumerate(check_value):
+        check[i % 8] ^= byte
+    check = bytes(check)
+
+    crypt = block(
+        4,
+        vint(0)  # AES-256
+        + vint(0x0001)  # password check data present
+        + bytes([LG2_COUNT])
+        + SALT
+        + check
+        + hashlib.sha256(check).digest()[:4],
+    )
+    ivs = iter(range(1, 256))
+
+    def encrypt(header):
+        # Fixed IVs keep the output the same from run to run
+        iv = bytes([next(ivs)] * 16)
+        padded = header + bytes(-len(header) % 16)
+        encryptor = Cipher(algorithms.AES(key), modes.CBC(iv)).encryptor()
+        return iv + encryptor.update(padded) + encryptor.finalize()
+
+    return crypt, encrypt
+
+
+def plain(header):
+    return header
+
+
+def write_archive(name, files, password=None, solid=False):
+    archive = b"Rar!\x1a\x07\x01\x00"
+    encrypt = plain
+    if password is not None:
+        crypt, encrypt = header_encryption(password)
+        archive += crypt
+
+    archive += encrypt(block(1, vint(0x0004 if solid else 0)))
+    for file_name, data in files:
+        archive += encrypt(file_header(file_name, data)) + data
+    archive += encrypt(block(5, vint(0)))
+
+    path = os.path.join(os.path.dirname(os.path.abspath(__file__)), name)
+    with open(path, "wb") as f:
+        f.write(archive)
+
+
+def main():
+    write_archive("encrypted-headers.rar", [("001.jpg", JPEG)], password="secret")
+    # An empty ZIP archive is enough for a nested archive that is only listed
+    empty_zip = b"PK\x05\x06" + bytes(18)
+    write_archive("mixed-content.rar", [("cover.jpg", JPEG), ("vol1.cbz", empty_zip)])
+    pages = [("001.jpg", jpeg(300, 200)), ("002.jpg", jpeg(200, 300))]
+    write_archive("pages.rar", pages)
+    write_archive("solid-pages.rar", pages, solid=True)
+
+
+if __name__ == "__main__":
+    main()
@@ -1,20 +1,40 @@
 import { getCurrentWindow } from "@tauri-apps/api/window";
 import { ask } from "@tauri-apps/plugin-dialog";
-import { useCallback, useEffect, useRef, useState } from "react";
-import { getArchiveImage } from "./api/archive";
-import { getSiblingArchives, moveFile, trashFile } from "./api/directory";
+import { useCallback, useEffect, useMemo, useRef, useState } from "react";
+import {
+  type ContentTree,
+  cancelPrefetch,
+  firstVolume,
+  getPageThumbnail,
+  prefetchPages,
+  type SortMode,
+  type TocEntry,
+} from "./api/archive";
+import { getFileKind, getSiblingArchives, moveFile, trashFile } from "./api/directory";
+import { archivePageUrl } from "./api/protocol";
 import { saveViewerSettings } from "./api/settings";
+import { PasswordPrompt } from "./components/PasswordPrompt/PasswordPrompt";
 import { SpreadViewer, type SpreadViewerHandle } from "./components/SpreadViewer/SpreadViewer";
 import { SubfolderPanel } from "./components/SubfolderPanel/SubfolderPanel";
 import { useArchiveLoader } from "./hooks/useArchiveLoader";
+import { useBulkExtraction } from "./hooks/useBulkExtraction";
 import { usePdfLoader } from "./hooks/usePdfLoader";
 import { useSiblingNavigation } from "./hooks/useSiblingNavigation";
 import { useWindowResize } from "./hooks/useWindowResize";
+import type { FileKind } from "./types";
+import { readingDirectionFromMetadata, soloPageIndices } from "./utils/comicInfo";
 import { errorToString } from "./utils/errorToString";
-import { detectFileType } from "./utils/fileType";
 import type { ReadingDirection } from "./utils/spreadLayout";
 import { fileNameFromPath } from "./utils/windowLabel";
 
+/** Longest side of the progress bar page previews, sized for high-DPI screens. */
+const PREVIEW_THUMBNAIL_SIZE = 240;
+
+/** Number of images an analyzed archive holds itself, nested archives aside. */
+function pageCount(node: ContentTree): number {
+  return node.image_groups.reduce((count, group) => count + group.names.length, 0);
+}
+
 function Viewer() {
   const [archivePath, setArchivePath] = useState<string | null>(null);
   const [trashError, setTrashError] = useState<string | null>(null);
@@ -23,17 +43,38 @@ function Viewer() {
   const [resumePage, setResumePage] = useState(0);
   const spreadViewerRef = useRef<SpreadViewerHandle>(null);
 
-  // Read archive path from URL query parameter
+  // Read archive path from URL query parameter. A later volume of a multi-volume
+  // archive opens the whole set from its first volume.
   useEffect(() => {
     const params = new URLSearchParams(window.location.search);
     const path = params.get("archive");
     if (path) {
-      setArchivePath(path);
+      firstVolume(path)
+        .then(setArchivePath)
+        .catch(() => setArchivePath(path));
     }
   }, []);
 
-  const fileType = archivePath ? detectFileType(archivePath) : "unknown";
-  const isPdf = fileType === "pdf";
+  // Whether the path is a PDF is the backend's call, by the same rules as the file list
+  // (a PDF named .zip is still a PDF). Neither loader runs until it is known.
+  const [detected, setDetected] = useState<{ path: string; kind: FileKind } | null>(null);
+  useEffect(() => {
+    if (!archivePath) return;
+    const path = archivePath;
+    let cancelled = false;
+    getFileKind(path)
+      .catch((): FileKind => "Archive")
+      .then((kind) => {
+        if (!cancelled) {
+          setDetected({ path, kind });
+        }
+      });
+    return () => {
+      cancelled = true;
+    };
+  }, [archivePath]);
+  const fileKind = detected?.path === archivePath ? detected.kind : null;
+  const isPdf = fileKind === "Pdf";
 
   const handleWindowResize = useCallback(async (size: { width: number; height: number }) => {
     await saveViewerSettings(size);
@@ -50,6 +91,8 @@ function Viewer() {
   // Native OS context menu with "Move to Trash"
   const archivePathRef = useRef(archivePath);
   archivePathRef.current = archivePath;
+  // メニューから並び順を読み書きするため、アーカイブ読み込み後に最新の状態を入れる
+  const archiveRef = useRef<ReturnType<typeof useArchiveLoader> | null>(null);
 
   const handleTrash = useCallback(async () => {
     const currentPath = archivePathRef.current;
@@ -125,10 +168,11 @@ function Viewer() {
       const currentPath = archivePathRef.current;
       if (!currentPath) return;
 
-      const { CheckMenuItem, Menu, MenuItem, PredefinedMenuItem } = await import(
+      const { CheckMenuItem, Menu, MenuItem, PredefinedMenuItem, Submenu } = await import(
         "@tauri-apps/api/menu"
       );
       const handle = spreadViewerRef.current;
+      const loader = archiveRef.current;
 
       const modeEntries = [
         ["single", "単ページ表示"],
@@ -152,6 +196,58 @@ function Viewer() {
         action: () => handle?.toggleReadingDirection(),
       });
 
+      // 画像を表示中のアーカイブだけ並び順を選べる（PDF・ネスト選択画面では出さない）
+      const sortEntries: [SortMode, string][] = [
+        ["Natural", "自然順"],
+        ["Lexicographic", "文字列順"],
+        ["DirectoryThenName", "フォルダ→ファイル名順"],
+        ["ArchiveOrder", "格納順"],
+        ["ModifiedTime", "更新日時順"],
+      ];
+      const sortItems = loader?.effectivePath
+        ? [
+            await Submenu.new({
+              text: "ページの並び順",
+              items: await Promise.all(
+                sortEntries.map(([mode, text]) =>
+                  CheckMenuItem.new({
+                    text,
+                    checked: loader.sortMode === mode,
+                    action: () => loader.changeSortMode(mode),
+                  }),
+                ),
+              ),
+            }),
+          ]
+        : [];
+
+      // 目次からフォルダ（章）の先頭ページへ飛ぶ。全体を包むだけのフォルダは開いて中身を並べる
+      let toc = loader?.effectivePath ? loader.tableOfContents : [];
+      while (toc.length === 1 && toc[0].children.length > 0) {
+        toc = toc[0].children;
+      }
+      async function tocItem(
+        entry: TocEntry,
+      ): Promise<InstanceType<typeof MenuItem> | InstanceType<typeof Submenu>> {
+        const text = `${entry.name} (${entry.page_count})`;
+        const jump = () => handle?.goToPage(entry.first_page);
+        if (entry.children.length === 0) {
+          return MenuItem.new({ text, action: jump });
+        }
+        return Submenu.new({
+          text,
+          items: [
+            await MenuItem.new({ text: "先頭へ", action: jump }),
+            await PredefinedMenuItem.new({ item: "Separator" }),
+            ...(await Promise.all(entry.children.map(tocItem))),
+          ],
+        });
+      }
+      const tocItems =
+        toc.length > 1
+          ? [await Submenu.new({ text: "目次", items: await Promise.all(toc.map(tocItem)) })]
+          : [];
+
       const separator0 = await PredefinedMenuItem.new({ item: "Separator" });
       const separator1 = await PredefinedMenuItem.new({ item: "Separator" });
       const separator2 = await PredefinedMenuItem.new({ item: "Separator" });
@@ -171,6 +267,8 @@ function Viewer() {
           ...modeItems,
           separator0,
           directionItem,
+          ...sortItems,
+          ...tocItems,
           separator1,
           trashItem,
           separator2,
@@ -185,7 +283,8 @@ function Viewer() {
   }, [handleTrash]);
 
   // Archive loader (only active for archive files)
-  const archive = useArchiveLoader(isPdf ? null : archivePath);
+  const archive = useArchiveLoader(fileKind && !isPdf ? archivePath : null);
+  archiveRef.current = isPdf ? null : archive;
 
   // Nested archive transitions remount SpreadViewer without changing archivePath,
   // so resumePage must be reset explicitly here (navigateToArchive doesn't run).
@@ -203,11 +302,57 @@ function Viewer() {
   const getArchivePageDataUrl = useCallback(
     async (pageIndex: number): Promise<string> => {
       if (!archiveEffectivePath) throw new Error("No archive path");
-      return getArchiveImage(archiveEffectivePath, archiveImageNames[pageIndex]);
+      return archivePageUrl(archiveEffectivePath, archiveImageNames[pageIndex]);
+    },
+    [archiveEffectivePath, archiveImageNames],
+  );
+
+  const getArchivePageThumbnail = useCallback(
+    async (pageIndex: number): Promise<string> => {
+      if (!archiveEffectivePath) throw new Error("No archive path");
+      return getPageThumbnail(
+        archiveEffectivePath,
+        archiveImageNames[pageIndex],
+        PREVIEW_THUMBNAIL_SIZE,
+      );
+    },
+    [archiveEffectivePath, archiveImageNames],
+  );
+
+  const handlePrefetch = useCallback(
+    (pageIndices: number[]) => {
+      if (!archiveEffectivePath) return;
+      const entries = pageIndices.map((i) => archiveImageNames[i]).filter(Boolean);
+      // The focused window's pages go ahead of background viewers
+      const priority = document.hasFocus() ? 1 : 0;
+      prefetchPages(archiveEffectivePath, entries, priority).catch((err) => {
+        console.error("Failed to prefetch pages:", err);
+      });
     },
     [archiveEffectivePath, archiveImageNames],
   );
 
+  const extraction = useBulkExtraction(isPdf ? null : archive.effectivePath);
+
+  // Covers and two-page spreads (marked in ComicInfo.xml or stored as landscape images) get
+  // a spread of their own
+  const archivePages = archive.pages;
+  const archivePageInfo = archive.pageInfo;
+  const soloPages = useMemo(
+    () => soloPageIndices(archivePages, archivePageInfo),
+    [archivePages, archivePageInfo],
+  );
+
+  // Drop pending prefetches when leaving an archive
+  useEffect(() => {
+    if (!archiveEffectivePath || isPdf) return;
+    return () => {
+      cancelPrefetch(archiveEffectivePath).catch((err) => {
+        console.error("Failed to cancel prefetch:", err);
+      });
+    };
+  }, [archiveEffectivePath, isPdf]);
+
   // Unified props
   const pageCount = isPdf ? pdf.pageCount : archive.imageNames.length;
   const pageNames = isPdf ? pdf.pageNames : archive.imageNames;
@@ -225,6 +370,10 @@ function Viewer() {
     [archivePath],
   );
 
+  if (!isPdf && archive.locked && !loading) {
+    return <PasswordPrompt error={archive.error} onSubmit={archive.unlock} />;
+  }
+
   if (error || trashError) {
     return (
       <div className="viewer viewer--error">
@@ -234,7 +383,7 @@ function Viewer() {
     );
   }
 
-  if (!archivePath || loading) {
+  if (!archivePath || !fileKind || loading) {
     return (
       <div className="viewer viewer--loading">
         <p>Loading...</p>
@@ -248,29 +397,60 @@ function Viewer() {
       <div className="viewer viewer--nested">
         <div className="nested-selector">
           <h2 className="nested-selector__title">Select Archive</h2>
-          <p className="nested-selector__desc">This archive contains multiple archives:</p>
+          <p className="nested-selector__desc">
+            {archive.nestedPages
+              ? "This archive contains pages and archives:"
+              : "This archive contains multiple archives:"}
+          </p>
           <ul className="nested-selector__list">
-            {archive.nestedArchives.map((name) => (
-              <li key={name}>
+            {archive.nestedPages && (
+              <li>
+                <button
+                  type="button"
+                  className="nested-selector__item nested-selector__item--pages"
+                  onClick={() => {
+                    if (!archive.nestedPages) return;
+                    setResumePage(0);
+                    archive.selectNestedArchive(archive.nestedPages);
+                  }}
+                >
+                  Pages in this archive ({pageCount(archive.nestedPages)})
+                </button>
+              </li>
+            )}
+            {archive.nestedArchives.map((nested) => (
+              <li key={nested.path}>
                 <button
                   type="button"
                   className="nested-selector__item"
                   onClick={() => {
                     setResumePage(0);
-                    archive.selectNestedArchive(name);
+                    archive.selectNestedArchive(nested);
                   }}
                 >
-                  {name.split("/").pop() || name}
+                  {nested.name.split("/").pop() || nested.name}
                 </button>
               </li>
             ))}
           </ul>
+          {archive.nestedDepth > 1 && (
+            <button
+              type="button"
+              className="nested-selector__back"
+              onClick={archive.backToNestedList}
+            >
+              Back
+            </button>
+          )}
         </div>
       </div>
     );
   }
 
   const defaultReadingDirection: ReadingDirection = isPdf ? "ltr" : "rtl";
+  const preferredReadingDirection = isPdf
+    ? undefined
+    : (readingDirectionFromMetadata(archive.metadata) ?? undefined);
 
   // Moving the outer archive of a nested container remounts through the nested
   // selector, dropping page position and selection — disable move in that flow.
@@ -288,6 +468,11 @@ function Viewer() {
 
   return (
     <div className="viewer">
+      {extraction && (
+        <div className="viewer__extract-progress" role="status">
+          Extracting pages {extraction.extracted}/{extraction.total}
+        </div>
+      )}
       {moveError && (
         <div className="viewer__move-error" role="alert">
           <span>Failed to move file: {moveError}</span>
@@ -302,9 +487,13 @@ function Viewer() {
         pageCount={pageCount}
         pageNames={pageNames}
         getPageDataUrl={getPageDataUrl}
+        getPageThumbnail={isPdf ? undefined : getArchivePageThumbnail}
+        onPrefetch={isPdf ? undefined : handlePrefetch}
+        soloPages={isPdf ? undefined : soloPages}
         onSpreadChange={handleSpreadChange}
         onBack={!isPdf && archive.hasNestedCache ? handleBackToNestedList : undefined}
         defaultReadingDirection={defaultReadingDirection}
+        preferredReadingDirection={preferredReadingDirection}
         initialPage={resumePage}
         movePanel={
           canMoveFile
@@ -1,35 +1,289 @@
 import { invoke } from "@tauri-apps/api/core";
+import type { MekuriError } from "../types";
 
-export type ArchiveContents =
-  | { type: "Images"; names: string[] }
-  | { type: "NestedArchives"; names: string[] }
-  | { type: "Empty" };
+/** The images in one folder of an archive. */
+export type ImageGroup = {
+  /** Folder path inside the archive; empty for its root */
+  folder: string;
+  names: string[];
+};
 
-export async function listArchiveImages(archivePath: string): Promise<string[]> {
+/**
+ * What an archive holds: its images by folder, and the archives nested in it. Nested
+ * archives are only listed, with `analyzed` false, until analyzed by their own path.
+ */
+export type ContentTree = {
+  /** Entry name in the parent archive; the file name for an archive on disk */
+  name: string;
+  /**
+   * Virtual path such as `outer.zip!/inner.cbz`, accepted by every command that takes an
+   * archive path
+   */
+  path: string;
+  /** Whether `image_groups` and `nested` were read */
+  analyzed: boolean;
+  image_groups: ImageGroup[];
+  nested: ContentTree[];
+};
+
+/** ComicInfo `Manga` field. */
+export type Manga = "Unknown" | "No" | "Yes" | "YesAndRightToLeft";
+
+/** One `<Page>` element of ComicInfo `<Pages>`. */
+export type PageMetadata = {
+  /** Index of the page among the archive's images */
+  image: number;
+  /** ComicInfo page type such as "FrontCover", "Story" or "Deleted" */
+  page_type: string | null;
+  double_page: boolean;
+  image_width: number | null;
+  image_height: number | null;
+};
+
+/** Metadata read from an archive's ComicInfo.xml. */
+export type ArchiveMetadata = {
+  title: string | null;
+  series: string | null;
+  number: string | null;
+  volume: number | null;
+  count: number | null;
+  summary: string | null;
+  year: number | null;
+  month: number | null;
+  day: number | null;
+  writer: string | null;
+  penciller: string | null;
+  publisher: string | null;
+  genre: string | null;
+  tags: string | null;
+  language_iso: string | null;
+  page_count: number | null;
+  manga: Manga;
+  pages: PageMetadata[];
+};
+
+/**
+ * An archive entry by its position in the archive's entry table as well as its name, for
+ * names that repeat or do not survive a round trip. Pass it back unchanged.
+ */
+export type EntryId = {
+  index: number;
+  name: string;
+};
+
+/**
+ * How the images of an archive are ordered. Natural sorts full paths so that `2.jpg` comes
+ * before `10.jpg`; DirectoryThenName groups the images by folder; ArchiveOrder keeps the
+ * order they are stored in; ModifiedTime puts the oldest first.
+ */
+export type SortMode =
+  | "Natural"
+  | "Lexicographic"
+  | "DirectoryThenName"
+  | "ArchiveOrder"
+  | "ModifiedTime";
+
+/** An archive image in reading order, with the layout hints from its ComicInfo.xml. */
+export type PageEntry = {
+  name: string;
+  /** Marked FrontCover; shown on its own */
+  cover: boolean;
+  /** A two-page spread stored as one wide image; shown on its own */
+  double_page: boolean;
+  /** Marked Deleted in the metadata */
+  deleted: boolean;
+};
+
+/** A page with the sizes and pixel dimensions of its image; null where the format cannot tell */
+export type PageInfo = PageEntry & {
+  compressed_size: number | null;
+  size: number | null;
+  width: number | null;
+  height: number | null;
+};
+
+/** A folder of pages in an archive, as an entry of its table of contents */
+export type TocEntry = {
+  name: string;
+  /** Full path of the folder in the archive */
+  path: string;
+  /** Index of the first page among the pages shown (deleted pages left out) */
+  first_page: number;
+  /** Pages in the folder and its subfolders */
+  page_count: number;
+  children: TocEntry[];
+};
+
+/** Payload of the `archive-extract-progress` event. */
+export type ExtractProgress = {
+  archive_path: string;
+  /** Page that was just extracted; null on the final event */
+  entry_name: string | null;
+  extracted: number;
+  total: number;
+  /** Set on the final event when the pass stopped early */
+  error: MekuriError | null;
+};
+
+export const EXTRACT_PROGRESS_EVENT = "archive-extract-progress";
+
+/**
+ * Image names in `sort` order, natural by default. `password` unlocks an encrypted archive;
+ * once accepted the backend remembers it for the session, so later calls can leave it out.
+ */
+export async function listArchiveImages(
+  archivePath: string,
+  password?: string,
+  sort?: SortMode,
+): Promise<string[]> {
   return invoke<string[]>("list_archive_images", {
     archivePath,
+    password,
+    sort,
+  });
+}
+
+/** Image entries in `sort` order, natural by default, with ids for `getArchiveImageById`. */
+export async function listArchiveEntries(
+  archivePath: string,
+  sort?: SortMode,
+): Promise<EntryId[]> {
+  return invoke<EntryId[]>("list_archive_entries", {
+    archivePath,
+    sort,
+  });
+}
+
+/**
+ * The file to open for `archivePath`: the first volume of a multi-volume set
+ * (`book.part1.rar`, or `book.zip` for a split ZIP) when given a later one, else the path itself.
+ */
+export async function firstVolume(archivePath: string): Promise<string> {
+  return invoke<string>("first_volume", {
+    archivePath,
+  });
+}
+
+/**
+ * Pages flagged by the archive's ComicInfo.xml. In natural order (the default) they also
+ * follow its `<Pages>` order when it lists every image; any other `sort` is kept as chosen.
+ */
+export async function listArchivePages(
+  archivePath: string,
+  sort?: SortMode,
+): Promise<PageEntry[]> {
+  return invoke<PageEntry[]>("list_archive_pages", {
+    archivePath,
+    sort,
+  });
+}
+
+export async function listPagesWithInfo(
+  archivePath: string,
+  sort?: SortMode,
+): Promise<PageInfo[]> {
+  return invoke<PageInfo[]>("list_pages_with_info", {
+    archivePath,
+    sort,
+  });
+}
+
+export async function getTableOfContents(
+  archivePath: string,
+  sort?: SortMode,
+): Promise<TocEntry[]> {
+  return invoke<TocEntry[]>("get_table_of_contents", {
+    archivePath,
+    sort,
   });
 }
 
-export async function getArchiveImage(archivePath: string, entryName: string): Promise<string> {
+export async function getArchiveImage(
+  archivePath: string,
+  entryName: string,
+  password?: string,
+): Promise<string> {
   return invoke<string>("get_archive_image", {
     archivePath,
     entryName,
+    password,
+  });
+}
+
+export async function getArchiveImageById(archivePath: string, entry: EntryId): Promise<string> {
+  return invoke<string>("get_archive_image_by_id", {
+    archivePath,
+    entry,
   });
 }
 
-export async function analyzeArchiveContents(archivePath: string): Promise<ArchiveContents> {
-  return invoke<ArchiveContents>("analyze_archive_contents", {
+/** Cover (first image) of an archive as a small JPEG data URL, at most `maxSize` px per side. */
+export async function getArchiveThumbnail(archivePath: string, maxSize: number): Promise<string> {
+  return invoke<string>("get_archive_thumbnail", {
     archivePath,
+    maxSize,
   });
 }
 
-export async function extractNestedArchive(
-  parentPath: string,
-  nestedName: string,
+/** Delete every cover stored in the on-disk thumbnail cache. */
+export async function clearThumbnailCache(): Promise<void> {
+  return invoke<void>("clear_thumbnail_cache");
+}
+
+export async function getPageThumbnail(
+  archivePath: string,
+  entryName: string,
+  maxSize: number,
 ): Promise<string> {
-  return invoke<string>("extract_nested_archive", {
-    parentPath,
-    nestedName,
+  return invoke<string>("get_page_thumbnail", {
+    archivePath,
+    entryName,
+    maxSize,
+  });
+}
+
+/**
+ * Read pages into the backend cache ahead of time. Replaces any pages still pending for the
+ * archive; higher priority requests (e.g. from the focused window) are served first.
+ */
+export async function prefetchPages(
+  archivePath: string,
+  entries: string[],
+  priority: number,
+): Promise<void> {
+  return invoke<void>("prefetch_pages", {
+    archivePath,
+    entries,
+    priority,
+  });
+}
+
+export async function cancelPrefetch(archivePath: string): Promise<void> {
+  return invoke<void>("cancel_prefetch", {
+    archivePath,
+  });
+}
+
+/**
+ * Extract all pages of a solid RAR archive in one pass on the backend, reporting progress via
+ * `EXTRACT_PROGRESS_EVENT`. Resolves to false when the archive does not need it.
+ */
+export async function startBulkExtraction(archivePath: string): Promise<boolean> {
+  return invoke<boolean>("start_bulk_extraction", {
+    archivePath,
+  });
+}
+
+/** ComicInfo.xml metadata of a ZIP or RAR archive; null when it has none. */
+export async function getArchiveMetadata(archivePath: string): Promise<ArchiveMetadata | null> {
+  return invoke<ArchiveMetadata | null>("get_archive_metadata", {
+    archivePath,
+  });
+}
+
+/** The archive's images and the nested archives it holds, which are not opened yet. */
+export async function analyzeArchiveContents(archivePath: string): Promise<ContentTree> {
+  return invoke<ContentTree>("analyze_archive_contents", {
+    archivePath,
   });
 }
@@ -1,5 +1,5 @@
 import { invoke } from "@tauri-apps/api/core";
-import type { DirectoryEntry } from "../types";
+import type { DirectoryEntry, FileKind } from "../types";
 
 export async function readDirectory(path: string): Promise<DirectoryEntry[]> {
   return invoke<DirectoryEntry[]>("read_directory", { path });
@@ -12,15 +12,17 @@ export async function readDirectoryFolders(path: string): Promise<DirectoryEntry
 
 export async function readDirectoryFiles(path: string): Promise<DirectoryEntry[]> {
   const entries = await readDirectory(path);
-  return entries.filter((entry) => !entry.is_dir && (entry.is_archive || entry.is_pdf));
+  return entries.filter(
+    (entry) => entry.is_image_folder || (!entry.is_dir && (entry.is_archive || entry.is_pdf)),
+  );
 }
 
 export async function searchDirectory(path: string, query: string): Promise<DirectoryEntry[]> {
   return invoke<DirectoryEntry[]>("search_directory", { path, query });
 }
 
-export async function readFileBase64(path: string): Promise<string> {
-  return invoke<string>("read_file_base64", { path });
+export async function getFileKind(path: string): Promise<FileKind> {
+  return invoke<FileKind>("get_file_kind", { path });
 }
 
 export function getParentDirectory(filePath: string): string {
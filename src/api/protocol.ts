@@ -0,0 +1,16 @@
+import { convertFileSrc } from "@tauri-apps/api/core";
+
+// mekuri:// プロトコル（src-tauri/src/protocol.rs）の URL を組み立てる。
+// convertFileSrc はプラットフォームごとの形式（Windows では http://mekuri.localhost/）を吸収する。
+function protocolUrl(route: string, ...segments: string[]): string {
+  const base = convertFileSrc("", "mekuri");
+  return `${base}${[route, ...segments.map(encodeURIComponent)].join("/")}`;
+}
+
+export function archivePageUrl(archivePath: string, entryName: string): string {
+  return protocolUrl("page", archivePath, entryName);
+}
+
+export function fileUrl(path: string): string {
+  return protocolUrl("file", path);
+}
@@ -6,6 +6,7 @@ import {
   DEFAULT_VIEWER_WIDTH,
 } from "../utils/constants";
 import type { ReadingDirection, ViewMode } from "../utils/spreadLayout";
+import type { SortMode } from "./archive";
 import { getStore } from "./store";
 
 type MainWindowSettings = {
@@ -33,9 +34,13 @@ const DEFAULT_VIEWER_SETTINGS: ViewerWindowSettings = {
   viewMode: "spread",
 };
 
+/** Sort mode chosen per archive path; archives without an entry use natural order */
+type PageSortModes = Record<string, SortMode>;
+
 const STORE_DEFAULTS = {
   windowSettings: DEFAULT_MAIN_SETTINGS,
   viewerSettings: DEFAULT_VIEWER_SETTINGS,
+  pageSortModes: {} as PageSortModes,
 };
 
 export async function getWindowSettings(): Promise<MainWindowSettings> {
@@ -62,3 +67,20 @@ export async function saveViewerSettings(settings: Partial<ViewerWindowSettings>
     (await store.get<ViewerWindowSettings>("viewerSettings")) ?? DEFAULT_VIEWER_SETTINGS;
   await store.set("viewerSettings", { ...current, ...settings });
 }
+
+export async function getPageSortMode(archivePath: string): Promise<SortMode> {
+  const store = await getStore(STORE_DEFAULTS);
+  const modes = await store.get<PageSortModes>("pageSortModes");
+  return modes?.[archivePath] ?? "Natural";
+}
+
+export async function savePageSortMode(archivePath: string, mode: SortMode): Promise<void> {
+  const store = await getStore(STORE_DEFAULTS);
+  const modes = (await store.get<PageSortModes>("pageSortModes")) ?? {};
+  const others = Object.fromEntries(Object.entries(modes).filter(([path]) => path !== archivePath));
+  // Natural order is the default, so it needs no entry
+  await store.set(
+    "pageSortModes",
+    mode === "Natural" ? others : { ...others, [archivePath]: mode },
+  );
+}
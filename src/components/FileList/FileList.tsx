@@ -3,11 +3,22 @@ import { ask } from "@tauri-apps/plugin-dialog";
 import { useCallback, useEffect, useState } from "react";
 import { readDirectoryFiles, trashFile } from "../../api/directory";
 import { useContextMenu } from "../../hooks/useContextMenu";
+import { useCoverThumbnails } from "../../hooks/useCoverThumbnails";
 import type { DirectoryEntry } from "../../types";
 import { FILE_DRAG_MIME } from "../../utils/constants";
 import { errorToString } from "../../utils/errorToString";
 import { ArchiveIcon, FolderIcon, PdfIcon } from "../Icons/Icons";
 
+function EntryIcon({ entry, cover }: { entry: DirectoryEntry; cover: string | undefined }) {
+  if (cover) {
+    return <img className="file-list__cover" src={cover} alt="" />;
+  }
+  if (entry.is_image_folder) {
+    return <FolderIcon size={14} />;
+  }
+  return entry.is_pdf ? <PdfIcon size={14} /> : <ArchiveIcon size={14} />;
+}
+
 type FileListProps = {
   folderPath: string | null;
   onArchiveSelect: (path: string) => void;
@@ -25,6 +36,7 @@ export function FileList({
   const [loading, setLoading] = useState(false);
   const [error, setError] = useState<string | null>(null);
   const { contextMenu, openContextMenu, closeContextMenu } = useContextMenu();
+  const covers = useCoverThumbnails(searchResults ?? files);
 
   const loadFiles = useCallback(async (path: string) => {
     setLoading(true);
@@ -151,7 +163,7 @@ export function FileList({
                 e.dataTransfer.effectAllowed = "move";
               }}
             >
-              {file.is_pdf ? <PdfIcon size={14} /> : <ArchiveIcon size={14} />}
+              <EntryIcon entry={file} cover={covers[file.path]} />
               <span className="file-list__name">{file.name}</span>
             </button>
           ))}
@@ -199,7 +211,7 @@ export function FileList({
   if (files.length === 0) {
     return (
       <div className="file-list file-list--empty">
-        <p>No archives or PDFs in this folder</p>
+        <p>No archives, PDFs or image folders in this folder</p>
       </div>
     );
   }
@@ -208,24 +220,38 @@ export function FileList({
     <div className="file-list">
       <div className="file-list__header">Files</div>
       <div className="file-list__items">
-        {files.map((file) => (
-          <button
-            key={file.path}
-            type="button"
-            className="file-list__item"
-            onClick={() => onArchiveSelect(file.path)}
-            onContextMenu={(e) => openContextMenu(e, file.path)}
-            title={file.path}
-            draggable
-            onDragStart={(e) => {
-              e.dataTransfer.setData(FILE_DRAG_MIME, file.path);
-              e.dataTransfer.effectAllowed = "move";
-            }}
-          >
-            {file.is_pdf ? <PdfIcon size={14} /> : <ArchiveIcon size={14} />}
-            <span className="file-list__name">{file.name}</span>
-          </button>
-        ))}
+        {files.map((file) =>
+          // Image folders open in the viewer but cannot be trashed or moved like files
+          file.is_image_folder ? (
+            <button
+              key={file.path}
+              type="button"
+              className="file-list__item"
+              onClick={() => onArchiveSelect(file.path)}
+              title={file.path}
+            >
+              <EntryIcon entry={file} cover={covers[file.path]} />
+              <span className="file-list__name">{file.name}</span>
+            </button>
+          ) : (
+            <button
+              key={file.path}
+              type="button"
+              className="file-list__item"
+              onClick={() => onArchiveSelect(file.path)}
+              onContextMenu={(e) => openContextMenu(e, file.path)}
+              title={file.path}
+              draggable
+              onDragStart={(e) => {
+                e.dataTransfer.setData(FILE_DRAG_MIME, file.path);
+                e.dataTransfer.effectAllowed = "move";
+              }}
+            >
+              <EntryIcon entry={file} cover={covers[file.path]} />
+              <span className="file-list__name">{file.name}</span>
+            </button>
+          ),
+        )}
       </div>
 
       {contextMenu && (
@@ -0,0 +1,39 @@
+import { useState } from "react";
+
+type PasswordPromptProps = {
+  /** パスワードが要る理由、または前回の入力が違っていたことを伝えるメッセージ */
+  error: string | null;
+  onSubmit: (password: string) => void;
+};
+
+/** 暗号化されたアーカイブのパスワード入力欄。受け付けたパスワードはセッション中バックエンドが覚える */
+export function PasswordPrompt({ error, onSubmit }: PasswordPromptProps) {
+  const [password, setPassword] = useState("");
+
+  return (
+    <div className="viewer viewer--password">
+      <form
+        className="password-prompt"
+        onSubmit={(e) => {
+          e.preventDefault();
+          if (password) {
+            onSubmit(password);
+          }
+        }}
+      >
+        <h2 className="password-prompt__title">Password Required</h2>
+        <input
+          type="password"
+          className="password-prompt__input"
+          value={password}
+          onChange={(e) => setPassword(e.target.value)}
+          placeholder="Password"
+        />
+        <button type="submit" className="password-prompt__submit" disabled={!password}>
+          Open
+        </button>
+        {error && <p className="password-prompt__detail">{error}</p>}
+      </form>
+    </div>
+  );
+}
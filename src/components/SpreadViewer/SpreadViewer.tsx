@@ -32,6 +32,7 @@ export type SpreadViewerHandle = {
   viewMode: ViewMode;
   readingDirection: ReadingDirection;
   currentPage: number;
+  goToPage: (page: number) => void;
   setViewMode: (mode: ViewMode) => void;
   toggleReadingDirection: () => void;
 };
@@ -40,9 +41,17 @@ type SpreadViewerProps = {
   pageCount: number;
   pageNames: string[];
   getPageDataUrl: (pageIndex: number) => Promise<string>;
+  /** Small preview of a page, shown while hovering the progress bar */
+  getPageThumbnail?: (pageIndex: number) => Promise<string>;
+  /** Called with the pages likely to be shown next, nearest first */
+  onPrefetch?: (pageIndices: number[]) => void;
+  /** Pages shown on their own in every mode, such as two-page spreads stored as one image */
+  soloPages?: ReadonlySet<number>;
   onSpreadChange?: (spreadIndex: number, totalSpreads: number) => void;
   onBack?: () => void;
   defaultReadingDirection?: ReadingDirection;
+  /** Direction declared by the file itself (ComicInfo `Manga`); wins over the saved setting */
+  preferredReadingDirection?: ReadingDirection;
   /** マウント時に開くページ index（移動後の位置復帰用） */
   initialPage?: number;
   /** ファイル移動パネル連携（指定時のみ移動ボタンとドラッグを有効化） */
@@ -54,6 +63,9 @@ type SpreadViewerProps = {
   ref?: Ref<SpreadViewerHandle>;
 };
 
+/** Number of upcoming groups whose pages are prefetched. */
+const PREFETCH_AHEAD_GROUPS = 2;
+
 /** Assumed page aspect (width/height) until a real page is measured. A4 portrait. */
 const DEFAULT_PAGE_ASPECT = Math.SQRT1_2;
 
@@ -68,9 +80,13 @@ export function SpreadViewer({
   pageCount,
   pageNames,
   getPageDataUrl,
+  getPageThumbnail,
+  onPrefetch,
+  soloPages,
   onSpreadChange,
   onBack,
   defaultReadingDirection = "rtl",
+  preferredReadingDirection,
   initialPage,
   movePanel,
   ref,
@@ -79,8 +95,9 @@ export function SpreadViewer({
   const [srcs, setSrcs] = useState<(string | null)[]>([]);
   const [loadError, setLoadError] = useState<string | null>(null);
   const [viewMode, setViewModeState] = useState<ViewMode>("spread");
-  const [readingDirection, setReadingDirection] =
-    useState<ReadingDirection>(defaultReadingDirection);
+  const [readingDirection, setReadingDirection] = useState<ReadingDirection>(
+    preferredReadingDirection ?? defaultReadingDirection,
+  );
   const [pageAspect, setPageAspect] = useState<number | null>(null);
   const [containerSize, setContainerSize] = useState({ width: 0, height: 0 });
   const pagesRef = useRef<HTMLDivElement>(null);
@@ -91,11 +108,11 @@ export function SpreadViewer({
       if (settings.viewMode) {
         setViewModeState(settings.viewMode);
       }
-      if (settings.readingDirection) {
+      if (settings.readingDirection && !preferredReadingDirection) {
         setReadingDirection(settings.readingDirection);
       }
     });
-  }, []);
+  }, [preferredReadingDirection]);
 
   // Track the pages container size for the fit mode
   useEffect(() => {
@@ -121,8 +138,8 @@ export function SpreadViewer({
       : 2;
 
   const groups: PageGroup[] = useMemo(
-    () => buildPageGroups(pageCount, viewMode, fitPageCount),
-    [pageCount, viewMode, fitPageCount],
+    () => buildPageGroups(pageCount, viewMode, fitPageCount, soloPages),
+    [pageCount, viewMode, fitPageCount, soloPages],
   );
 
   // Derive the group index from the canonical currentPage so that position is
@@ -139,6 +156,11 @@ export function SpreadViewer({
     saveViewerSettings({ viewMode: mode });
   }, []);
 
+  // Shows the group holding the page, e.g. the first page of a chapter
+  const goToPage = useCallback((page: number) => {
+    setCurrentPage(Math.max(0, page));
+  }, []);
+
   const toggleReadingDirection = useCallback(() => {
     setReadingDirection((prev) => {
       const next: ReadingDirection = prev === "rtl" ? "ltr" : "rtl";
@@ -151,6 +173,7 @@ export function SpreadViewer({
     viewMode,
     readingDirection,
     currentPage: currentPageFromGroup(currentGroup),
+    goToPage,
     setViewMode,
     toggleReadingDirection,
   }));
@@ -182,6 +205,17 @@ export function SpreadViewer({
     };
   }, [getPageDataUrl, groupKey]);
 
+  // Prefetch the next groups, then the previous one for paging back
+  useEffect(() => {
+    if (!onPrefetch) return;
+    const nearby = [
+      ...groups.slice(groupIndex + 1, groupIndex + 1 + PREFETCH_AHEAD_GROUPS),
+      ...groups.slice(Math.max(0, groupIndex - 1), groupIndex),
+    ];
+    const pages = nearby.flat();
+    if (pages.length > 0) onPrefetch(pages);
+  }, [groups, groupIndex, onPrefetch]);
+
   const goNext = useCallback(() => {
     const next = groups[groupIndex + 1];
     if (next) setCurrentPage(next[0]);
@@ -244,20 +278,67 @@ export function SpreadViewer({
   const isLast = groupIndex >= groups.length - 1;
   const progressPercent = groups.length > 1 ? (groupIndex / (groups.length - 1)) * 100 : 100;
 
-  const handleProgressClick = useCallback(
+  // Map a pointer position on the progress bar to a group index
+  const groupIndexAtPointer = useCallback(
     (e: React.MouseEvent<HTMLDivElement>) => {
       const rect = e.currentTarget.getBoundingClientRect();
       const ratio = isRtl
         ? (rect.right - e.clientX) / rect.width
         : (e.clientX - rect.left) / rect.width;
       const newIndex = Math.round(ratio * (groups.length - 1));
-      const clamped = Math.max(0, Math.min(newIndex, groups.length - 1));
-      const group = groups[clamped];
-      if (group) setCurrentPage(group[0]);
+      return Math.max(0, Math.min(newIndex, groups.length - 1));
     },
     [groups, isRtl],
   );
 
+  const handleProgressClick = useCallback(
+    (e: React.MouseEvent<HTMLDivElement>) => {
+      const group = groups[groupIndexAtPointer(e)];
+      if (group) setCurrentPage(group[0]);
+    },
+    [groups, groupIndexAtPointer],
+  );
+
+  // Page preview while hovering the progress bar
+  const [hoverPreview, setHoverPreview] = useState<{ page: number; left: number } | null>(null);
+  const [previewSrc, setPreviewSrc] = useState<string | null>(null);
+  const thumbnailsRef = useRef(new Map<number, string>());
+
+  const handleProgressMove = useCallback(
+    (e: React.MouseEvent<HTMLDivElement>) => {
+      if (!getPageThumbnail) return;
+      const group = groups[groupIndexAtPointer(e)];
+      if (!group) return;
+      const left = e.clientX - e.currentTarget.getBoundingClientRect().left;
+      setHoverPreview({ page: group[0], left });
+    },
+    [getPageThumbnail, groups, groupIndexAtPointer],
+  );
+
+  const previewPage = hoverPreview?.page ?? null;
+  useEffect(() => {
+    if (previewPage === null || !getPageThumbnail) return;
+    const cached = thumbnailsRef.current.get(previewPage);
+    if (cached) {
+      setPreviewSrc(cached);
+      return;
+    }
+
+    let cancelled = false;
+    setPreviewSrc(null);
+    getPageThumbnail(previewPage)
+      .then((src) => {
+        thumbnailsRef.current.set(previewPage, src);
+        if (!cancelled) setPreviewSrc(src);
+      })
+      .catch(() => {
+        // A missing preview only leaves the page number visible
+      });
+    return () => {
+      cancelled = true;
+    };
+  }, [previewPage, getPageThumbnail]);
+
   // Page-turn by clicking the left/right half of the pages area
   const handlePagesClick = useCallback(
     (e: React.MouseEvent<HTMLDivElement>) => {
@@ -319,7 +400,18 @@ export function SpreadViewer({
       </div>
       <div className="spread-viewer__footer">
         {/* biome-ignore lint/a11y/useKeyWithClickEvents lint/a11y/noStaticElementInteractions: mouse interaction for progress bar */}
-        <div className="spread-viewer__progress" onClick={handleProgressClick}>
+        <div
+          className="spread-viewer__progress"
+          onClick={handleProgressClick}
+          onMouseMove={handleProgressMove}
+          onMouseLeave={() => setHoverPreview(null)}
+        >
+          {hoverPreview && (
+            <div className="spread-viewer__preview" style={{ left: hoverPreview.left }}>
+              {previewSrc && <img src={previewSrc} alt="" draggable={false} />}
+              <span>{hoverPreview.page + 1}</span>
+            </div>
+          )}
           <div
             className="spread-viewer__progress-fill"
             style={
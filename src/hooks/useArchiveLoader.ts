@@ -1,58 +1,159 @@
-import { useCallback, useEffect, useState } from "react";
+import { listen } from "@tauri-apps/api/event";
+import { useCallback, useEffect, useMemo, useState } from "react";
 import {
-  type ArchiveContents,
+  type ArchiveMetadata,
   analyzeArchiveContents,
-  extractNestedArchive,
+  type ContentTree,
+  EXTRACT_PROGRESS_EVENT,
+  type ExtractProgress,
+  getArchiveMetadata,
+  getTableOfContents,
   listArchiveImages,
+  listArchivePages,
+  listPagesWithInfo,
+  type PageEntry,
+  type PageInfo,
+  type SortMode,
+  type TocEntry,
 } from "../api/archive";
-import { errorToString } from "../utils/errorToString";
+import { getPageSortMode, savePageSortMode } from "../api/settings";
+import type { MekuriErrorCode } from "../types";
+import { visiblePages } from "../utils/comicInfo";
+import { errorCode, errorToString } from "../utils/errorToString";
+
+// Metadata is optional: a missing or broken ComicInfo.xml must not keep the archive from opening
+async function loadMetadata(path: string): Promise<ArchiveMetadata | null> {
+  try {
+    return await getArchiveMetadata(path);
+  } catch (err) {
+    console.error("Failed to read archive metadata:", err);
+    return null;
+  }
+}
+
+/** One list of nested archives to choose from */
+type NestedLevel = {
+  /** Pages stored next to the archives, such as the cover of an omnibus; null if none */
+  pages: ContentTree | null;
+  archives: ContentTree[];
+};
+
+// An archive holding other archives opens as a list of them. Its own pages come first in
+// the list, as an entry holding only those pages, so that neither is out of reach
+function nestedLevel(node: ContentTree): NestedLevel {
+  const pages = node.image_groups.length > 0 ? { ...node, nested: [] } : null;
+  return { pages, archives: node.nested };
+}
 
 type ArchiveLoaderState = {
   effectivePath: string | null;
   imageNames: string[];
-  nestedArchives: string[] | null;
+  /** Pages to show, in reading order, with their layout hints */
+  pages: PageEntry[];
+  /** Sizes and dimensions of the pages, matched by name; loaded after the pages, null until then */
+  pageInfo: PageInfo[] | null;
+  metadata: ArchiveMetadata | null;
+  /** Folders of the pages, for jumping between chapters; empty when there are none */
+  tableOfContents: TocEntry[];
+  /** Order of the pages, remembered per archive */
+  sortMode: SortMode;
+  /** Nested archives to choose from, when the archive shown holds other archives */
+  nestedArchives: ContentTree[] | null;
+  /** Pages stored next to `nestedArchives`, to offer as one more choice; null if none */
+  nestedPages: ContentTree | null;
   loading: boolean;
   error: string | null;
+  /** Backend code of `error`, for reacting to specific failures such as a missing password */
+  errorCode: MekuriErrorCode | null;
+  /** Whether `unlock` can be called, i.e. the archive failed for lack of a password */
+  locked: boolean;
   hasNestedCache: boolean;
+  /** How many lists of nested archives were opened on the way down */
+  nestedDepth: number;
 };
 
 export function useArchiveLoader(archivePath: string | null) {
   const [effectivePath, setEffectivePath] = useState<string | null>(null);
-  const [imageNames, setImageNames] = useState<string[]>([]);
-  const [nestedArchives, setNestedArchives] = useState<string[] | null>(null);
-  const [cachedNestedArchives, setCachedNestedArchives] = useState<string[] | null>(null);
+  const [pages, setPages] = useState<PageEntry[]>([]);
+  const [pageInfo, setPageInfo] = useState<PageInfo[] | null>(null);
+  const [metadata, setMetadata] = useState<ArchiveMetadata | null>(null);
+  const [tableOfContents, setTableOfContents] = useState<TocEntry[]>([]);
+  const [sortMode, setSortMode] = useState<SortMode>("Natural");
+  const [nestedArchives, setNestedArchives] = useState<ContentTree[] | null>(null);
+  const [nestedPages, setNestedPages] = useState<ContentTree | null>(null);
+  // Lists of nested archives from the outermost down, to go back up through
+  const [nestedLevels, setNestedLevels] = useState<NestedLevel[]>([]);
   const [loading, setLoading] = useState(false);
   const [error, setError] = useState<string | null>(null);
+  const [failureCode, setFailureCode] = useState<MekuriErrorCode | null>(null);
+  // Archive that failed for lack of a password, until `unlock` gets the right one
+  const [lockedPath, setLockedPath] = useState<string | null>(null);
+  const [retryTrigger, setRetryTrigger] = useState(0);
+
+  const fail = useCallback((err: unknown, path: string) => {
+    const code = errorCode(err);
+    setError(errorToString(err));
+    setFailureCode(code);
+    setLockedPath(code === "PasswordRequired" ? path : null);
+  }, []);
 
-  // Analyze archive contents when archive path changes
+  const showImages = useCallback(async (path: string) => {
+    const sort = await getPageSortMode(path);
+    const [archivePages, archiveMetadata] = await Promise.all([
+      listArchivePages(path, sort),
+      loadMetadata(path),
+    ]);
+    setPages(visiblePages(archivePages));
+    setSortMode(sort);
+    setMetadata(archiveMetadata);
+    setEffectivePath(path);
+  }, []);
+
+  // Analyze archive contents when archive path changes, or again once it is unlocked
+  // biome-ignore lint/correctness/useExhaustiveDependencies: retryTrigger is intentionally used to re-analyze
   useEffect(() => {
     if (!archivePath) return;
     const path = archivePath;
 
     let cancelled = false;
     setLoading(true);
+    setError(null);
+    setFailureCode(null);
+    setLockedPath(null);
     setNestedArchives(null);
-    setImageNames([]);
+    setNestedPages(null);
+    setPages([]);
+    setMetadata(null);
     setEffectivePath(null);
 
     async function analyze() {
       try {
-        const contents: ArchiveContents = await analyzeArchiveContents(path);
+        const [contents, archiveMetadata] = await Promise.all([
+          analyzeArchiveContents(path),
+          loadMetadata(path),
+        ]);
         if (cancelled) return;
 
-        if (contents.type === "Images") {
-          setImageNames(contents.names);
+        if (contents.nested.length > 0) {
+          const level = nestedLevel(contents);
+          setNestedArchives(level.archives);
+          setNestedPages(level.pages);
+          setNestedLevels([level]);
+        } else if (contents.image_groups.length > 0) {
+          const sort = await getPageSortMode(path);
+          const archivePages = await listArchivePages(path, sort);
+          if (cancelled) return;
+          setPages(visiblePages(archivePages));
+          setSortMode(sort);
+          setMetadata(archiveMetadata);
           setEffectivePath(path);
-          setCachedNestedArchives(null);
-        } else if (contents.type === "NestedArchives") {
-          setNestedArchives(contents.names);
-          setCachedNestedArchives(contents.names);
+          setNestedLevels([]);
         } else {
           setError("No images found in this archive");
         }
       } catch (err) {
         if (!cancelled) {
-          setError(errorToString(err));
+          fail(err, path);
         }
       } finally {
         if (!cancelled) {
@@ -65,45 +166,169 @@ export function useArchiveLoader(archivePath: string | null) {
     return () => {
       cancelled = true;
     };
-  }, [archivePath]);
+  }, [archivePath, fail, retryTrigger]);
+
+  // Dimensions need every image header, so they load in the background instead of delaying
+  // the first page. Pages are matched to their info by name, so sorting does not reload it.
+  // Pages of a solid RAR are measured once extracted, so the info reloads when that is done
+  useEffect(() => {
+    setPageInfo(null);
+    if (!effectivePath) return;
+    const path = effectivePath;
+
+    let cancelled = false;
+    const load = () => {
+      listPagesWithInfo(path)
+        .then((info) => {
+          if (!cancelled) {
+            setPageInfo(info);
+          }
+        })
+        .catch((err) => {
+          console.error("Failed to read page dimensions:", err);
+        });
+    };
+    const unlisten = listen<ExtractProgress>(EXTRACT_PROGRESS_EVENT, (event) => {
+      const { archive_path, entry_name } = event.payload;
+      if (!cancelled && archive_path === path && entry_name === null) {
+        load();
+      }
+    });
+    load();
+    return () => {
+      cancelled = true;
+      unlisten.then((fn) => fn());
+    };
+  }, [effectivePath]);
+
+  // Page indices of the folders follow the sort mode too
+  useEffect(() => {
+    setTableOfContents([]);
+    if (!effectivePath) return;
+
+    let cancelled = false;
+    getTableOfContents(effectivePath, sortMode)
+      .then((toc) => {
+        if (!cancelled) {
+          setTableOfContents(toc);
+        }
+      })
+      .catch((err) => {
+        console.error("Failed to read table of contents:", err);
+      });
+    return () => {
+      cancelled = true;
+    };
+  }, [effectivePath, sortMode]);
 
+  // Nested archives are opened by their virtual path, however deep they are
   const selectNestedArchive = useCallback(
-    async (nestedName: string) => {
-      if (!archivePath) return;
+    async (selected: ContentTree) => {
       setLoading(true);
       setError(null);
+      setFailureCode(null);
 
       try {
-        const extractedPath = await extractNestedArchive(archivePath, nestedName);
-        const names = await listArchiveImages(extractedPath);
-        setImageNames(names);
-        setEffectivePath(extractedPath);
-        setNestedArchives(null);
+        // Nested archives are listed without being opened, so they are analyzed once chosen
+        const nested = selected.analyzed ? selected : await analyzeArchiveContents(selected.path);
+        if (nested.nested.length > 0) {
+          // An archive of archives opens as another list to choose from
+          const level = nestedLevel(nested);
+          setNestedArchives(level.archives);
+          setNestedPages(level.pages);
+          setNestedLevels((levels) => [...levels, level]);
+        } else if (nested.image_groups.length > 0) {
+          await showImages(nested.path);
+          setNestedArchives(null);
+          setNestedPages(null);
+        } else {
+          setError("No images found in this archive");
+        }
       } catch (err) {
-        setError(errorToString(err));
+        fail(err, selected.path);
       } finally {
         setLoading(false);
       }
     },
-    [archivePath],
+    [fail, showImages],
   );
 
+  const unlock = useCallback(
+    async (password: string) => {
+      if (!lockedPath) return;
+      const path = lockedPath;
+      setLoading(true);
+
+      try {
+        await listArchiveImages(path, password);
+        setError(null);
+        setFailureCode(null);
+        setLockedPath(null);
+        if (path === archivePath) {
+          // Contents of an archive with encrypted headers could not be analyzed at all
+          setRetryTrigger((n) => n + 1);
+        } else {
+          // A nested archive with encrypted headers could not be analyzed either
+          await selectNestedArchive(await analyzeArchiveContents(path));
+        }
+      } catch (err) {
+        fail(err, path);
+      } finally {
+        setLoading(false);
+      }
+    },
+    [archivePath, lockedPath, fail, selectNestedArchive],
+  );
+
+  // Reorders the open archive and remembers the choice for the next time it is opened
+  const changeSortMode = useCallback(
+    async (mode: SortMode) => {
+      if (!effectivePath) return;
+      const path = effectivePath;
+      try {
+        const archivePages = await listArchivePages(path, mode);
+        setPages(visiblePages(archivePages));
+        setSortMode(mode);
+        await savePageSortMode(path, mode);
+      } catch (err) {
+        console.error("Failed to sort pages:", err);
+      }
+    },
+    [effectivePath],
+  );
+
+  // From the pages, back to the list they were chosen from; from a list, up one level
   const backToNestedList = useCallback(() => {
-    if (cachedNestedArchives) {
-      setNestedArchives(cachedNestedArchives);
-      setImageNames([]);
-      setEffectivePath(null);
-    }
-  }, [cachedNestedArchives]);
+    const levels = effectivePath ? nestedLevels : nestedLevels.slice(0, -1);
+    const level = levels[levels.length - 1];
+    if (!level) return;
+    setNestedLevels(levels);
+    setNestedArchives(level.archives);
+    setNestedPages(level.pages);
+    setPages([]);
+    setMetadata(null);
+    setEffectivePath(null);
+  }, [effectivePath, nestedLevels]);
+
+  const imageNames = useMemo(() => pages.map((page) => page.name), [pages]);
 
   const state: ArchiveLoaderState = {
     effectivePath,
     imageNames,
+    pages,
+    pageInfo,
+    metadata,
+    tableOfContents,
+    sortMode,
     nestedArchives,
+    nestedPages,
     loading,
     error,
-    hasNestedCache: cachedNestedArchives !== null,
+    errorCode: failureCode,
+    locked: lockedPath !== null,
+    hasNestedCache: nestedLevels.length > 0,
+    nestedDepth: nestedLevels.length,
   };
 
-  return { ...state, selectNestedArchive, backToNestedList };
+  return { ...state, selectNestedArchive, backToNestedList, unlock, changeSortMode };
 }
@@ -0,0 +1,44 @@
+import { listen } from "@tauri-apps/api/event";
+import { useEffect, useState } from "react";
+import { EXTRACT_PROGRESS_EVENT, type ExtractProgress, startBulkExtraction } from "../api/archive";
+
+type BulkExtractionProgress = {
+  extracted: number;
+  total: number;
+};
+
+/**
+ * Start the backend's single-pass extraction for solid RAR archives and track its progress.
+ * Returns null when no extraction is running. Pages requested meanwhile are served as soon as
+ * they are extracted, so this is only used for display.
+ */
+export function useBulkExtraction(archivePath: string | null): BulkExtractionProgress | null {
+  const [progress, setProgress] = useState<BulkExtractionProgress | null>(null);
+
+  useEffect(() => {
+    if (!archivePath) return;
+    const path = archivePath;
+    let cancelled = false;
+    setProgress(null);
+
+    // Subscribe before starting so the first pages are not missed
+    const unlisten = listen<ExtractProgress>(EXTRACT_PROGRESS_EVENT, (event) => {
+      const { archive_path, entry_name, extracted, total } = event.payload;
+      if (cancelled || archive_path !== path) return;
+      setProgress(entry_name === null ? null : { extracted, total });
+    });
+
+    unlisten
+      .then(() => startBulkExtraction(path))
+      .catch((err) => {
+        console.error("Failed to start bulk extraction:", err);
+      });
+
+    return () => {
+      cancelled = true;
+      unlisten.then((fn) => fn());
+    };
+  }, [archivePath]);
+
+  return progress;
+}
@@ -0,0 +1,53 @@
+import { useEffect, useMemo, useState } from "react";
+import { getArchiveThumbnail } from "../api/archive";
+import { fileUrl } from "../api/protocol";
+import type { DirectoryEntry } from "../types";
+
+/** Long edge of list covers. Must match `COVER_THUMBNAIL_SIZE` in src-tauri/src/commands/fs.rs */
+const COVER_THUMBNAIL_SIZE = 128;
+
+/**
+ * Cover image URLs for the archives and image folders in a listing, keyed by path.
+ * Covers already in the on-disk cache arrive with the listing; the rest are generated one at a
+ * time in the background, which also stores them for the next visit.
+ */
+export function useCoverThumbnails(entries: DirectoryEntry[]): Record<string, string> {
+  const [generated, setGenerated] = useState<Record<string, string>>({});
+
+  useEffect(() => {
+    let cancelled = false;
+    setGenerated({});
+    const missing = entries.filter(
+      (entry) => (entry.is_archive || entry.is_image_folder) && entry.thumbnail === null,
+    );
+
+    async function generate() {
+      for (const entry of missing) {
+        if (cancelled) return;
+        try {
+          const url = await getArchiveThumbnail(entry.path, COVER_THUMBNAIL_SIZE);
+          if (!cancelled) {
+            setGenerated((prev) => ({ ...prev, [entry.path]: url }));
+          }
+        } catch {
+          // Entries without a readable image keep their icon
+        }
+      }
+    }
+
+    generate();
+    return () => {
+      cancelled = true;
+    };
+  }, [entries]);
+
+  return useMemo(() => {
+    const covers = { ...generated };
+    for (const entry of entries) {
+      if (entry.thumbnail) {
+        covers[entry.path] = fileUrl(entry.thumbnail);
+      }
+    }
+    return covers;
+  }, [entries, generated]);
+}
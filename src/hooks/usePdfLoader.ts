@@ -1,8 +1,8 @@
 import type { PDFDocumentProxy } from "pdfjs-dist";
 import { useCallback, useEffect, useRef, useState } from "react";
-import { readFileBase64 } from "../api/directory";
+import { fileUrl } from "../api/protocol";
 import { errorToString } from "../utils/errorToString";
-import { loadPdfFromBase64, renderPageToDataUrl } from "../utils/pdf";
+import { loadPdfFromUrl, renderPageToDataUrl } from "../utils/pdf";
 
 type PdfLoaderResult = {
   pageCount: number;
@@ -28,10 +28,7 @@ export function usePdfLoader(pdfPath: string | null): PdfLoaderResult {
 
     async function load() {
       try {
-        const base64 = await readFileBase64(pdfPath as string);
-        if (cancelled) return;
-
-        const doc = await loadPdfFromBase64(base64);
+        const doc = await loadPdfFromUrl(fileUrl(pdfPath as string));
         if (cancelled) {
           doc.destroy();
           return;
@@ -4,7 +4,31 @@ export type DirectoryEntry = {
   is_dir: boolean;
   is_archive: boolean;
   is_pdf: boolean;
+  /** Folder that directly contains images and can be opened in the viewer */
+  is_image_folder: boolean;
   has_subfolders: boolean;
+  /** Cover JPEG already in the on-disk thumbnail cache; null until one has been generated */
+  thumbnail: string | null;
+};
+
+/** How the backend classifies a file, by its name and first bytes */
+export type FileKind = "Archive" | "Pdf" | "Other";
+
+/** Stable codes of the backend's `MekuriError`; branch on these, not on the message */
+export type MekuriErrorCode =
+  | "NotFound"
+  | "UnsupportedFormat"
+  | "Corrupt"
+  | "PasswordRequired"
+  | "PermissionDenied"
+  | "AlreadyExists"
+  | "InvalidInput"
+  | "Io";
+
+/** What a failed backend command rejects with */
+export type MekuriError = {
+  code: MekuriErrorCode;
+  message: string;
 };
 
 export type TreeNodeData = {
@@ -0,0 +1,86 @@
+import { describe, expect, it } from "vitest";
+import type { ArchiveMetadata, Manga, PageEntry, PageInfo } from "../api/archive";
+import { readingDirectionFromMetadata, soloPageIndices, visiblePages } from "./comicInfo";
+
+function metadata(manga: Manga): ArchiveMetadata {
+  return {
+    title: null,
+    series: null,
+    number: null,
+    volume: null,
+    count: null,
+    summary: null,
+    year: null,
+    month: null,
+    day: null,
+    writer: null,
+    penciller: null,
+    publisher: null,
+    genre: null,
+    tags: null,
+    language_iso: null,
+    page_count: null,
+    manga,
+    pages: [],
+  };
+}
+
+describe("readingDirectionFromMetadata", () => {
+  it("reads right-to-left manga as RTL", () => {
+    expect(readingDirectionFromMetadata(metadata("YesAndRightToLeft"))).toBe("rtl");
+  });
+
+  it("reads non-manga as LTR", () => {
+    expect(readingDirectionFromMetadata(metadata("No"))).toBe("ltr");
+  });
+
+  it("has no opinion when the direction is not declared", () => {
+    expect(readingDirectionFromMetadata(metadata("Yes"))).toBeNull();
+    expect(readingDirectionFromMetadata(metadata("Unknown"))).toBeNull();
+    expect(readingDirectionFromMetadata(null)).toBeNull();
+  });
+});
+
+function page(name: string, flags: Partial<PageEntry> = {}): PageEntry {
+  return { name, cover: false, double_page: false, deleted: false, ...flags };
+}
+
+describe("visiblePages", () => {
+  it("leaves out deleted pages", () => {
+    const pages = [page("1.jpg"), page("ad.jpg", { deleted: true }), page("2.jpg")];
+    expect(visiblePages(pages).map((p) => p.name)).toEqual(["1.jpg", "2.jpg"]);
+  });
+
+  it("keeps everything when every page is marked deleted", () => {
+    const pages = [page("1.jpg", { deleted: true })];
+    expect(visiblePages(pages)).toEqual(pages);
+  });
+});
+
+describe("soloPageIndices", () => {
+  it("collects covers and double pages", () => {
+    const pages = [
+      page("c.jpg", { cover: true }),
+      page("1.jpg"),
+      page("2-3.jpg", { double_page: true }),
+    ];
+    expect([...soloPageIndices(pages)]).toEqual([0, 2]);
+  });
+
+  it("adds landscape pages once their dimensions are known", () => {
+    const pages = [page("1.jpg"), page("2-3.jpg"), page("4.jpg")];
+    const info = (name: string, width: number | null, height: number | null): PageInfo => ({
+      ...page(name),
+      compressed_size: null,
+      size: null,
+      width,
+      height,
+    });
+    const sizes = [
+      info("1.jpg", 800, 1200),
+      info("2-3.jpg", 1600, 1200),
+      info("4.jpg", null, null),
+    ];
+    expect([...soloPageIndices(pages, sizes)]).toEqual([1]);
+  });
+});
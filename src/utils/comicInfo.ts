@@ -0,0 +1,46 @@
+import type { ArchiveMetadata, PageEntry, PageInfo } from "../api/archive";
+import type { ReadingDirection } from "./spreadLayout";
+
+/**
+ * Reading direction declared by an archive's ComicInfo `Manga` field.
+ * Returns null when the archive does not say, so the viewer keeps its usual default.
+ */
+export function readingDirectionFromMetadata(
+  metadata: ArchiveMetadata | null,
+): ReadingDirection | null {
+  switch (metadata?.manga) {
+    case "YesAndRightToLeft":
+      return "rtl";
+    case "No":
+      return "ltr";
+    default:
+      // "Yes" marks manga without stating the direction
+      return null;
+  }
+}
+
+/** Pages to show, leaving out those marked deleted unless that would leave nothing. */
+export function visiblePages(pages: PageEntry[]): PageEntry[] {
+  const visible = pages.filter((page) => !page.deleted);
+  return visible.length > 0 ? visible : pages;
+}
+
+/** Whether an image is wider than tall, which usually means a two-page spread. */
+function isLandscape(info: PageInfo | undefined): boolean {
+  return info?.width != null && info.height != null && info.width > info.height;
+}
+
+/**
+ * Indices of pages that must be shown on their own: covers and two-page spreads, either
+ * marked in the metadata or, once `info` is known, stored as landscape images.
+ */
+export function soloPageIndices(pages: PageEntry[], info: PageInfo[] | null = null): Set<number> {
+  const infoByName = new Map(info?.map((page) => [page.name, page]));
+  const indices = new Set<number>();
+  pages.forEach((page, index) => {
+    if (page.cover || page.double_page || isLandscape(infoByName.get(page.name))) {
+      indices.add(index);
+    }
+  });
+  return indices;
+}
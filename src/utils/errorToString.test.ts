@@ -1,5 +1,5 @@
 import { describe, expect, it } from "vitest";
-import { errorToString } from "./errorToString";
+import { errorCode, errorToString } from "./errorToString";
 
 describe("errorToString", () => {
   it("returns message from Error instance", () => {
@@ -25,4 +25,22 @@ describe("errorToString", () => {
   it("converts object to string", () => {
     expect(errorToString({ code: 404 })).toBe("[object Object]");
   });
+
+  it("returns message from backend error", () => {
+    expect(errorToString({ code: "NotFound", message: "Entry not found: 01.jpg" })).toBe(
+      "Entry not found: 01.jpg",
+    );
+  });
+});
+
+describe("errorCode", () => {
+  it("returns code from backend error", () => {
+    expect(errorCode({ code: "PasswordRequired", message: "encrypted" })).toBe("PasswordRequired");
+  });
+
+  it("returns null for other errors", () => {
+    expect(errorCode(new Error("boom"))).toBeNull();
+    expect(errorCode("plain string error")).toBeNull();
+    expect(errorCode({ code: 404 })).toBeNull();
+  });
 });
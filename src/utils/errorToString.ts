@@ -1,4 +1,19 @@
+import type { MekuriError, MekuriErrorCode } from "../types";
+
+/** Whether a value is the `{ code, message }` error rejected by backend commands. */
+export function isMekuriError(err: unknown): err is MekuriError {
+  if (typeof err !== "object" || err === null) return false;
+  const { code, message } = err as Record<string, unknown>;
+  return typeof code === "string" && typeof message === "string";
+}
+
+/** The backend error code of a rejected command, or null for any other kind of error. */
+export function errorCode(err: unknown): MekuriErrorCode | null {
+  return isMekuriError(err) ? err.code : null;
+}
+
 export function errorToString(err: unknown): string {
   if (err instanceof Error) return err.message;
+  if (isMekuriError(err)) return err.message;
   return String(err);
 }
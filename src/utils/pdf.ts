@@ -9,14 +9,10 @@ pdfjsLib.GlobalWorkerOptions.workerSrc = new URL(
 const CMAP_URL = "/pdfjs/cmaps/";
 const STANDARD_FONT_DATA_URL = "/pdfjs/standard_fonts/";
 
-export async function loadPdfFromBase64(base64: string): Promise<PDFDocumentProxy> {
-  const binaryString = atob(base64);
-  const bytes = new Uint8Array(binaryString.length);
-  for (let i = 0; i < binaryString.length; i++) {
-    bytes[i] = binaryString.charCodeAt(i);
-  }
+export async function loadPdfFromUrl(url: string): Promise<PDFDocumentProxy> {
+  // Range 対応のプロトコル経由で読み込むため、必要な部分だけ分割して取得される
   const doc = await pdfjsLib.getDocument({
-    data: bytes,
+    url,
     cMapUrl: CMAP_URL,
     cMapPacked: true,
     standardFontDataUrl: STANDARD_FONT_DATA_URL,
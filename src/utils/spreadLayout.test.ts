@@ -80,6 +80,32 @@ describe("buildPageGroups (fit)", () => {
   });
 });
 
+describe("buildPageGroups (solo pages)", () => {
+  it("shows a double page alone and restarts pairing after it", () => {
+    expect(buildPageGroups(7, "spread", undefined, new Set([2]))).toEqual([
+      [0],
+      [1],
+      [2],
+      [3, 4],
+      [5, 6],
+    ]);
+  });
+
+  it("keeps solo pages alone in triple and fit modes", () => {
+    expect(buildPageGroups(6, "triple", undefined, new Set([1]))).toEqual([
+      [0],
+      [1],
+      [2, 3, 4],
+      [5],
+    ]);
+    expect(buildPageGroups(4, "fit", 3, new Set([3]))).toEqual([[0, 1, 2], [3]]);
+  });
+
+  it("does not change single mode", () => {
+    expect(buildPageGroups(3, "single", undefined, new Set([1]))).toEqual([[0], [1], [2]]);
+  });
+});
+
 describe("groupIndexForPage", () => {
   const groups = buildPageGroups(7, "spread"); // [[0], [1,2], [3,4], [5,6]]
 
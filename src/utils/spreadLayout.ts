@@ -7,6 +7,9 @@
  * - "triple": three pages per group, no cover
  * - "fit":   fitPageCount pages per group, no cover
  *
+ * Solo pages (covers, two-page spreads stored as one image) always get a group of their own,
+ * and grouping restarts after them.
+ *
  * RTL/LTR reading direction is applied at render time.
  */
 
@@ -25,11 +28,13 @@ const DEFAULT_FIT_PAGE_COUNT = 2;
  * - "spread": first page alone (cover), then pairs
  * - "triple": three pages per group, no cover
  * - "fit":   fitPageCount pages per group, no cover
+ * Pages in soloPages are shown alone in every mode.
  */
 export function buildPageGroups(
   totalPages: number,
   mode: ViewMode = "spread",
   fitPageCount: number = DEFAULT_FIT_PAGE_COUNT,
+  soloPages: ReadonlySet<number> = new Set(),
 ): PageGroup[] {
   if (totalPages <= 0) return [];
 
@@ -58,8 +63,13 @@ export function buildPageGroups(
     i = 1;
   }
   while (i < totalPages) {
+    if (soloPages.has(i)) {
+      groups.push([i]);
+      i++;
+      continue;
+    }
     const group: PageGroup = [];
-    for (let k = 0; k < groupSize && i < totalPages; k++, i++) {
+    for (let k = 0; k < groupSize && i < totalPages && !soloPages.has(i); k++, i++) {
       group.push(i);
     }
     groups.push(group);